@@ -1,61 +1,582 @@
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{IsTerminal, Read, Write};
 use std::path::Path;
 
-use clap::Parser;
-use eyre::Result;
+use clap::{Parser, Subcommand};
+use eyre::{eyre, Result};
 
 use duet_charter_lib::chart::Chart;
-use duet_charter_lib::phrases::LyricPhraseCollection;
+use duet_charter_lib::diff::{ChangeKind, ChartDiff};
+use duet_charter_lib::difficulty::chart_difficulties;
+use duet_charter_lib::duet_analysis::DuetAnalysis;
+use duet_charter_lib::merge::ChartMerge;
+use duet_charter_lib::pacing::{PacingReport, PacingThresholds};
+use duet_charter_lib::phrases::{LyricPhraseCollection, Singer};
+use duet_charter_lib::quantize::{snap, SnapGrid, SnapOptions};
+use duet_charter_lib::reduction::generate_difficulties;
+use duet_charter_lib::resolution::rescale;
+use duet_charter_lib::scoring::ScoringTrack;
+use duet_charter_lib::song_ini::{PropertySource, SongIni};
+use duet_charter_lib::star_power_path::{
+    chart_paths, chart_scores, StarPowerPath, DEFAULT_HIT_WINDOW_MICROS,
+};
+use duet_charter_lib::stats::ChartStats;
+use duet_charter_lib::structure::SongStructure;
+use duet_charter_lib::timing::TempoMap;
 
 /// Commandline lyric charting tool for Clone Hero .chart files!
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Source .chart file to make into duet
-    #[clap(value_parser)]
-    source: String,
+    #[clap(value_parser, required = true)]
+    source: Option<String>,
 
     /// Destination to save result to
     #[clap(value_parser)]
     dest: Option<String>,
+
+    /// Name of a section to give to the duet singer, may be repeated
+    #[clap(long = "duet", value_parser)]
+    duet_sections: Vec<String>,
+
+    /// Print how the lines are divided between the main and duet singer
+    #[clap(long, value_parser)]
+    duet_report: bool,
+
+    /// Add Hard, Medium and Easy tracks derived from the Expert tracks where they are missing
+    #[clap(long, value_parser)]
+    generate_difficulties: bool,
+
+    /// Only keep this section, as a standalone practice chart starting at tick 0
+    #[clap(long, value_parser)]
+    excerpt: Option<String>,
+
+    /// Move every event by this many milliseconds, negative to move them earlier
+    #[clap(long, value_parser, allow_hyphen_values = true)]
+    shift_ms: Option<i64>,
+
+    /// Rescale the chart to this many ticks per beat
+    #[clap(long, value_parser)]
+    resolution: Option<u32>,
+
+    /// Snap lyrics and phrase events to a grid of 1/4, 1/8, 1/12, 1/16, 1/24 or 1/32 notes,
+    /// given as 4, 8, 12, 16, 24 or 32
+    #[clap(long, value_parser)]
+    snap: Option<u32>,
+
+    /// Leave events further than this many ticks from the snapping grid where they are
+    #[clap(long, value_parser)]
+    snap_max_ticks: Option<u32>,
+
+    /// Snap notes and special spans as well as lyrics
+    #[clap(long, value_parser)]
+    snap_notes: bool,
+
+    /// Print a table of the phrases that are hard to read or sing along with
+    #[clap(long, value_parser)]
+    pacing: bool,
+
+    /// Syllables per second above which a phrase is flagged
+    #[clap(long, value_parser)]
+    max_syllables_per_second: Option<f64>,
+
+    /// Line length in characters above which a phrase is flagged
+    #[clap(long, value_parser)]
+    max_line_chars: Option<usize>,
+
+    /// Milliseconds between phrase start and first syllable below which a phrase is flagged
+    #[clap(long, value_parser)]
+    min_lead_in_ms: Option<u64>,
+
+    /// Milliseconds on screen below which a phrase is flagged
+    #[clap(long, value_parser)]
+    min_on_screen_ms: Option<u64>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let source_str = String::from(&args.source);
-    let dest_str = String::from(&args.dest.unwrap_or_else(|| "duet.chart".to_owned()));
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print note, tempo and lyric statistics of a chart
+    Stats {
+        /// Source .chart file to measure
+        #[clap(value_parser)]
+        source: String,
 
-    let source = Path::new(&source_str);
-    let dest = Path::new(&dest_str);
+        /// Print the statistics as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+
+        /// Also print the max score and star thresholds, searching the star power path of
+        /// every guitar track
+        #[clap(long, value_parser)]
+        scores: bool,
+    },
+    /// Print where to activate star power for the highest score
+    Path {
+        /// Source .chart file to search
+        #[clap(value_parser)]
+        source: String,
+
+        /// Track to search, such as ExpertSingle; all guitar tracks when left out
+        #[clap(long, value_parser)]
+        track: Option<String>,
+
+        /// Milliseconds a note can be hit early to squeeze it into an activation
+        #[clap(long, value_parser)]
+        hit_window_ms: Option<u64>,
+    },
+    /// Print the differences between two versions of a chart
+    Diff {
+        /// The .chart file to compare from
+        #[clap(value_parser)]
+        old: String,
+
+        /// The .chart file to compare to
+        #[clap(value_parser)]
+        new: String,
+
+        /// Print the differences as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+    },
+    /// Merge two edited versions of a chart, keeping our side of conflicting edits
+    Merge {
+        /// The .chart file both versions were edited from
+        #[clap(value_parser)]
+        base: String,
+
+        /// Our edited .chart file
+        #[clap(value_parser)]
+        ours: String,
 
-    let mut file = fs::File::open(source)?;
+        /// Their edited .chart file
+        #[clap(value_parser)]
+        theirs: String,
+
+        /// Destination to save the merged chart to
+        #[clap(value_parser)]
+        dest: Option<String>,
+    },
+    /// Merge song.ini with the [Song] block of a chart, printing the values they disagree on
+    Ini {
+        /// Source .chart file, left untouched
+        #[clap(value_parser)]
+        source: String,
+
+        /// The song.ini file, next to the chart when left out; created when missing
+        #[clap(long, value_parser)]
+        ini: Option<String>,
+
+        /// Destination to save the chart to when song.ini fills in or overrides its properties
+        #[clap(long, value_parser)]
+        dest: Option<String>,
+
+        /// Side to keep when the two disagree: chart or ini
+        #[clap(long, value_parser)]
+        prefer: Option<String>,
+
+        /// Set the diff_* fields from the estimated difficulty of the Expert tracks
+        #[clap(long, value_parser)]
+        difficulties: bool,
+    },
+}
+
+fn print_duet_report(chart: &Chart) {
+    let analysis = DuetAnalysis::from_chart(chart);
+    let tempo_map = TempoMap::from_chart(chart);
+    for singer in [Singer::Main, Singer::Duet] {
+        println!(
+            "{}: {:.1}% of sung time, {} syllables ({:.1}%)",
+            singer,
+            analysis.time_percentage(singer),
+            analysis.syllables(singer),
+            analysis.syllable_percentage(singer)
+        );
+    }
+    if let Some(stretch) = analysis.get_longest_stretch() {
+        println!(
+            "longest stretch without a switch: {} sings {} phrases for {:.1}s from {:.1}s",
+            stretch.get_singer(),
+            stretch.get_phrase_count(),
+            stretch.get_micros() as f64 / 1_000_000.0,
+            tempo_map.ticks_to_seconds(stretch.get_start_timestamp())
+        );
+    }
+    println!("{} overlapping phrases", analysis.get_overlaps().len());
+    for overlap in analysis.get_overlaps() {
+        println!(
+            "  main phrase at {} overlaps duet phrase at {} from {:.2}s to {:.2}s",
+            overlap.get_main_phrase_start(),
+            overlap.get_duet_phrase_start(),
+            tempo_map.ticks_to_seconds(overlap.get_start_timestamp()),
+            tempo_map.ticks_to_seconds(overlap.get_end_timestamp())
+        );
+    }
+    for absence in analysis.get_absences() {
+        println!(
+            "[{}] has no {} phrases",
+            absence.get_name().unwrap_or("no section"),
+            absence.get_absent()
+        );
+    }
+}
+
+fn print_pacing_table(chart: &Chart, args: &Args) {
+    let defaults = PacingThresholds::default();
+    let thresholds = PacingThresholds {
+        max_syllables_per_second: args
+            .max_syllables_per_second
+            .or(defaults.max_syllables_per_second),
+        max_line_chars: args.max_line_chars.or(defaults.max_line_chars),
+        min_lead_in_micros: args
+            .min_lead_in_ms
+            .map(|millis| millis * 1000)
+            .or(defaults.min_lead_in_micros),
+        min_on_screen_micros: args
+            .min_on_screen_ms
+            .map(|millis| millis * 1000)
+            .or(defaults.min_on_screen_micros),
+    };
+    let tempo_map = TempoMap::from_chart(chart);
+    let report = PacingReport::new(
+        &LyricPhraseCollection::new(chart.get_lyrics()),
+        &tempo_map,
+        &thresholds,
+    );
+    let flagged = report.flagged();
+    println!("{} phrases flagged", flagged.len());
+    if flagged.is_empty() {
+        return;
+    }
+    println!(
+        "{:>9}  {:<6}  {:>6}  {:>5}  {:>8}  {:>9}  {:<40}  text",
+        "time", "singer", "syl/s", "chars", "lead-in", "on screen", "issues"
+    );
+    for phrase in flagged {
+        let seconds = tempo_map.ticks_to_seconds(phrase.get_start_timestamp());
+        let issues: Vec<String> = phrase
+            .get_issues()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        println!(
+            "{:>3}:{:05.2}  {:<6}  {:>6.2}  {:>5}  {:>6}ms  {:>7}ms  {:<40}  {}",
+            (seconds / 60.0).floor(),
+            seconds % 60.0,
+            phrase.get_singer(),
+            phrase.get_syllables_per_second(),
+            phrase.get_line_chars(),
+            phrase.get_lead_in_micros() / 1000,
+            phrase.get_on_screen_micros() / 1000,
+            issues.join(", "),
+            phrase.get_text()
+        );
+    }
+}
+
+fn read_chart(path: &str) -> Result<Chart> {
+    let mut file = fs::File::open(Path::new(path))?;
     let mut file_str = String::new();
     file.read_to_string(&mut file_str)?;
-    let chart = Chart::new(&file_str)?;
-    let phrases = LyricPhraseCollection::new(chart.get_lyrics());
-    println!("main: {:?}", phrases.get_main_phrases());
-    println!("duet: {:?}", phrases.get_duet_phrases());
-    let mut out_file = fs::File::create(dest)?;
-    let byte_count_main = out_file.write(
-        phrases
-            .get_main_phrases()
-            .iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<String>>()
-            .join("\r\n")
-            .as_bytes(),
-    )?;
-    let byte_count_duet = out_file.write(
-        phrases
-            .get_duet_phrases()
+    Chart::new(&file_str)
+}
+
+fn print_stats(source: &str, json: bool, scores: bool) -> Result<()> {
+    let chart = read_chart(source)?;
+    let stats = ChartStats::new(&chart);
+    if json {
+        println!("{}", stats.to_json()?);
+        return Ok(());
+    }
+    let seconds = stats.get_song_length_seconds();
+    println!(
+        "length: {}:{:05.2}",
+        (seconds / 60.0).floor(),
+        seconds % 60.0
+    );
+    if (stats.get_max_bpm() - stats.get_min_bpm()).abs() < f64::EPSILON {
+        println!("bpm: {}", stats.get_min_bpm());
+    } else {
+        println!("bpm: {} - {}", stats.get_min_bpm(), stats.get_max_bpm());
+    }
+    println!(
+        "lyrics: {}, main phrases: {}, duet phrases: {}, sections: {}",
+        stats.get_lyrics(),
+        stats.get_main_phrases(),
+        stats.get_duet_phrases(),
+        stats.get_sections()
+    );
+    if stats.get_tracks().is_empty() {
+        return Ok(());
+    }
+    println!(
+        "{:<20}  {:>6}  {:>6}  {:>8}  {:>9}  {:>3}  {:>7}  {:>7}  {:>4}",
+        "track", "notes", "chords", "sustains", "sustain s", "sp", "avg nps", "peak nps", "tier"
+    );
+    for (track, difficulty) in stats.get_tracks().iter().zip(chart_difficulties(&chart)) {
+        println!(
+            "{:<20}  {:>6}  {:>6}  {:>8}  {:>9.1}  {:>3}  {:>7.2}  {:>8}  {:>4}",
+            track.get_name(),
+            track.get_notes(),
+            track.get_chords(),
+            track.get_sustains(),
+            track.get_sustain_seconds(),
+            track.get_star_power_phrases(),
+            track.get_average_nps(),
+            track.get_peak_nps(),
+            difficulty.get_tier()
+        );
+    }
+    if !scores {
+        return Ok(());
+    }
+    for score in chart_scores(&chart) {
+        let stars: Vec<String> = score
+            .get_star_thresholds()
             .iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<String>>()
-            .join("\r\n")
-            .as_bytes(),
-    )?;
-    println!("{} bytes written", byte_count_main + byte_count_duet);
+            .map(ToString::to_string)
+            .collect();
+        println!(
+            "{}: max score {}, {} without star power, stars at {}",
+            score.get_name(),
+            score.get_max_score(),
+            score.get_no_star_power_score(),
+            stars.join(" / ")
+        );
+    }
+    Ok(())
+}
+
+fn print_paths(source: &str, track: Option<&str>, hit_window_ms: Option<u64>) -> Result<()> {
+    let chart = read_chart(source)?;
+    let hit_window_micros = hit_window_ms.map_or(DEFAULT_HIT_WINDOW_MICROS, |millis| millis * 1000);
+    let paths = match track {
+        Some(name) => vec![StarPowerPath::new(
+            &ScoringTrack::from_chart(&chart, name)
+                .ok_or_else(|| eyre!("No guitar track named {:?}", name))?,
+            hit_window_micros,
+        )],
+        None => chart_paths(&chart, hit_window_micros),
+    };
+    for path in paths {
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+fn print_diff(old: &str, new: &str, json: bool) -> Result<()> {
+    let diff = ChartDiff::new(&read_chart(old)?, &read_chart(new)?);
+    if json {
+        println!("{}", diff.to_json()?);
+        return Ok(());
+    }
+    if diff.is_empty() {
+        println!("no differences");
+        return Ok(());
+    }
+    let colour = std::io::stdout().is_terminal();
+    for change in diff.get_changes() {
+        if colour {
+            let code = match change.get_kind() {
+                ChangeKind::Added => 32,
+                ChangeKind::Removed => 31,
+                ChangeKind::Changed => 33,
+            };
+            println!("\x1b[{}m{}\x1b[0m", code, change);
+        } else {
+            println!("{}", change);
+        }
+    }
+    Ok(())
+}
+
+fn merge_charts(base: &str, ours: &str, theirs: &str, dest: Option<&str>) -> Result<()> {
+    let merge = ChartMerge::new(&read_chart(base)?, &read_chart(ours)?, &read_chart(theirs)?);
+    for conflict in merge.get_conflicts() {
+        println!("conflict: {}", conflict);
+    }
+    let merged = merge.get_chart().to_string();
+    fs::write(dest.unwrap_or("merged.chart"), &merged)?;
+    println!("{} bytes written", merged.len());
+    if merge.is_clean() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "{} conflicts, kept our side of them",
+            merge.get_conflicts().len()
+        ))
+    }
+}
+
+fn sync_ini(
+    source: &str,
+    ini: Option<&str>,
+    dest: Option<&str>,
+    prefer: Option<&str>,
+    difficulties: bool,
+) -> Result<()> {
+    let prefer = match prefer {
+        None => None,
+        Some("chart") => Some(PropertySource::Chart),
+        Some("ini") => Some(PropertySource::SongIni),
+        Some(other) => return Err(eyre!("Cannot prefer {:?}, expected chart or ini", other)),
+    };
+    let ini_path = ini.map_or_else(
+        || Path::new(source).with_file_name("song.ini"),
+        |path| Path::new(path).to_path_buf(),
+    );
+    let mut song_ini = if ini_path.exists() {
+        SongIni::new(&fs::read_to_string(&ini_path)?)?
+    } else {
+        SongIni::default()
+    };
+    let mut chart = read_chart(source)?;
+    let original = chart.clone();
+
+    for conflict in song_ini.sync_with_chart(&mut chart, prefer) {
+        println!("conflict: {}", conflict);
+    }
+    if difficulties {
+        song_ini.update_difficulties(&chart);
+    }
+    fs::write(&ini_path, song_ini.to_string())?;
+    println!("{} written", ini_path.display());
+    if chart != original {
+        match dest {
+            Some(dest) => {
+                fs::write(dest, chart.to_string())?;
+                println!("{} written", dest);
+            }
+            None => println!("chart properties changed, pass --dest to save the chart"),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    match &args.command {
+        Some(Command::Stats {
+            source,
+            json,
+            scores,
+        }) => return print_stats(source, *json, *scores),
+        Some(Command::Path {
+            source,
+            track,
+            hit_window_ms,
+        }) => return print_paths(source, track.as_deref(), *hit_window_ms),
+        Some(Command::Diff { old, new, json }) => return print_diff(old, new, *json),
+        Some(Command::Merge {
+            base,
+            ours,
+            theirs,
+            dest,
+        }) => return merge_charts(base, ours, theirs, dest.as_deref()),
+        Some(Command::Ini {
+            source,
+            ini,
+            dest,
+            prefer,
+            difficulties,
+        }) => {
+            return sync_ini(
+                source,
+                ini.as_deref(),
+                dest.as_deref(),
+                prefer.as_deref(),
+                *difficulties,
+            )
+        }
+        None => {}
+    }
+    let dest_str = String::from(args.dest.as_deref().unwrap_or("duet.chart"));
+    let dest = Path::new(&dest_str);
+
+    let source = args
+        .source
+        .as_deref()
+        .ok_or_else(|| eyre!("No source chart given"))?;
+    let mut chart = read_chart(source)?;
+
+    let structure = SongStructure::new(chart.get_lyrics());
+    for name in &args.duet_sections {
+        let mut sections = structure.sections_named(name).peekable();
+        if sections.peek().is_none() {
+            return Err(eyre!("No section named {:?}", name));
+        }
+        for section in sections {
+            section.assign_to(chart.get_lyrics_mut(), Singer::Duet);
+        }
+    }
+
+    let structure = SongStructure::new(chart.get_lyrics());
+    for section in structure.get_sections() {
+        println!(
+            "[{}] main: {} phrases, duet: {} phrases",
+            section.get_name().unwrap_or("no section"),
+            section.phrase_count(Singer::Main),
+            section.phrase_count(Singer::Duet)
+        );
+        for phrase in section.get_phrases(Singer::Main) {
+            println!("  main: {}", phrase);
+        }
+        for phrase in section.get_phrases(Singer::Duet) {
+            println!("  duet: {}", phrase);
+        }
+    }
+
+    if let Some(name) = &args.excerpt {
+        chart = chart
+            .slice_section(name)
+            .ok_or_else(|| eyre!("No section named {:?}", name))?;
+    }
+    if let Some(millis) = args.shift_ms {
+        chart.shift_millis(millis);
+    }
+    if let Some(resolution) = args.resolution {
+        for collision in rescale(&mut chart, resolution) {
+            println!("{}", collision);
+        }
+    }
+    if let Some(step) = args.snap {
+        let grid = SnapGrid::from_step(step)
+            .ok_or_else(|| eyre!("No snapping grid of 1/{} notes", step))?;
+        let report = snap(
+            &mut chart,
+            &SnapOptions {
+                grid,
+                max_distance: args.snap_max_ticks,
+                notes: args.snap_notes,
+            },
+        );
+        println!("snapped {} events", report.get_moves().len());
+        for collision in report.get_collisions() {
+            println!("{}", collision);
+        }
+        for crossing in report.get_crossings() {
+            println!("{}", crossing);
+        }
+    }
+    if args.generate_difficulties {
+        for track in generate_difficulties(&mut chart, false) {
+            println!("generated [{}]", track);
+        }
+    }
+    if args.duet_report {
+        print_duet_report(&chart);
+    }
+    if args.pacing {
+        print_pacing_table(&chart, &args);
+    }
+
+    let mut out_file = fs::File::create(dest)?;
+    let byte_count = out_file.write(chart.to_string().as_bytes())?;
+    println!("{} bytes written", byte_count);
 
     Ok(())
 }
@@ -0,0 +1,67 @@
+use std::fs;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use duet_charter_lib::chart::{Chart, LyricEvent};
+use duet_charter_lib::phrases::LyricPhraseCollection;
+use duet_charter_lib::TimestampedEvent;
+
+const FULL_ALBUM: &str =
+    "../charts/Dream Theater - Scenes From a Memory (Full Album) [Peddy]/notes.chart";
+
+type RawPhrase = (u32, u32, Vec<(u32, String)>);
+
+/// Phrase construction as it was before the single pass rewrite, rescanning every event per phrase.
+fn rescanning_phrases(lyric_events: &[LyricEvent]) -> Vec<RawPhrase> {
+    let timestamps: Vec<u32> = lyric_events
+        .iter()
+        .filter_map(|x| match x {
+            LyricEvent::PhraseStart { timestamp } => Some(*timestamp),
+            _ => None,
+        })
+        .collect();
+    timestamps
+        .iter()
+        .enumerate()
+        .map(|(i, &low)| {
+            let high = timestamps.get(i + 1).copied();
+            let lyrics: Vec<(u32, String)> = lyric_events
+                .iter()
+                .filter(|x| high.is_none_or(|h| h > x.get_timestamp()) && x.get_timestamp() >= low)
+                .filter_map(|x| match x {
+                    LyricEvent::Lyric { timestamp, text } => Some((*timestamp, text.clone())),
+                    _ => None,
+                })
+                .collect();
+            let end = lyric_events
+                .iter()
+                .find(|x| {
+                    high.is_none_or(|h| h >= x.get_timestamp())
+                        && x.get_timestamp() > low
+                        && matches!(x, LyricEvent::PhraseEnd { .. })
+                })
+                .map(TimestampedEvent::get_timestamp)
+                .or(high)
+                .unwrap_or_else(|| lyrics.last().map_or(low + 1, |(t, _)| t + 1));
+            (low, end, lyrics)
+        })
+        .collect()
+}
+
+fn phrase_construction(c: &mut Criterion) {
+    let file_content = fs::read_to_string(FULL_ALBUM).expect("file reading failed");
+    let chart = Chart::new(&file_content).expect("chart parsing failed");
+    let lyrics = chart.get_lyrics();
+
+    let mut group = c.benchmark_group("full album phrases");
+    group.bench_function("single pass", |b| {
+        b.iter(|| LyricPhraseCollection::new(black_box(lyrics)));
+    });
+    group.bench_function("rescanning", |b| {
+        b.iter(|| rescanning_phrases(black_box(lyrics)));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, phrase_construction);
+criterion_main!(benches);
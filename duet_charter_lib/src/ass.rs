@@ -0,0 +1,410 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chart::Chart;
+use crate::phrases::{Phrase, Singer};
+use crate::structure::SongStructure;
+use crate::timing::TempoMap;
+
+/// Look of one singer's lines in an ASS script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssStyle {
+    pub name: String,
+    pub font_name: String,
+    pub font_size: u32,
+    /// Colour of sung syllables, as ASS `&HAABBGGRR`.
+    pub primary_colour: String,
+    /// Colour of syllables still to be sung, as ASS `&HAABBGGRR`.
+    pub secondary_colour: String,
+    /// Numpad style position of the lines, 2 is bottom centre and 8 is top centre.
+    pub alignment: u8,
+    pub margin_vertical: u32,
+}
+
+impl AssStyle {
+    #[must_use]
+    pub fn main() -> Self {
+        Self {
+            name: "Main".to_string(),
+            font_name: "Arial".to_string(),
+            font_size: 64,
+            primary_colour: "&H0000D7FF".to_string(),
+            secondary_colour: "&H00FFFFFF".to_string(),
+            alignment: 2,
+            margin_vertical: 60,
+        }
+    }
+
+    #[must_use]
+    pub fn duet() -> Self {
+        Self {
+            name: "Duet".to_string(),
+            font_name: "Arial".to_string(),
+            font_size: 64,
+            primary_colour: "&H00FFD700".to_string(),
+            secondary_colour: "&H00FFFFFF".to_string(),
+            alignment: 8,
+            margin_vertical: 60,
+        }
+    }
+}
+
+/// Settings for writing an ASS karaoke script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssOptions {
+    /// Use `\kf`, sweeping the colour across each syllable, instead of `\k`, which switches the
+    /// colour at the start of the syllable.
+    pub sweep: bool,
+    pub main_style: AssStyle,
+    pub duet_style: AssStyle,
+    pub resolution: (u32, u32),
+}
+
+impl Default for AssOptions {
+    fn default() -> Self {
+        Self {
+            sweep: false,
+            main_style: AssStyle::main(),
+            duet_style: AssStyle::duet(),
+            resolution: (1920, 1080),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AssEvent {
+    Comment {
+        start_centis: u64,
+        end_centis: u64,
+        text: String,
+    },
+    Dialogue {
+        start_centis: u64,
+        end_centis: u64,
+        singer: Singer,
+        text: String,
+    },
+}
+
+/// A karaoke script in the Advanced SubStation Alpha format, one `Dialogue` line per phrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssScript {
+    title: Option<String>,
+    options: AssOptions,
+    events: Vec<AssEvent>,
+}
+
+impl AssScript {
+    /// Constructor for `AssScript` from the sections and phrases of a song.
+    ///
+    /// Every phrase becomes a `Dialogue` line in its singer's style, with a karaoke tag giving
+    /// each syllable's duration up to the next syllable or the phrase end. Every named section
+    /// becomes a `Comment` line spanning the section.
+    ///
+    /// # Arguments
+    ///
+    /// * `structure`: the sections and phrases of the song.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    /// * `options`: the styles and karaoke settings.
+    ///
+    /// returns: `AssScript`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::ass::{AssOptions, AssScript};
+    /// use duet_charter_lib::chart::Chart;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let ass_file = AssScript::from_chart(&chart, &AssOptions::default()).to_string();
+    /// ```
+    #[must_use]
+    pub fn new(structure: &SongStructure, tempo_map: &TempoMap, options: &AssOptions) -> Self {
+        let centis = |timestamp: u32| (tempo_map.ticks_to_micros(timestamp) + 5_000) / 10_000;
+        let tag = if options.sweep { "kf" } else { "k" };
+        let mut events = vec![];
+        for section in structure.get_sections() {
+            if let Some(name) = section.get_name() {
+                events.push(AssEvent::Comment {
+                    start_centis: centis(section.get_start_timestamp()),
+                    end_centis: centis(section.get_end_timestamp()),
+                    text: escape_ass(name),
+                });
+            }
+            let mut dialogues = vec![];
+            for singer in [Singer::Main, Singer::Duet] {
+                for phrase in section.get_phrases(singer) {
+                    if phrase.get_lyrics().is_empty() {
+                        continue;
+                    }
+                    dialogues.push(AssEvent::Dialogue {
+                        start_centis: centis(phrase.get_start_timestamp()),
+                        end_centis: centis(phrase.get_end_timestamp()),
+                        singer,
+                        text: Self::karaoke_text(phrase, tag, centis),
+                    });
+                }
+            }
+            dialogues.sort_by_key(|event| match event {
+                AssEvent::Comment { start_centis, .. }
+                | AssEvent::Dialogue { start_centis, .. } => *start_centis,
+            });
+            events.extend(dialogues);
+        }
+        Self {
+            title: None,
+            options: options.clone(),
+            events,
+        }
+    }
+
+    /// Constructor for `AssScript` from a chart, titled after its `Name` property.
+    #[must_use]
+    pub fn from_chart(chart: &Chart, options: &AssOptions) -> Self {
+        let mut script = Self::new(
+            &SongStructure::new(chart.get_lyrics()),
+            &TempoMap::from_chart(chart),
+            options,
+        );
+        script.title = chart
+            .get_properties()
+            .get("Name")
+            .map(|name| name.trim().trim_matches('"').to_string());
+        script
+    }
+
+    fn karaoke_text(phrase: &Phrase, tag: &str, centis: impl Fn(u32) -> u64) -> String {
+        let syllables = phrase.get_display_syllables();
+        let phrase_start = centis(phrase.get_start_timestamp());
+        let phrase_end = centis(phrase.get_end_timestamp());
+        let mut text = String::new();
+        let first = syllables
+            .first()
+            .map_or(phrase_start, |(timestamp, _, _)| centis(*timestamp));
+        if first > phrase_start {
+            text.push_str(&format!("{{\\{}{}}}", tag, first - phrase_start));
+        }
+        let mut separator = "";
+        for (i, (timestamp, syllable, joined)) in syllables.iter().enumerate() {
+            let start = centis(*timestamp);
+            let end = syllables
+                .get(i + 1)
+                .map_or(phrase_end, |(next, _, _)| centis(*next));
+            text.push_str(separator);
+            text.push_str(&format!("{{\\{}{}}}", tag, end.saturating_sub(start)));
+            text.push_str(&escape_ass(syllable));
+            separator = if *joined { "" } else { " " };
+        }
+        text
+    }
+
+    fn write_style(f: &mut Formatter<'_>, style: &AssStyle) -> std::fmt::Result {
+        write!(
+            f,
+            "Style: {},{},{},{},{},&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,0,{},40,40,{},1\r\n",
+            style.name,
+            style.font_name,
+            style.font_size,
+            style.primary_colour,
+            style.secondary_colour,
+            style.alignment,
+            style.margin_vertical
+        )
+    }
+}
+
+impl Display for AssScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[Script Info]\r\n")?;
+        if let Some(title) = &self.title {
+            write!(f, "Title: {}\r\n", title)?;
+        }
+        write!(
+            f,
+            "ScriptType: v4.00+\r\nPlayResX: {}\r\nPlayResY: {}\r\nWrapStyle: 0\r\n\
+             ScaledBorderAndShadow: yes\r\n\r\n",
+            self.options.resolution.0, self.options.resolution.1
+        )?;
+        write!(
+            f,
+            "[V4+ Styles]\r\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+             OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, \
+             Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, \
+             Encoding\r\n"
+        )?;
+        Self::write_style(f, &self.options.main_style)?;
+        Self::write_style(f, &self.options.duet_style)?;
+        write!(
+            f,
+            "\r\n[Events]\r\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, \
+             Effect, Text\r\n"
+        )?;
+        for event in &self.events {
+            match event {
+                AssEvent::Comment {
+                    start_centis,
+                    end_centis,
+                    text,
+                } => write!(
+                    f,
+                    "Comment: 0,{},{},{},,0,0,0,section,{}\r\n",
+                    format_ass_time(*start_centis),
+                    format_ass_time(*end_centis),
+                    self.options.main_style.name,
+                    text
+                )?,
+                AssEvent::Dialogue {
+                    start_centis,
+                    end_centis,
+                    singer,
+                    text,
+                } => {
+                    let style = match singer {
+                        Singer::Main => &self.options.main_style,
+                        Singer::Duet => &self.options.duet_style,
+                    };
+                    write!(
+                        f,
+                        "Dialogue: 0,{},{},{},,0,0,0,karaoke,{}\r\n",
+                        format_ass_time(*start_centis),
+                        format_ass_time(*end_centis),
+                        style.name,
+                        text
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_ass_time(centis: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6_000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// Keeps lyric text from being read as override blocks or line breaks.
+fn escape_ass(text: &str) -> String {
+    text.replace('{', "(").replace('}', ")").replace('\\', "/")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chart::{LyricEvent, TempoEvent};
+
+    use super::*;
+
+    #[test]
+    fn karaoke_export() {
+        let events = vec![
+            LyricEvent::Section {
+                timestamp: 0,
+                text: "Verse 1".to_string(),
+            },
+            LyricEvent::PhraseStart { timestamp: 192 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+            LyricEvent::DuetPhraseStart { timestamp: 768 },
+            LyricEvent::DuetLyric {
+                timestamp: 768,
+                text: "Bye".to_string(),
+            },
+            LyricEvent::DuetPhraseEnd { timestamp: 960 },
+        ];
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let script = AssScript::new(
+            &SongStructure::new(&events),
+            &tempo_map,
+            &AssOptions {
+                sweep: true,
+                ..AssOptions::default()
+            },
+        )
+        .to_string();
+        let event_lines: Vec<&str> = script
+            .lines()
+            .skip_while(|line| *line != "[Events]")
+            .skip(2)
+            .collect();
+        assert_eq!(
+            event_lines,
+            vec![
+                "Comment: 0,0:00:00.00,0:00:02.50,Main,,0,0,0,section,Verse 1",
+                "Dialogue: 0,0:00:00.50,0:00:02.00,Main,,0,0,0,karaoke,\
+                 {\\kf50}{\\kf25}Hel{\\kf25}lo {\\kf50}world",
+                "Dialogue: 0,0:00:02.00,0:00:02.50,Duet,,0,0,0,karaoke,{\\kf50}Bye",
+            ]
+        );
+        assert!(script.contains("Style: Duet,Arial,64,&H00FFD700,&H00FFFFFF"));
+    }
+
+    #[test]
+    fn karaoke_chart_markup() {
+        let events = vec![
+            LyricEvent::PhraseStart { timestamp: 384 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo#".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 528,
+                text: "+".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+        ];
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let script = AssScript::new(
+            &SongStructure::new(&events),
+            &tempo_map,
+            &AssOptions::default(),
+        )
+        .to_string();
+        assert_eq!(
+            script.lines().last(),
+            Some("Dialogue: 0,0:00:01.00,0:00:02.00,Main,,0,0,0,karaoke,{\\k25}Hel{\\k25}lo {\\k50}world")
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 use eyre::{eyre, Result, WrapErr};
 use regex::Regex;
@@ -11,6 +12,44 @@ use LyricEvent::{
 use TempoEvent::{Anchor, Beat, OtherTempoEvent, TimeSignature};
 use crate::TimestampedEvent;
 
+/// Order in which well known `[Song]` properties are written, following Moonscraper.
+const PROPERTY_ORDER: [&str; 21] = [
+    "Name",
+    "Artist",
+    "Charter",
+    "Album",
+    "Year",
+    "Offset",
+    "Resolution",
+    "Player2",
+    "Difficulty",
+    "PreviewStart",
+    "PreviewEnd",
+    "Genre",
+    "MediaType",
+    "MusicStream",
+    "GuitarStream",
+    "BassStream",
+    "RhythmStream",
+    "DrumStream",
+    "VocalStream",
+    "KeysStream",
+    "CrowdStream",
+];
+
+const DIFFICULTY_ORDER: [&str; 4] = ["Expert", "Hard", "Medium", "Easy"];
+
+const INSTRUMENT_ORDER: [&str; 8] = [
+    "Single",
+    "DoubleGuitar",
+    "DoubleBass",
+    "DoubleRhythm",
+    "Keyboard",
+    "Drums",
+    "GHLGuitar",
+    "GHLBass",
+];
+
 macro_rules! read_capture {
     ($captures:expr, $name:expr) => {
         $captures
@@ -26,7 +65,7 @@ macro_rules! parse {
     };
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LyricEvent {
     PhraseStart {
         timestamp: u32,
@@ -72,9 +111,34 @@ impl TimestampedEvent for LyricEvent {
             | DuetLyric { timestamp, .. } => *timestamp,
         }
     }
+
+    fn set_timestamp(&mut self, new_timestamp: u32) {
+        match self {
+            PhraseStart { timestamp, .. }
+            | PhraseEnd { timestamp, .. }
+            | Lyric { timestamp, .. }
+            | Section { timestamp, .. }
+            | OtherLyricEvent { timestamp, .. }
+            | DuetPhraseStart { timestamp, .. }
+            | DuetPhraseEnd { timestamp, .. }
+            | DuetLyric { timestamp, .. } => *timestamp = new_timestamp,
+        }
+    }
 }
 
-#[derive(Debug)]
+impl LyricEvent {
+    /// Ordering of events sharing a timestamp: a phrase end closes the phrase before it,
+    /// and a lyric belongs to a phrase starting on the same tick.
+    pub(crate) const fn processing_rank(&self) -> u8 {
+        match self {
+            PhraseEnd { .. } | DuetPhraseEnd { .. } => 0,
+            PhraseStart { .. } | DuetPhraseStart { .. } => 1,
+            Lyric { .. } | DuetLyric { .. } | Section { .. } | OtherLyricEvent { .. } => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KeyPressEvent {
     Note {
         timestamp: u32,
@@ -106,9 +170,18 @@ impl TimestampedEvent for KeyPressEvent {
             | OtherKeyPress { timestamp, .. } => *timestamp,
         }
     }
+
+    fn set_timestamp(&mut self, new_timestamp: u32) {
+        match self {
+            Note { timestamp, .. }
+            | Special { timestamp, .. }
+            | TextEvent { timestamp, .. }
+            | OtherKeyPress { timestamp, .. } => *timestamp = new_timestamp,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TempoEvent {
     Beat {
         timestamp: u32,
@@ -138,9 +211,30 @@ impl TimestampedEvent for TempoEvent {
             | OtherTempoEvent { timestamp, .. } => *timestamp,
         }
     }
+
+    fn set_timestamp(&mut self, new_timestamp: u32) {
+        match self {
+            Beat { timestamp, .. }
+            | TimeSignature { timestamp, .. }
+            | Anchor { timestamp, .. }
+            | OtherTempoEvent { timestamp, .. } => *timestamp = new_timestamp,
+        }
+    }
+}
+
+impl TempoEvent {
+    /// Order of tempo events on the same tick, as Moonscraper writes them.
+    pub(crate) const fn write_rank(&self) -> u8 {
+        match self {
+            TimeSignature { .. } => 0,
+            Anchor { .. } => 1,
+            Beat { .. } => 2,
+            OtherTempoEvent { .. } => 3,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chart {
     properties: HashMap<String, String>,
     lyrics: Vec<LyricEvent>,
@@ -193,7 +287,7 @@ impl Chart {
         for section in chart_file.split('}') {
             let header = match header_regex.find(section) {
                 None => continue,
-                Some(x) => x.as_str().replace('[', "").replace(']', ""),
+                Some(x) => x.as_str().replace(['[', ']'], ""),
             };
             match header.as_str() {
                 "Song" => Self::decode_properties(&mut properties, section)?,
@@ -210,6 +304,23 @@ impl Chart {
         })
     }
 
+    /// Creates a chart struct from already decoded parts, such as a chart converted from another
+    /// format.
+    #[must_use]
+    pub fn from_parts(
+        properties: HashMap<String, String>,
+        lyrics: Vec<LyricEvent>,
+        tempo_map: Vec<TempoEvent>,
+        key_presses: HashMap<String, Vec<KeyPressEvent>>,
+    ) -> Self {
+        Self {
+            properties,
+            lyrics,
+            tempo_map,
+            key_presses,
+        }
+    }
+
     fn decode_properties(properties: &mut HashMap<String, String>, section: &str) -> Result<()> {
         Regex::new(r" {2}(?P<property>[^ =]+) = (?P<content>[^\n\r]+)")?
             .captures_iter(section)
@@ -355,7 +466,7 @@ impl Chart {
                 }
             })
             .collect::<Result<Vec<_>>>()?;
-        key_presses.insert(header.replace('[', "").replace(']', ""), new_notes);
+        key_presses.insert(header.replace(['[', ']'], ""), new_notes);
         Ok(())
     }
 
@@ -369,15 +480,194 @@ impl Chart {
         &self.lyrics
     }
 
+    /// Number of ticks per beat, from the `Resolution` property, defaulting to 192.
+    #[must_use]
+    pub fn get_resolution(&self) -> u32 {
+        self.properties
+            .get("Resolution")
+            .and_then(|resolution| resolution.trim().parse().ok())
+            .unwrap_or(192)
+    }
+
+    #[must_use]
+    pub fn get_lyrics_mut(&mut self) -> &mut Vec<LyricEvent> {
+        &mut self.lyrics
+    }
+
+    /// Replaces all phrase and lyric events of both singers with `vocal_events`, keeping sections
+    /// and other events in place. The result is ordered by timestamp, with phrase ends before
+    /// phrase starts before lyrics on the same tick, so readers going in file order put every
+    /// lyric in the right phrase.
+    pub fn replace_vocal_events(&mut self, vocal_events: Vec<LyricEvent>) {
+        self.lyrics
+            .retain(|event| matches!(event, Section { .. } | OtherLyricEvent { .. }));
+        self.lyrics.extend(vocal_events);
+        self.lyrics
+            .sort_by_key(|event| (event.get_timestamp(), event.processing_rank()));
+    }
+
+    /// Sets a `[Song]` property such as `Resolution`, returning the value it replaces.
+    pub fn set_property(&mut self, name: &str, value: String) -> Option<String> {
+        self.properties.insert(name.to_string(), value)
+    }
+
     #[must_use]
     pub const fn get_tempo_map(&self) -> &Vec<TempoEvent> {
         &self.tempo_map
     }
 
+    #[must_use]
+    pub fn get_tempo_map_mut(&mut self) -> &mut Vec<TempoEvent> {
+        &mut self.tempo_map
+    }
+
     #[must_use]
     pub const fn get_key_presses(&self) -> &HashMap<String, Vec<KeyPressEvent>> {
         &self.key_presses
     }
+
+    #[must_use]
+    pub fn get_key_presses_mut(&mut self) -> &mut HashMap<String, Vec<KeyPressEvent>> {
+        &mut self.key_presses
+    }
+
+    /// Sets the events of a note track such as `HardSingle`, returning the events it replaces.
+    pub fn insert_track(
+        &mut self,
+        name: &str,
+        events: Vec<KeyPressEvent>,
+    ) -> Option<Vec<KeyPressEvent>> {
+        self.key_presses.insert(name.to_string(), events)
+    }
+
+    /// Sort key placing note tracks in the order Moonscraper writes them, unknown tracks last.
+    pub(crate) fn track_order(name: &str) -> (usize, usize, String) {
+        INSTRUMENT_ORDER
+            .iter()
+            .enumerate()
+            .find_map(|(instrument_index, instrument)| {
+                let difficulty = name.strip_suffix(instrument)?;
+                let difficulty_index = DIFFICULTY_ORDER.iter().position(|x| *x == difficulty)?;
+                Some((instrument_index, difficulty_index, String::new()))
+            })
+            .unwrap_or((INSTRUMENT_ORDER.len(), 0, name.to_string()))
+    }
+}
+
+impl Display for LyricEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhraseStart { timestamp } => write!(f, "{} = E \"phrase_start\"", timestamp),
+            PhraseEnd { timestamp } => write!(f, "{} = E \"phrase_end\"", timestamp),
+            Lyric { timestamp, text } => write!(f, "{} = E \"lyric {}\"", timestamp, text),
+            Section { timestamp, text } => write!(f, "{} = E \"section {}\"", timestamp, text),
+            DuetPhraseStart { timestamp } => {
+                write!(f, "{} = E \"duet_phrase_start\"", timestamp)
+            }
+            DuetPhraseEnd { timestamp } => write!(f, "{} = E \"duet_phrase_end\"", timestamp),
+            DuetLyric { timestamp, text } => {
+                write!(f, "{} = E \"duet_lyric {}\"", timestamp, text)
+            }
+            OtherLyricEvent {
+                code,
+                timestamp,
+                content,
+            } => write!(f, "{} = {} \"{}\"", timestamp, code, content),
+        }
+    }
+}
+
+impl Display for TempoEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Beat {
+                timestamp,
+                milli_bpm,
+            } => write!(f, "{} = B {}", timestamp, milli_bpm),
+            TimeSignature {
+                timestamp,
+                time_signature: (numerator, 4),
+            } => write!(f, "{} = TS {}", timestamp, numerator),
+            TimeSignature {
+                timestamp,
+                time_signature: (numerator, denominator),
+            } => write!(
+                f,
+                "{} = TS {} {}",
+                timestamp,
+                numerator,
+                denominator.trailing_zeros()
+            ),
+            Anchor {
+                timestamp,
+                song_microseconds,
+            } => write!(f, "{} = A {}", timestamp, song_microseconds),
+            OtherTempoEvent {
+                code,
+                timestamp,
+                content,
+            } => write!(f, "{} = {} {}", timestamp, code, content),
+        }
+    }
+}
+
+impl Display for KeyPressEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Note {
+                timestamp,
+                duration,
+                key,
+            } => write!(f, "{} = N {} {}", timestamp, key, duration),
+            Special {
+                timestamp,
+                special_type,
+                duration,
+            } => write!(f, "{} = S {} {}", timestamp, special_type, duration),
+            TextEvent { timestamp, content } => write!(f, "{} = E {}", timestamp, content),
+            OtherKeyPress {
+                code,
+                timestamp,
+                content,
+            } => write!(f, "{} = {} {}", timestamp, code, content),
+        }
+    }
+}
+
+impl Display for Chart {
+    /// Serialises the chart back into the .chart file format.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut properties: Vec<(&String, &String)> = self.properties.iter().collect();
+        properties.sort_by_key(|(name, _)| {
+            PROPERTY_ORDER
+                .iter()
+                .position(|x| x == name)
+                .map_or((PROPERTY_ORDER.len(), name.as_str()), |i| (i, ""))
+        });
+        write!(f, "[Song]\r\n{{\r\n")?;
+        for (name, value) in properties {
+            write!(f, "  {} = {}\r\n", name, value)?;
+        }
+        write!(f, "}}\r\n[SyncTrack]\r\n{{\r\n")?;
+        for event in &self.tempo_map {
+            write!(f, "  {}\r\n", event)?;
+        }
+        write!(f, "}}\r\n[Events]\r\n{{\r\n")?;
+        for event in &self.lyrics {
+            write!(f, "  {}\r\n", event)?;
+        }
+        write!(f, "}}\r\n")?;
+        let mut tracks: Vec<(&String, &Vec<KeyPressEvent>)> = self.key_presses.iter().collect();
+        tracks.sort_by_key(|(name, _)| Self::track_order(name));
+        for (name, events) in tracks {
+            write!(f, "[{}]\r\n{{\r\n", name)?;
+            for event in events {
+                write!(f, "  {}\r\n", event)?;
+            }
+            write!(f, "}}\r\n")?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -412,4 +702,29 @@ mod test {
         Chart::new(&file_content)?;
         Ok(())
     }
+
+    #[test]
+    fn round_trip_test() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            round_trip_test_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn round_trip_test_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        assert_eq!(Chart::new(&chart.to_string())?, chart);
+        Ok(())
+    }
 }
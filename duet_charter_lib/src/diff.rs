@@ -0,0 +1,916 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+
+use eyre::Result;
+use serde::Serialize;
+
+use crate::chart::{Chart, LyricEvent, TempoEvent};
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::TimestampedEvent;
+
+/// Whether a change adds, removes or changes something, such as for colouring it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A phrase as compared by `ChartDiff`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PhraseSummary {
+    start_timestamp: u32,
+    end_timestamp: u32,
+    text: String,
+    lyric_timestamps: Vec<u32>,
+}
+
+impl PhraseSummary {
+    fn new(phrase: &Phrase) -> Self {
+        Self {
+            start_timestamp: phrase.get_start_timestamp(),
+            end_timestamp: phrase.get_end_timestamp(),
+            text: phrase.get_text(),
+            lyric_timestamps: phrase
+                .get_lyrics()
+                .iter()
+                .map(TimestampedEvent::get_timestamp)
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+
+    #[must_use]
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    pub const fn get_lyric_timestamps(&self) -> &Vec<u32> {
+        &self.lyric_timestamps
+    }
+}
+
+/// One difference between two charts. Events are given as written in a .chart file without
+/// their tick, such as `B 120000` or `N 0 96`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChartChange {
+    PropertyAdded {
+        name: String,
+        value: String,
+    },
+    PropertyRemoved {
+        name: String,
+        value: String,
+    },
+    PropertyChanged {
+        name: String,
+        old: String,
+        new: String,
+    },
+    TempoAdded {
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    TempoRemoved {
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    TempoChanged {
+        timestamp: u32,
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+    SectionAdded {
+        timestamp: u32,
+        name: String,
+    },
+    SectionRemoved {
+        timestamp: u32,
+        name: String,
+    },
+    SectionRenamed {
+        timestamp: u32,
+        old: String,
+        new: String,
+    },
+    /// Other `[Events]` entries, and lyrics outside any phrase.
+    EventsAdded {
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    EventsRemoved {
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    EventsChanged {
+        timestamp: u32,
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+    /// A phrase only in the new chart, with the section it starts in.
+    PhraseAdded {
+        singer: Singer,
+        section: Option<String>,
+        phrase: PhraseSummary,
+    },
+    /// A phrase only in the old chart, with the section it started in.
+    PhraseRemoved {
+        singer: Singer,
+        section: Option<String>,
+        phrase: PhraseSummary,
+    },
+    /// A phrase with different lyrics or timing, with the section it starts in in the new chart.
+    PhraseChanged {
+        singer: Singer,
+        section: Option<String>,
+        old: PhraseSummary,
+        new: PhraseSummary,
+    },
+    TrackAdded {
+        track: String,
+    },
+    TrackRemoved {
+        track: String,
+    },
+    NotesAdded {
+        track: String,
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    NotesRemoved {
+        track: String,
+        timestamp: u32,
+        events: Vec<String>,
+    },
+    NotesChanged {
+        track: String,
+        timestamp: u32,
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+}
+
+impl ChartChange {
+    #[must_use]
+    pub const fn get_kind(&self) -> ChangeKind {
+        match self {
+            Self::PropertyAdded { .. }
+            | Self::TempoAdded { .. }
+            | Self::SectionAdded { .. }
+            | Self::EventsAdded { .. }
+            | Self::PhraseAdded { .. }
+            | Self::TrackAdded { .. }
+            | Self::NotesAdded { .. } => ChangeKind::Added,
+            Self::PropertyRemoved { .. }
+            | Self::TempoRemoved { .. }
+            | Self::SectionRemoved { .. }
+            | Self::EventsRemoved { .. }
+            | Self::PhraseRemoved { .. }
+            | Self::TrackRemoved { .. }
+            | Self::NotesRemoved { .. } => ChangeKind::Removed,
+            Self::PropertyChanged { .. }
+            | Self::TempoChanged { .. }
+            | Self::SectionRenamed { .. }
+            | Self::EventsChanged { .. }
+            | Self::PhraseChanged { .. }
+            | Self::NotesChanged { .. } => ChangeKind::Changed,
+        }
+    }
+}
+
+fn phrase_location(singer: Singer, section: Option<&String>) -> String {
+    section.map_or_else(
+        || format!("{} phrase", singer),
+        |section| format!("{} phrase in {}", singer, section),
+    )
+}
+
+impl Display for ChartChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PropertyAdded { name, value } => write!(f, "+ [Song] {} = {}", name, value),
+            Self::PropertyRemoved { name, value } => write!(f, "- [Song] {} = {}", name, value),
+            Self::PropertyChanged { name, old, new } => {
+                write!(f, "~ [Song] {} = {} -> {}", name, old, new)
+            }
+            Self::TempoAdded { timestamp, events } => {
+                write!(f, "+ [SyncTrack] {} = {}", timestamp, events.join(", "))
+            }
+            Self::TempoRemoved { timestamp, events } => {
+                write!(f, "- [SyncTrack] {} = {}", timestamp, events.join(", "))
+            }
+            Self::TempoChanged {
+                timestamp,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ [SyncTrack] {} = {} -> {}",
+                timestamp,
+                old.join(", "),
+                new.join(", ")
+            ),
+            Self::SectionAdded { timestamp, name } => {
+                write!(f, "+ [Events] {} = section {}", timestamp, name)
+            }
+            Self::SectionRemoved { timestamp, name } => {
+                write!(f, "- [Events] {} = section {}", timestamp, name)
+            }
+            Self::SectionRenamed {
+                timestamp,
+                old,
+                new,
+            } => write!(f, "~ [Events] {} = section {} -> {}", timestamp, old, new),
+            Self::EventsAdded { timestamp, events } => {
+                write!(f, "+ [Events] {} = {}", timestamp, events.join(", "))
+            }
+            Self::EventsRemoved { timestamp, events } => {
+                write!(f, "- [Events] {} = {}", timestamp, events.join(", "))
+            }
+            Self::EventsChanged {
+                timestamp,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ [Events] {} = {} -> {}",
+                timestamp,
+                old.join(", "),
+                new.join(", ")
+            ),
+            Self::PhraseAdded {
+                singer,
+                section,
+                phrase,
+            } => write!(
+                f,
+                "+ [Events] {} at {}: {}",
+                phrase_location(*singer, section.as_ref()),
+                phrase.start_timestamp,
+                phrase.text
+            ),
+            Self::PhraseRemoved {
+                singer,
+                section,
+                phrase,
+            } => write!(
+                f,
+                "- [Events] {} at {}: {}",
+                phrase_location(*singer, section.as_ref()),
+                phrase.start_timestamp,
+                phrase.text
+            ),
+            Self::PhraseChanged {
+                singer,
+                section,
+                old,
+                new,
+            } => {
+                write!(
+                    f,
+                    "~ [Events] {} at {}: ",
+                    phrase_location(*singer, section.as_ref()),
+                    new.start_timestamp
+                )?;
+                if old.text == new.text {
+                    write!(f, "{} (retimed", new.text)?;
+                    if old.start_timestamp != new.start_timestamp {
+                        write!(f, " from {}", old.start_timestamp)?;
+                    }
+                    write!(f, ")")
+                } else {
+                    write!(f, "{} -> {}", old.text, new.text)
+                }
+            }
+            Self::TrackAdded { track } => write!(f, "+ [{}]", track),
+            Self::TrackRemoved { track } => write!(f, "- [{}]", track),
+            Self::NotesAdded {
+                track,
+                timestamp,
+                events,
+            } => write!(f, "+ [{}] {} = {}", track, timestamp, events.join(", ")),
+            Self::NotesRemoved {
+                track,
+                timestamp,
+                events,
+            } => write!(f, "- [{}] {} = {}", track, timestamp, events.join(", ")),
+            Self::NotesChanged {
+                track,
+                timestamp,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ [{}] {} = {} -> {}",
+                track,
+                timestamp,
+                old.join(", "),
+                new.join(", ")
+            ),
+        }
+    }
+}
+
+/// An event as written in a .chart file, without its tick.
+fn event_text<T: TimestampedEvent + Display>(event: &T) -> String {
+    let line = event.to_string();
+    line.strip_prefix(&format!("{} = ", event.get_timestamp()))
+        .unwrap_or(&line)
+        .to_string()
+}
+
+/// Pairs up the items of two sequences along their longest common subsequence. Runs of
+/// unmatched items between two matches are paired up in order, as edits of each other.
+fn align<T>(
+    old: &[T],
+    new: &[T],
+    same: impl Fn(&T, &T) -> bool,
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut lengths = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = vec![];
+    let mut removed = vec![];
+    let mut added = vec![];
+    let flush = |pairs: &mut Vec<_>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let count = removed.len().max(added.len());
+        for k in 0..count {
+            pairs.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+        removed.clear();
+        added.clear();
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same(&old[i], &new[j]) {
+            flush(&mut pairs, &mut removed, &mut added);
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            added.push(j);
+            j += 1;
+        }
+    }
+    flush(&mut pairs, &mut removed, &mut added);
+    pairs
+}
+
+/// The name of the section a tick is in.
+fn section_at(lyrics: &[LyricEvent], timestamp: u32) -> Option<String> {
+    lyrics
+        .iter()
+        .filter_map(|event| match event {
+            LyricEvent::Section {
+                timestamp: tick,
+                text,
+            } if *tick <= timestamp => Some((*tick, text)),
+            _ => None,
+        })
+        .max_by_key(|(tick, _)| *tick)
+        .map(|(_, text)| text.clone())
+}
+
+fn sections(lyrics: &[LyricEvent]) -> BTreeMap<u32, String> {
+    lyrics
+        .iter()
+        .filter_map(|event| match event {
+            LyricEvent::Section { timestamp, text } => Some((*timestamp, text.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `[Events]` entries that are neither sections nor part of a phrase: other events, and
+/// lyrics and phrase events that no phrase takes up.
+fn loose_events(lyrics: &[LyricEvent]) -> Vec<LyricEvent> {
+    let phrases = LyricPhraseCollection::new(lyrics);
+    let mut in_phrases: HashMap<(Singer, u32), usize> = HashMap::new();
+    for singer in [Singer::Main, Singer::Duet] {
+        for phrase in phrases.get_phrases(singer) {
+            for lyric in phrase.get_lyrics() {
+                *in_phrases
+                    .entry((singer, lyric.get_timestamp()))
+                    .or_default() += 1;
+            }
+        }
+    }
+    lyrics
+        .iter()
+        .filter(|event| {
+            let singer = match event {
+                LyricEvent::Lyric { .. } => Singer::Main,
+                LyricEvent::DuetLyric { .. } => Singer::Duet,
+                LyricEvent::OtherLyricEvent { .. } => return true,
+                _ => return false,
+            };
+            match in_phrases.get_mut(&(singer, event.get_timestamp())) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// The events of a track or the tempo map grouped by tick, each group sorted.
+fn events_by_tick<T: TimestampedEvent + Display>(events: &[T]) -> BTreeMap<u32, Vec<String>> {
+    let mut grouped: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for event in events {
+        grouped
+            .entry(event.get_timestamp())
+            .or_default()
+            .push(event_text(event));
+    }
+    grouped.values_mut().for_each(|events| events.sort());
+    grouped
+}
+
+/// Compares two groupings of events by tick, creating a change per tick with differences.
+fn diff_by_tick<C>(
+    old: &BTreeMap<u32, Vec<String>>,
+    new: &BTreeMap<u32, Vec<String>>,
+    mut change: impl FnMut(u32, Option<&Vec<String>>, Option<&Vec<String>>) -> C,
+) -> Vec<C> {
+    let ticks: BTreeSet<u32> = old.keys().chain(new.keys()).copied().collect();
+    ticks
+        .into_iter()
+        .filter(|tick| old.get(tick) != new.get(tick))
+        .map(|tick| change(tick, old.get(&tick), new.get(&tick)))
+        .collect()
+}
+
+/// The differences between two versions of a chart, such as an updated chart sent in by a
+/// charter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChartDiff {
+    changes: Vec<ChartChange>,
+}
+
+impl ChartDiff {
+    /// Compares two charts by meaning rather than by line: properties by name, tempo events
+    /// and notes by tick, sections and other events by tick and phrases by their lyrics, so a
+    /// phrase that moved is reported as retimed rather than removed and added again.
+    ///
+    /// # Arguments
+    ///
+    /// * `old`: the chart to compare from.
+    /// * `new`: the chart to compare to.
+    ///
+    /// returns: `ChartDiff` with properties first, then tempo events, lyrics in song order and
+    /// tracks in the order they are written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::diff::ChartDiff;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let mut edited = chart.clone();
+    /// edited.set_property("Charter", "\"someone else\"".to_string());
+    /// assert!(ChartDiff::new(&chart, &chart).is_empty());
+    /// assert_eq!(ChartDiff::new(&chart, &edited).get_changes().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn new(old: &Chart, new: &Chart) -> Self {
+        let mut changes = Self::properties(old, new);
+        changes.extend(Self::tempo_map(old, new));
+        changes.extend(Self::lyrics(old, new));
+        changes.extend(Self::tracks(old, new));
+        Self { changes }
+    }
+
+    fn properties(old: &Chart, new: &Chart) -> Vec<ChartChange> {
+        let names: BTreeSet<&String> = old
+            .get_properties()
+            .keys()
+            .chain(new.get_properties().keys())
+            .collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let name_string = name.clone();
+                match (
+                    old.get_properties().get(name),
+                    new.get_properties().get(name),
+                ) {
+                    (Some(old), Some(new)) if old != new => Some(ChartChange::PropertyChanged {
+                        name: name_string,
+                        old: old.clone(),
+                        new: new.clone(),
+                    }),
+                    (Some(value), None) => Some(ChartChange::PropertyRemoved {
+                        name: name_string,
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => Some(ChartChange::PropertyAdded {
+                        name: name_string,
+                        value: value.clone(),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn tempo_map(old: &Chart, new: &Chart) -> Vec<ChartChange> {
+        let sorted = |chart: &Chart| {
+            let mut events = chart.get_tempo_map().clone();
+            events.sort_by_key(|event: &TempoEvent| (event.get_timestamp(), event.write_rank()));
+            events
+        };
+        diff_by_tick(
+            &events_by_tick(&sorted(old)),
+            &events_by_tick(&sorted(new)),
+            |timestamp, old, new| match (old, new) {
+                (Some(old), Some(new)) => ChartChange::TempoChanged {
+                    timestamp,
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+                (Some(events), None) => ChartChange::TempoRemoved {
+                    timestamp,
+                    events: events.clone(),
+                },
+                (_, events) => ChartChange::TempoAdded {
+                    timestamp,
+                    events: events.cloned().unwrap_or_default(),
+                },
+            },
+        )
+    }
+
+    fn lyrics(old: &Chart, new: &Chart) -> Vec<ChartChange> {
+        let mut changes: Vec<(u32, ChartChange)> = vec![];
+        let old_sections = sections(old.get_lyrics());
+        let new_sections = sections(new.get_lyrics());
+        let ticks: BTreeSet<u32> = old_sections
+            .keys()
+            .chain(new_sections.keys())
+            .copied()
+            .collect();
+        for timestamp in ticks {
+            let change = match (old_sections.get(&timestamp), new_sections.get(&timestamp)) {
+                (Some(old), Some(new)) if old != new => ChartChange::SectionRenamed {
+                    timestamp,
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+                (Some(name), None) => ChartChange::SectionRemoved {
+                    timestamp,
+                    name: name.clone(),
+                },
+                (None, Some(name)) => ChartChange::SectionAdded {
+                    timestamp,
+                    name: name.clone(),
+                },
+                _ => continue,
+            };
+            changes.push((timestamp, change));
+        }
+
+        changes.extend(diff_by_tick(
+            &events_by_tick(&loose_events(old.get_lyrics())),
+            &events_by_tick(&loose_events(new.get_lyrics())),
+            |timestamp, old, new| {
+                let change = match (old, new) {
+                    (Some(old), Some(new)) => ChartChange::EventsChanged {
+                        timestamp,
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                    (Some(events), None) => ChartChange::EventsRemoved {
+                        timestamp,
+                        events: events.clone(),
+                    },
+                    (_, events) => ChartChange::EventsAdded {
+                        timestamp,
+                        events: events.cloned().unwrap_or_default(),
+                    },
+                };
+                (timestamp, change)
+            },
+        ));
+
+        let old_phrases = LyricPhraseCollection::new(old.get_lyrics());
+        let new_phrases = LyricPhraseCollection::new(new.get_lyrics());
+        for singer in [Singer::Main, Singer::Duet] {
+            let old_phrases: Vec<PhraseSummary> = old_phrases
+                .get_phrases(singer)
+                .iter()
+                .map(PhraseSummary::new)
+                .collect();
+            let new_phrases: Vec<PhraseSummary> = new_phrases
+                .get_phrases(singer)
+                .iter()
+                .map(PhraseSummary::new)
+                .collect();
+            for pair in align(&old_phrases, &new_phrases, |old, new| old.text == new.text) {
+                let change = match pair {
+                    (Some(i), Some(j)) if old_phrases[i] == new_phrases[j] => continue,
+                    (Some(i), Some(j)) => ChartChange::PhraseChanged {
+                        singer,
+                        section: section_at(new.get_lyrics(), new_phrases[j].start_timestamp),
+                        old: old_phrases[i].clone(),
+                        new: new_phrases[j].clone(),
+                    },
+                    (Some(i), None) => ChartChange::PhraseRemoved {
+                        singer,
+                        section: section_at(old.get_lyrics(), old_phrases[i].start_timestamp),
+                        phrase: old_phrases[i].clone(),
+                    },
+                    (None, Some(j)) => ChartChange::PhraseAdded {
+                        singer,
+                        section: section_at(new.get_lyrics(), new_phrases[j].start_timestamp),
+                        phrase: new_phrases[j].clone(),
+                    },
+                    (None, None) => continue,
+                };
+                let timestamp = pair.1.map_or_else(
+                    || pair.0.map_or(0, |i| old_phrases[i].start_timestamp),
+                    |j| new_phrases[j].start_timestamp,
+                );
+                changes.push((timestamp, change));
+            }
+        }
+        changes.sort_by_key(|(timestamp, _)| *timestamp);
+        changes.into_iter().map(|(_, change)| change).collect()
+    }
+
+    fn tracks(old: &Chart, new: &Chart) -> Vec<ChartChange> {
+        let mut names: Vec<&String> = old
+            .get_key_presses()
+            .keys()
+            .chain(new.get_key_presses().keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        names.sort_by_key(|name| Chart::track_order(name));
+        let mut changes = vec![];
+        for name in names {
+            let old_track = old.get_key_presses().get(name);
+            let new_track = new.get_key_presses().get(name);
+            // a whole track added or removed is one change, not one per tick of it
+            let (old_track, new_track) = match (old_track, new_track) {
+                (Some(old_track), Some(new_track)) => (old_track, new_track),
+                (Some(_), None) => {
+                    changes.push(ChartChange::TrackRemoved {
+                        track: name.clone(),
+                    });
+                    continue;
+                }
+                (None, Some(_)) => {
+                    changes.push(ChartChange::TrackAdded {
+                        track: name.clone(),
+                    });
+                    continue;
+                }
+                (None, None) => continue,
+            };
+            changes.extend(diff_by_tick(
+                &events_by_tick(old_track),
+                &events_by_tick(new_track),
+                |timestamp, old, new| match (old, new) {
+                    (Some(old), Some(new)) => ChartChange::NotesChanged {
+                        track: name.clone(),
+                        timestamp,
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                    (Some(events), None) => ChartChange::NotesRemoved {
+                        track: name.clone(),
+                        timestamp,
+                        events: events.clone(),
+                    },
+                    (_, events) => ChartChange::NotesAdded {
+                        track: name.clone(),
+                        timestamp,
+                        events: events.cloned().unwrap_or_default(),
+                    },
+                },
+            ));
+        }
+        changes
+    }
+
+    #[must_use]
+    pub const fn get_changes(&self) -> &Vec<ChartChange> {
+        &self.changes
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes as pretty-printed JSON.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the changes cannot be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::WrapErr;
+
+    use super::*;
+    use crate::chart::KeyPressEvent;
+    use crate::test_util::{chart, phrase};
+
+    #[test]
+    fn lyric_changes() {
+        let old = chart(
+            [
+                vec![LyricEvent::Section {
+                    timestamp: 0,
+                    text: "Verse 1".to_string(),
+                }],
+                phrase(0, &["one", "two"]),
+                phrase(384, &["three", "four"]),
+                phrase(768, &["five"]),
+            ]
+            .concat(),
+            vec![KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 0,
+                key: 0,
+            }],
+        );
+        let new = chart(
+            [
+                vec![LyricEvent::Section {
+                    timestamp: 0,
+                    text: "Verse 1".to_string(),
+                }],
+                phrase(96, &["one", "two"]),
+                phrase(384, &["three", "more"]),
+                vec![LyricEvent::Section {
+                    timestamp: 768,
+                    text: "Chorus".to_string(),
+                }],
+            ]
+            .concat(),
+            vec![KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 0,
+                key: 1,
+            }],
+        );
+        let diff = ChartDiff::new(&old, &new);
+        let lines: Vec<String> = diff.get_changes().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "~ [Events] main phrase in Verse 1 at 96: one two (retimed from 0)",
+                "~ [Events] main phrase in Verse 1 at 384: three four -> three more",
+                "+ [Events] 768 = section Chorus",
+                "- [Events] main phrase in Verse 1 at 768: five",
+                "~ [ExpertSingle] 0 = N 0 0 -> N 1 0",
+            ]
+        );
+        assert_eq!(diff.get_changes()[3].get_kind(), ChangeKind::Removed);
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json().unwrap()).unwrap();
+        assert_eq!(json["changes"][1]["change"], "phrase_changed");
+        assert_eq!(json["changes"][1]["singer"], "main");
+        assert_eq!(json["changes"][1]["new"]["text"], "three more");
+    }
+
+    #[test]
+    fn other_events() {
+        let old = chart(
+            [
+                vec![LyricEvent::OtherLyricEvent {
+                    code: "E".to_string(),
+                    timestamp: 0,
+                    content: "Default".to_string(),
+                }],
+                phrase(384, &["one"]),
+            ]
+            .concat(),
+            vec![],
+        );
+        let new = chart(
+            [
+                vec![LyricEvent::Lyric {
+                    timestamp: 96,
+                    text: "stray".to_string(),
+                }],
+                phrase(384, &["one"]),
+            ]
+            .concat(),
+            vec![],
+        );
+        let diff = ChartDiff::new(&old, &new);
+        let lines: Vec<String> = diff.get_changes().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "- [Events] 0 = E \"Default\"",
+                "+ [Events] 96 = E \"lyric stray\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn whole_tracks() {
+        let notes = vec![
+            KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 0,
+                key: 0,
+            },
+            KeyPressEvent::Note {
+                timestamp: 192,
+                duration: 0,
+                key: 1,
+            },
+        ];
+        let old = chart(phrase(0, &["one"]), notes.clone());
+        let mut new = chart(phrase(0, &["one"]), notes.clone());
+        new.get_key_presses_mut()
+            .insert("HardSingle".to_string(), notes);
+        new.get_key_presses_mut().remove("ExpertSingle");
+        let diff = ChartDiff::new(&old, &new);
+        let lines: Vec<String> = diff.get_changes().iter().map(ToString::to_string).collect();
+        assert_eq!(lines, vec!["- [ExpertSingle]", "+ [HardSingle]"]);
+    }
+
+    #[test]
+    fn diff_edited_charts() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            diff_edited_charts_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn diff_edited_charts_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        assert!(ChartDiff::new(&chart, &Chart::new(&chart.to_string())?).is_empty());
+
+        let mut edited = chart.clone();
+        edited.get_key_presses_mut().remove("ExpertSingle");
+        let diff = ChartDiff::new(&chart, &edited);
+        assert_eq!(
+            diff.is_empty(),
+            !chart.get_key_presses().contains_key("ExpertSingle")
+        );
+        assert!(diff
+            .get_changes()
+            .iter()
+            .all(|change| change.get_kind() == ChangeKind::Removed));
+        Ok(())
+    }
+}
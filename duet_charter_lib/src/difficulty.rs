@@ -0,0 +1,406 @@
+use serde::Serialize;
+
+use crate::chart::{Chart, KeyPressEvent};
+use crate::notes::{chords, note_kinds, Chord, Instrument, NoteKind};
+use crate::timing::TempoMap;
+
+/// Weight of the peak notes per second in the difficulty score.
+const PEAK_WEIGHT: f64 = 0.15;
+/// Weight of the average number of extra gems per note in the difficulty score.
+const CHORD_WEIGHT: f64 = 1.5;
+/// Weight of the share of HOPOs and taps in the difficulty score.
+const HOPO_TAP_WEIGHT: f64 = 1.0;
+/// Weight of the share of the track spent sustaining in the difficulty score.
+const SUSTAIN_WEIGHT: f64 = 0.5;
+
+/// The highest difficulty tier, as used by the `diff_*` fields of `song.ini`.
+pub const MAX_TIER: u8 = 6;
+
+/// Window the peak notes per second are counted in, in microseconds.
+const PEAK_WINDOW_MICROS: f64 = 1_000_000.0;
+
+/// The notes per second at one moment of a track.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DensityPoint {
+    timestamp: u32,
+    seconds: f64,
+    nps: f64,
+}
+
+impl DensityPoint {
+    #[must_use]
+    pub const fn get_timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Song time of the point, in seconds.
+    #[must_use]
+    pub const fn get_seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    /// Notes per second in the window ending at this point.
+    #[must_use]
+    pub const fn get_nps(&self) -> f64 {
+        self.nps
+    }
+}
+
+/// Notes per second over a rolling window, sampled at a fixed step through a track.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DensityCurve {
+    window_seconds: f64,
+    points: Vec<DensityPoint>,
+}
+
+impl DensityCurve {
+    /// Constructor for `DensityCurve` from the chords of a track.
+    ///
+    /// Each point counts the chords in the window ending at it, so the curve rises as a dense
+    /// passage starts and falls `window_seconds` after it ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `chords`: the chords of the track, ordered by timestamp.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    /// * `window_seconds`: the length of the rolling window.
+    /// * `step_seconds`: the time between two points.
+    ///
+    /// returns: `DensityCurve`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::chart::{KeyPressEvent, TempoEvent};
+    /// use duet_charter_lib::difficulty::DensityCurve;
+    /// use duet_charter_lib::notes::{chords, Instrument};
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let tempo_map = TempoMap::new(&[TempoEvent::Beat { timestamp: 0, milli_bpm: 120_000 }], 192);
+    /// let events: Vec<KeyPressEvent> = (0..4)
+    ///     .map(|i| KeyPressEvent::Note { timestamp: i * 96, duration: 0, key: 0 })
+    ///     .collect();
+    /// let curve = DensityCurve::new(&chords(Instrument::Guitar, &events), &tempo_map, 1.0, 0.25);
+    /// assert_eq!(curve.peak(), 4.0);
+    /// ```
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn new(
+        chords: &[Chord],
+        tempo_map: &TempoMap,
+        window_seconds: f64,
+        step_seconds: f64,
+    ) -> Self {
+        let times: Vec<f64> = chords
+            .iter()
+            .map(|chord| tempo_map.ticks_to_seconds(chord.get_timestamp()))
+            .collect();
+        let mut points = vec![];
+        if let (Some(last), true) = (times.last(), window_seconds > 0.0 && step_seconds > 0.0) {
+            let steps = ((last + window_seconds) / step_seconds).ceil() as usize;
+            let (mut start, mut end) = (0, 0);
+            for step in 0..=steps {
+                let seconds = step as f64 * step_seconds;
+                while end < times.len() && times[end] <= seconds {
+                    end += 1;
+                }
+                while start < end && times[start] <= seconds - window_seconds {
+                    start += 1;
+                }
+                points.push(DensityPoint {
+                    timestamp: tempo_map.seconds_to_ticks(seconds),
+                    seconds,
+                    nps: (end - start) as f64 / window_seconds,
+                });
+            }
+        }
+        Self {
+            window_seconds,
+            points,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_window_seconds(&self) -> f64 {
+        self.window_seconds
+    }
+
+    #[must_use]
+    pub const fn get_points(&self) -> &Vec<DensityPoint> {
+        &self.points
+    }
+
+    /// The highest notes per second of the curve. Bursts shorter than the step between two
+    /// points can fall between them, see `peak_nps` for the exact peak.
+    #[must_use]
+    pub fn peak(&self) -> f64 {
+        self.points
+            .iter()
+            .map(DensityPoint::get_nps)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// The most chords in any one second stretch of a track, checking the window ending at every
+/// chord, so unlike a sampled `DensityCurve` no burst is missed.
+///
+/// # Arguments
+///
+/// * `chords`: the chords of the track, ordered by timestamp.
+/// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn peak_nps(chords: &[Chord], tempo_map: &TempoMap) -> f64 {
+    let times: Vec<f64> = chords
+        .iter()
+        .map(|chord| tempo_map.ticks_to_micros_f64(chord.get_timestamp()))
+        .collect();
+    let mut peak = 0;
+    let mut start = 0;
+    for (end, time) in times.iter().enumerate() {
+        while times[start] <= time - PEAK_WINDOW_MICROS {
+            start += 1;
+        }
+        peak = peak.max(end + 1 - start);
+    }
+    peak as f64 / (PEAK_WINDOW_MICROS / 1_000_000.0)
+}
+
+/// Estimated difficulty of a single `key_presses` track, with the measures it is built from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackDifficulty {
+    name: String,
+    peak_nps: f64,
+    chord_complexity: f64,
+    hopo_tap_ratio: f64,
+    sustain_load: f64,
+    score: f64,
+    curve: DensityCurve,
+}
+
+impl TrackDifficulty {
+    /// Constructor for `TrackDifficulty` from the events of a track, using a one second window
+    /// sampled every quarter second.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the track name, such as `ExpertSingle`.
+    /// * `events`: the events of the track, in any order.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    ///
+    /// returns: `TrackDifficulty`
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(name: &str, events: &[KeyPressEvent], tempo_map: &TempoMap) -> Self {
+        let instrument = Instrument::from_track(name);
+        let chords = chords(instrument, events);
+        let curve = DensityCurve::new(&chords, tempo_map, 1.0, 0.25);
+        let notes = chords.len().max(1) as f64;
+
+        let extra_gems: usize = chords.iter().map(|chord| chord.get_keys().len() - 1).sum();
+        let chord_complexity = extra_gems as f64 / notes;
+        let hopos_and_taps = note_kinds(instrument, &chords, tempo_map.get_resolution())
+            .into_iter()
+            .filter(|kind| *kind != NoteKind::Strum)
+            .count();
+        let hopo_tap_ratio = hopos_and_taps as f64 / notes;
+
+        let span = match (chords.first(), chords.last()) {
+            (Some(first), Some(last)) => {
+                tempo_map.ticks_to_seconds(last.get_end_timestamp())
+                    - tempo_map.ticks_to_seconds(first.get_timestamp())
+            }
+            _ => 0.0,
+        };
+        let sustained: f64 = chords
+            .iter()
+            .map(|chord| {
+                tempo_map.ticks_to_seconds(chord.get_end_timestamp())
+                    - tempo_map.ticks_to_seconds(chord.get_timestamp())
+            })
+            .sum();
+        let sustain_load = if span > 0.0 {
+            (sustained / span).min(1.0)
+        } else {
+            0.0
+        };
+
+        let peak_nps = peak_nps(&chords, tempo_map);
+        Self {
+            name: name.to_string(),
+            peak_nps,
+            chord_complexity,
+            hopo_tap_ratio,
+            sustain_load,
+            score: PEAK_WEIGHT * peak_nps
+                + CHORD_WEIGHT * chord_complexity
+                + HOPO_TAP_WEIGHT * hopo_tap_ratio
+                + SUSTAIN_WEIGHT * sustain_load,
+            curve,
+        }
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Most notes in any one second stretch of the track, see `peak_nps`.
+    #[must_use]
+    pub const fn get_peak_nps(&self) -> f64 {
+        self.peak_nps
+    }
+
+    /// The average number of gems per note beyond the first.
+    #[must_use]
+    pub const fn get_chord_complexity(&self) -> f64 {
+        self.chord_complexity
+    }
+
+    /// The share of notes that are HOPOs or taps, between 0 and 1.
+    #[must_use]
+    pub const fn get_hopo_tap_ratio(&self) -> f64 {
+        self.hopo_tap_ratio
+    }
+
+    /// The share of the track spent holding sustains, between 0 and 1.
+    #[must_use]
+    pub const fn get_sustain_load(&self) -> f64 {
+        self.sustain_load
+    }
+
+    /// The weighted sum of the measures; an expert track typically scores between 2 and 6.
+    #[must_use]
+    pub const fn get_score(&self) -> f64 {
+        self.score
+    }
+
+    /// The score rounded to a tier from 0 to `MAX_TIER`.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn get_tier(&self) -> u8 {
+        self.score.round().clamp(0.0, f64::from(MAX_TIER)) as u8
+    }
+
+    #[must_use]
+    pub const fn get_curve(&self) -> &DensityCurve {
+        &self.curve
+    }
+}
+
+/// The difficulty of every note track of a chart, in the order the tracks are written to a
+/// chart file.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::difficulty::chart_difficulties;
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// for track in chart_difficulties(&Chart::new(&file_content).unwrap()) {
+///     println!("{}: tier {} ({:.2})", track.get_name(), track.get_tier(), track.get_score());
+/// }
+/// ```
+#[must_use]
+pub fn chart_difficulties(chart: &Chart) -> Vec<TrackDifficulty> {
+    let tempo_map = TempoMap::from_chart(chart);
+    let mut names: Vec<&String> = chart.get_key_presses().keys().collect();
+    names.sort_by_key(|name| Chart::track_order(name));
+    names
+        .into_iter()
+        .map(|name| TrackDifficulty::new(name, &chart.get_key_presses()[name], &tempo_map))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::chart::TempoEvent;
+
+    use super::*;
+
+    #[test]
+    fn track_difficulty() {
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let note = |timestamp, duration, key| KeyPressEvent::Note {
+            timestamp,
+            duration,
+            key,
+        };
+        let events = [
+            note(0, 0, 0),
+            note(0, 0, 1),
+            note(48, 0, 2),
+            note(96, 0, 3),
+            note(96, 0, 6),
+            note(384, 384, 0),
+        ];
+        let difficulty = TrackDifficulty::new("ExpertSingle", &events, &tempo_map);
+        assert!((difficulty.get_peak_nps() - 3.0).abs() < 1e-9);
+        assert!((difficulty.get_chord_complexity() - 0.25).abs() < 1e-9);
+        assert!((difficulty.get_hopo_tap_ratio() - 0.5).abs() < 1e-9);
+        assert!((difficulty.get_sustain_load() - 0.5).abs() < 1e-9);
+        assert!((difficulty.get_score() - 1.575).abs() < 1e-9);
+        assert_eq!(difficulty.get_tier(), 2);
+
+        let points = difficulty.get_curve().get_points();
+        assert_eq!(points.first().map(DensityPoint::get_nps), Some(1.0));
+        assert_eq!(points.last().map(DensityPoint::get_nps), Some(0.0));
+    }
+
+    #[test]
+    fn chart_difficulty() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            chart_difficulty_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn chart_difficulty_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        for track in chart_difficulties(&chart) {
+            assert!(track.get_tier() <= MAX_TIER);
+            assert!((0.0..=1.0).contains(&track.get_sustain_load()));
+            assert!((0.0..=1.0).contains(&track.get_hopo_tap_ratio()));
+            assert!(track
+                .get_curve()
+                .get_points()
+                .windows(2)
+                .all(|pair| pair[0].get_seconds() < pair[1].get_seconds()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,423 @@
+use crate::chart::Chart;
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::structure::SongStructure;
+use crate::timing::TempoMap;
+
+/// A stretch of time where a main and a duet phrase are both on screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhraseOverlap {
+    main_phrase_start: u32,
+    duet_phrase_start: u32,
+    start_timestamp: u32,
+    end_timestamp: u32,
+}
+
+impl PhraseOverlap {
+    #[must_use]
+    pub const fn get_main_phrase_start(&self) -> u32 {
+        self.main_phrase_start
+    }
+
+    #[must_use]
+    pub const fn get_duet_phrase_start(&self) -> u32 {
+        self.duet_phrase_start
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+}
+
+/// Consecutive phrases sung by the same singer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingerStretch {
+    singer: Singer,
+    start_timestamp: u32,
+    end_timestamp: u32,
+    micros: u64,
+    phrase_count: usize,
+}
+
+impl SingerStretch {
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+
+    /// Length of the stretch in microseconds.
+    #[must_use]
+    pub const fn get_micros(&self) -> u64 {
+        self.micros
+    }
+
+    #[must_use]
+    pub const fn get_phrase_count(&self) -> usize {
+        self.phrase_count
+    }
+}
+
+/// A section with lyrics in which one of the singers has no phrases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionAbsence {
+    section_index: usize,
+    name: Option<String>,
+    absent: Singer,
+}
+
+impl SectionAbsence {
+    /// Index of the section in `SongStructure::get_sections`.
+    #[must_use]
+    pub const fn get_section_index(&self) -> usize {
+        self.section_index
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_absent(&self) -> Singer {
+        self.absent
+    }
+}
+
+/// How the lines of a song are divided between the main and duet singer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuetAnalysis {
+    overlaps: Vec<PhraseOverlap>,
+    main_micros: u64,
+    duet_micros: u64,
+    main_syllables: usize,
+    duet_syllables: usize,
+    longest_stretch: Option<SingerStretch>,
+    absences: Vec<SectionAbsence>,
+}
+
+impl DuetAnalysis {
+    /// Constructor for `DuetAnalysis` from the phrases and sections of a song. Only phrases with
+    /// lyrics are taken into account, and sung time runs from phrase start to phrase end.
+    ///
+    /// # Arguments
+    ///
+    /// * `phrases`: the main and duet phrases of the song.
+    /// * `structure`: the sections of the song.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    ///
+    /// returns: `DuetAnalysis`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::duet_analysis::DuetAnalysis;
+    /// use duet_charter_lib::phrases::Singer;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let analysis = DuetAnalysis::from_chart(&chart);
+    /// println!("duet sings {:.1}% of the time", analysis.time_percentage(Singer::Duet));
+    /// ```
+    #[must_use]
+    pub fn new(
+        phrases: &LyricPhraseCollection,
+        structure: &SongStructure,
+        tempo_map: &TempoMap,
+    ) -> Self {
+        let sung = |singer: Singer| -> Vec<&Phrase> {
+            phrases
+                .get_phrases(singer)
+                .iter()
+                .filter(|phrase| !phrase.get_lyrics().is_empty())
+                .collect()
+        };
+        let (main, duet) = (sung(Singer::Main), sung(Singer::Duet));
+        let duration = |phrase: &&Phrase| {
+            tempo_map
+                .ticks_to_micros(phrase.get_end_timestamp())
+                .saturating_sub(tempo_map.ticks_to_micros(phrase.get_start_timestamp()))
+        };
+        let syllables = |phrases: &[&Phrase]| {
+            phrases
+                .iter()
+                .map(|phrase| phrase.get_display_syllables().len())
+                .sum()
+        };
+
+        // the phrases of a singer are sorted and do not overlap, so the pair ending first can't
+        // overlap any later phrase of the other singer
+        let mut overlaps = vec![];
+        let (mut main_index, mut duet_index) = (0, 0);
+        while let (Some(main_phrase), Some(duet_phrase)) =
+            (main.get(main_index), duet.get(duet_index))
+        {
+            let start_timestamp = main_phrase
+                .get_start_timestamp()
+                .max(duet_phrase.get_start_timestamp());
+            let end_timestamp = main_phrase
+                .get_end_timestamp()
+                .min(duet_phrase.get_end_timestamp());
+            if start_timestamp < end_timestamp {
+                overlaps.push(PhraseOverlap {
+                    main_phrase_start: main_phrase.get_start_timestamp(),
+                    duet_phrase_start: duet_phrase.get_start_timestamp(),
+                    start_timestamp,
+                    end_timestamp,
+                });
+            }
+            if main_phrase.get_end_timestamp() <= duet_phrase.get_end_timestamp() {
+                main_index += 1;
+            } else {
+                duet_index += 1;
+            }
+        }
+        overlaps.sort_by_key(PhraseOverlap::get_start_timestamp);
+
+        let mut ordered: Vec<(Singer, &Phrase)> = main
+            .iter()
+            .map(|phrase| (Singer::Main, *phrase))
+            .chain(duet.iter().map(|phrase| (Singer::Duet, *phrase)))
+            .collect();
+        ordered.sort_by_key(|(_, phrase)| phrase.get_start_timestamp());
+        let mut stretches: Vec<SingerStretch> = vec![];
+        for (singer, phrase) in ordered {
+            match stretches.last_mut() {
+                Some(stretch) if stretch.singer == singer => {
+                    stretch.end_timestamp = stretch.end_timestamp.max(phrase.get_end_timestamp());
+                    stretch.phrase_count += 1;
+                }
+                _ => stretches.push(SingerStretch {
+                    singer,
+                    start_timestamp: phrase.get_start_timestamp(),
+                    end_timestamp: phrase.get_end_timestamp(),
+                    micros: 0,
+                    phrase_count: 1,
+                }),
+            }
+        }
+        for stretch in &mut stretches {
+            stretch.micros = tempo_map
+                .ticks_to_micros(stretch.end_timestamp)
+                .saturating_sub(tempo_map.ticks_to_micros(stretch.start_timestamp));
+        }
+        let longest_stretch = stretches.into_iter().max_by_key(|stretch| stretch.micros);
+
+        let mut absences = vec![];
+        for (section_index, section) in structure.get_sections().iter().enumerate() {
+            let has_lyrics = |singer: Singer| {
+                section
+                    .get_phrases(singer)
+                    .iter()
+                    .any(|phrase| !phrase.get_lyrics().is_empty())
+            };
+            match (has_lyrics(Singer::Main), has_lyrics(Singer::Duet)) {
+                (true, false) => absences.push(SectionAbsence {
+                    section_index,
+                    name: section.get_name().map(ToString::to_string),
+                    absent: Singer::Duet,
+                }),
+                (false, true) => absences.push(SectionAbsence {
+                    section_index,
+                    name: section.get_name().map(ToString::to_string),
+                    absent: Singer::Main,
+                }),
+                _ => {}
+            }
+        }
+
+        Self {
+            overlaps,
+            main_micros: main.iter().map(duration).sum(),
+            duet_micros: duet.iter().map(duration).sum(),
+            main_syllables: syllables(&main),
+            duet_syllables: syllables(&duet),
+            longest_stretch,
+            absences,
+        }
+    }
+
+    /// Constructor for `DuetAnalysis` from the lyrics and tempo map of a chart.
+    #[must_use]
+    pub fn from_chart(chart: &Chart) -> Self {
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        Self::new(
+            &phrases,
+            &SongStructure::from_phrases(chart.get_lyrics(), &phrases),
+            &TempoMap::from_chart(chart),
+        )
+    }
+
+    #[must_use]
+    pub const fn get_overlaps(&self) -> &Vec<PhraseOverlap> {
+        &self.overlaps
+    }
+
+    /// Total time of the singer's phrases in microseconds.
+    #[must_use]
+    pub const fn sung_micros(&self, singer: Singer) -> u64 {
+        match singer {
+            Singer::Main => self.main_micros,
+            Singer::Duet => self.duet_micros,
+        }
+    }
+
+    /// Number of syllables shown for the singer, leaving out `+` holds.
+    #[must_use]
+    pub const fn syllables(&self, singer: Singer) -> usize {
+        match singer {
+            Singer::Main => self.main_syllables,
+            Singer::Duet => self.duet_syllables,
+        }
+    }
+
+    /// The singer's share of the sung time of both singers, from 0 to 100.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn time_percentage(&self, singer: Singer) -> f64 {
+        percentage(
+            self.sung_micros(singer) as f64,
+            (self.main_micros + self.duet_micros) as f64,
+        )
+    }
+
+    /// The singer's share of the syllables of both singers, from 0 to 100.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn syllable_percentage(&self, singer: Singer) -> f64 {
+        percentage(
+            self.syllables(singer) as f64,
+            (self.main_syllables + self.duet_syllables) as f64,
+        )
+    }
+
+    /// The longest run of phrases without the other singer taking over.
+    #[must_use]
+    pub const fn get_longest_stretch(&self) -> Option<&SingerStretch> {
+        self.longest_stretch.as_ref()
+    }
+
+    #[must_use]
+    pub const fn get_absences(&self) -> &Vec<SectionAbsence> {
+        &self.absences
+    }
+}
+
+fn percentage(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part * 100.0 / total
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chart::{LyricEvent, TempoEvent};
+
+    use super::*;
+
+    #[test]
+    fn duet_balance() {
+        let lyric = |timestamp, text: &str| LyricEvent::Lyric {
+            timestamp,
+            text: text.to_string(),
+        };
+        let duet_lyric = |timestamp, text: &str| LyricEvent::DuetLyric {
+            timestamp,
+            text: text.to_string(),
+        };
+        let events = vec![
+            LyricEvent::Section {
+                timestamp: 0,
+                text: "Verse".to_string(),
+            },
+            LyricEvent::PhraseStart { timestamp: 0 },
+            lyric(0, "one"),
+            lyric(96, "two"),
+            lyric(144, "+"),
+            LyricEvent::PhraseEnd { timestamp: 192 },
+            LyricEvent::PhraseStart { timestamp: 192 },
+            lyric(192, "three"),
+            LyricEvent::PhraseEnd { timestamp: 576 },
+            LyricEvent::DuetPhraseStart { timestamp: 384 },
+            duet_lyric(384, "four"),
+            LyricEvent::DuetPhraseEnd { timestamp: 768 },
+            LyricEvent::Section {
+                timestamp: 768,
+                text: "Chorus".to_string(),
+            },
+            LyricEvent::DuetPhraseStart { timestamp: 768 },
+            duet_lyric(768, "five"),
+            LyricEvent::DuetPhraseEnd { timestamp: 1152 },
+        ];
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let phrases = LyricPhraseCollection::new(&events);
+        let analysis = DuetAnalysis::new(
+            &phrases,
+            &SongStructure::from_phrases(&events, &phrases),
+            &tempo_map,
+        );
+
+        assert_eq!(
+            analysis.get_overlaps(),
+            &vec![PhraseOverlap {
+                main_phrase_start: 192,
+                duet_phrase_start: 384,
+                start_timestamp: 384,
+                end_timestamp: 576,
+            }]
+        );
+        assert_eq!(analysis.sung_micros(Singer::Main), 1_500_000);
+        assert_eq!(analysis.sung_micros(Singer::Duet), 2_000_000);
+        assert!((analysis.time_percentage(Singer::Duet) - 400.0 / 7.0).abs() < 1e-9);
+        assert_eq!(analysis.syllables(Singer::Main), 3);
+        assert!((analysis.syllable_percentage(Singer::Main) - 60.0).abs() < 1e-9);
+        assert_eq!(
+            analysis.get_longest_stretch(),
+            Some(&SingerStretch {
+                singer: Singer::Duet,
+                start_timestamp: 384,
+                end_timestamp: 1152,
+                micros: 2_000_000,
+                phrase_count: 2,
+            })
+        );
+        assert_eq!(
+            analysis.get_absences(),
+            &vec![SectionAbsence {
+                section_index: 1,
+                name: Some("Chorus".to_string()),
+                absent: Singer::Main,
+            }]
+        );
+    }
+}
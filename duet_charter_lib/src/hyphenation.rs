@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use hypher::{hyphenate_bounded, Lang};
+
+/// A language with embedded hyphenation patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Italian,
+    Portuguese,
+    Spanish,
+    Swedish,
+}
+
+impl Language {
+    /// The language with the given ISO 639-1 code, such as `"en"` or `"de"`.
+    #[must_use]
+    pub fn from_iso(code: &str) -> Option<Self> {
+        match code.to_lowercase().as_str() {
+            "nl" => Some(Self::Dutch),
+            "en" => Some(Self::English),
+            "fi" => Some(Self::Finnish),
+            "fr" => Some(Self::French),
+            "de" => Some(Self::German),
+            "it" => Some(Self::Italian),
+            "pt" => Some(Self::Portuguese),
+            "es" => Some(Self::Spanish),
+            "sv" => Some(Self::Swedish),
+            _ => None,
+        }
+    }
+
+    const fn patterns(self) -> Lang {
+        match self {
+            Self::Dutch => Lang::Dutch,
+            Self::English => Lang::English,
+            Self::Finnish => Lang::Finnish,
+            Self::French => Lang::French,
+            Self::German => Lang::German,
+            Self::Italian => Lang::Italian,
+            Self::Portuguese => Lang::Portuguese,
+            Self::Spanish => Lang::Spanish,
+            Self::Swedish => Lang::Swedish,
+        }
+    }
+}
+
+/// Splits lyric text into chart syllables using Liang's hyphenation patterns, the ones TeX
+/// uses, with per-word exceptions taking precedence over the patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyphenator {
+    language: Language,
+    left_min: usize,
+    right_min: usize,
+    exceptions: HashMap<String, Vec<usize>>,
+}
+
+impl Hyphenator {
+    /// Constructor for `Hyphenator` for the given language.
+    ///
+    /// Unlike typesetting, singing splits off single letters at the start of a word, so the
+    /// first syllable may be one letter long and the last syllable two letters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::hyphenation::{Hyphenator, Language};
+    ///
+    /// let mut hyphenator = Hyphenator::new(Language::English);
+    /// hyphenator.add_exception("ev-ery");
+    /// assert_eq!(
+    ///     hyphenator.hyphenate_line("Every beautiful day"),
+    ///     vec!["Ev-", "ery", "beau-", "ti-", "ful", "day"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            left_min: 1,
+            right_min: 2,
+            exceptions: HashMap::new(),
+        }
+    }
+
+    /// Changes the least number of letters before the first and after the last break in a word.
+    #[must_use]
+    pub fn with_bounds(mut self, left_min: usize, right_min: usize) -> Self {
+        self.left_min = left_min.max(1);
+        self.right_min = right_min.max(1);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_language(&self) -> Language {
+        self.language
+    }
+
+    /// Adds a word with its breaks marked by `-`, as in TeX's `\hyphenation`, overriding the
+    /// patterns for that word regardless of case. A word without any `-` is never split.
+    pub fn add_exception(&mut self, hyphenated: &str) {
+        let mut breaks = vec![];
+        let mut length = 0;
+        for piece in hyphenated.split('-') {
+            if length > 0 && !piece.is_empty() {
+                breaks.push(length);
+            }
+            length += piece.chars().count();
+        }
+        self.exceptions
+            .insert(hyphenated.replace('-', "").to_lowercase(), breaks);
+    }
+
+    /// Adds every whitespace separated word of a dictionary as an exception.
+    pub fn add_exceptions(&mut self, dictionary: &str) {
+        for word in dictionary.split_whitespace() {
+            self.add_exception(word);
+        }
+    }
+
+    /// Splits a single word into chart syllables, with a trailing `-` on every syllable but the
+    /// last.
+    ///
+    /// Punctuation around the word stays attached to the first and last syllable. Existing
+    /// hyphens in the word are kept as syllable breaks that stay on screen, so the syllable
+    /// before one ends in Clone Hero's `=` instead of `-`.
+    #[must_use]
+    pub fn hyphenate_word(&self, word: &str) -> Vec<String> {
+        let parts: Vec<Vec<String>> = word
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .map(|part| self.split_part(part))
+            .collect();
+        let last_part = parts.len().saturating_sub(1);
+        parts
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, syllables)| {
+                let last = syllables.len().saturating_sub(1);
+                syllables.into_iter().enumerate().map(move |(j, syllable)| {
+                    if j < last {
+                        syllable + "-"
+                    } else if i < last_part {
+                        syllable + "="
+                    } else {
+                        syllable
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Splits every whitespace separated word of a line into chart syllables.
+    #[must_use]
+    pub fn hyphenate_line(&self, line: &str) -> Vec<String> {
+        line.split_whitespace()
+            .flat_map(|word| self.hyphenate_word(word))
+            .collect()
+    }
+
+    /// Splits a word without hyphens, keeping punctuation around its letters in place.
+    fn split_part(&self, part: &str) -> Vec<String> {
+        let core = part.trim_matches(|c: char| !c.is_alphanumeric());
+        if core.is_empty() {
+            return vec![part.to_string()];
+        }
+        let core_start = part.find(core).unwrap_or(0);
+        let prefix = &part[..core_start];
+        let suffix = &part[core_start + core.len()..];
+
+        let mut syllables: Vec<String> = match self.exceptions.get(&core.to_lowercase()) {
+            Some(breaks) => {
+                let chars: Vec<char> = core.chars().collect();
+                let mut syllables = vec![];
+                let mut start = 0;
+                for end in breaks.iter().copied().chain([chars.len()]) {
+                    if end > start && end <= chars.len() {
+                        syllables.push(chars[start..end].iter().collect());
+                        start = end;
+                    }
+                }
+                syllables
+            }
+            None => hyphenate_bounded(
+                core,
+                self.language.patterns(),
+                self.left_min,
+                self.right_min,
+            )
+            .map(str::to_string)
+            .collect(),
+        };
+        if let Some(first) = syllables.first_mut() {
+            first.insert_str(0, prefix);
+        }
+        if let Some(last) = syllables.last_mut() {
+            last.push_str(suffix);
+        }
+        syllables
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pattern_hyphenation() {
+        let english = Hyphenator::new(Language::English);
+        assert_eq!(
+            english.hyphenate_line("(Happy) emotion, forever!"),
+            vec!["(Hap-", "py)", "e-", "mo-", "tion,", "for-", "ev-", "er!"]
+        );
+        assert_eq!(english.hyphenate_word("well-known"), vec!["well=", "known"]);
+        assert_eq!(
+            english.hyphenate_word("forever-lasting"),
+            vec!["for-", "ev-", "er=", "last-", "ing"]
+        );
+        assert_eq!(
+            Hyphenator::new(Language::German).hyphenate_line("Auf Wiedersehen"),
+            vec!["Auf", "Wie-", "der-", "se-", "hen"]
+        );
+        assert_eq!(
+            Hyphenator::new(Language::English)
+                .with_bounds(2, 3)
+                .hyphenate_word("again"),
+            vec!["again"]
+        );
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut hyphenator = Hyphenator::new(Language::English);
+        hyphenator.add_exceptions("sing-ing\no-ver\n baby");
+        assert_eq!(
+            hyphenator.hyphenate_line("Singing over, baby"),
+            vec!["Sing-", "ing", "o-", "ver,", "baby"]
+        );
+    }
+}
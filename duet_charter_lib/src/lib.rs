@@ -1,6 +1,33 @@
+pub mod ass;
 pub mod chart;
+pub mod diff;
+pub mod difficulty;
+pub mod duet_analysis;
+pub mod hyphenation;
+pub mod lrc;
+pub mod merge;
+pub mod notes;
+pub mod pacing;
+pub mod phrase_inference;
 pub mod phrases;
+pub mod plain_text;
+pub mod quantize;
+pub mod reduction;
+pub mod resolution;
+pub mod scoring;
+pub mod song_ini;
+pub mod star_power_path;
+pub mod stats;
+pub mod structure;
+pub mod subtitles;
+pub mod tempo_edit;
+#[cfg(test)]
+mod test_util;
+pub mod time_range;
+pub mod timing;
+pub mod ultrastar;
 
 pub trait TimestampedEvent {
     fn get_timestamp(&self) -> u32;
+    fn set_timestamp(&mut self, timestamp: u32);
 }
@@ -0,0 +1,773 @@
+use std::fmt::{Display, Formatter};
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+
+use crate::chart::{Chart, LyricEvent};
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::timing::TempoMap;
+use crate::TimestampedEvent;
+
+/// A word of an LRC line, timed if it was preceded by an enhanced LRC `<mm:ss.xx>` tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcWord {
+    microseconds: Option<u64>,
+    text: String,
+}
+
+impl LrcWord {
+    #[must_use]
+    pub const fn get_microseconds(&self) -> Option<u64> {
+        self.microseconds
+    }
+
+    #[must_use]
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// A single timed line of an LRC file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcLine {
+    microseconds: u64,
+    end_microseconds: Option<u64>,
+    singer: Singer,
+    words: Vec<LrcWord>,
+}
+
+impl LrcLine {
+    #[must_use]
+    pub const fn get_microseconds(&self) -> u64 {
+        self.microseconds
+    }
+
+    /// End of the line, given by a trailing enhanced LRC tag without any text after it.
+    #[must_use]
+    pub const fn get_end_microseconds(&self) -> Option<u64> {
+        self.end_microseconds
+    }
+
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    #[must_use]
+    pub const fn get_words(&self) -> &Vec<LrcWord> {
+        &self.words
+    }
+}
+
+/// Settings for turning LRC lines into chart lyric events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcImportOptions {
+    /// Longest a syllable without its own timestamp may last, in microseconds. Keeps lines
+    /// followed by an instrumental break from stretching their syllables across the break.
+    pub max_syllable_micros: u64,
+}
+
+impl Default for LrcImportOptions {
+    fn default() -> Self {
+        Self {
+            max_syllable_micros: 400_000,
+        }
+    }
+}
+
+/// Settings for writing phrases as LRC.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LrcExportOptions {
+    /// Write enhanced LRC, with a `<mm:ss.xx>` tag before every syllable and at the end of
+    /// each line. Plain LRC only times the lines, and clears the screen when a phrase ends.
+    pub enhanced: bool,
+}
+
+/// Synced lyrics in the (enhanced) LRC format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrcLyrics {
+    tags: Vec<(String, String)>,
+    lines: Vec<LrcLine>,
+    singer_tags: bool,
+}
+
+impl LrcLyrics {
+    /// Creates an `LrcLyrics` struct by parsing the contents of an .lrc file.
+    ///
+    /// Lines with several time tags are repeated at each of them, the `[offset:]` tag is applied
+    /// to all timestamps, and a leading `v1:` or `v2:` marks a line as main or duet.
+    ///
+    /// # Arguments
+    ///
+    /// * `lrc_file`: the contents of the .lrc file to parse.
+    ///
+    /// returns: `Result<LrcLyrics, eyre::Report>`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a timestamp or the offset tag cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::chart::TempoEvent;
+    /// use duet_charter_lib::lrc::{LrcImportOptions, LrcLyrics};
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let lrc = LrcLyrics::new("[00:01.00]<00:01.00>Hel-<00:01.25>lo <00:01.50>world<00:02.00>").unwrap();
+    /// let tempo_map = TempoMap::new(&[TempoEvent::Beat { timestamp: 0, milli_bpm: 120_000 }], 192);
+    /// let events = lrc.to_lyric_events(&tempo_map, &LrcImportOptions::default());
+    /// assert_eq!(events.len(), 5);
+    /// ```
+    pub fn new(lrc_file: &str) -> Result<Self> {
+        let line_regex = Regex::new(r"^(?P<times>(?:\[\d+:\d+(?:[.:]\d+)?])+)(?P<text>.*)$")?;
+        let time_regex = Regex::new(r"\[(?P<time>\d+:\d+(?:[.:]\d+)?)]")?;
+        let tag_regex = Regex::new(r"^\[(?P<name>[A-Za-z#]+):(?P<value>[^]]*)]$")?;
+        let word_regex = Regex::new(r"<(?P<time>\d+:\d+(?:[.:]\d+)?)>")?;
+
+        let mut tags = vec![];
+        let mut lines = vec![];
+        let mut singer_tags = false;
+        for raw_line in lrc_file.lines() {
+            let raw_line = raw_line.trim().trim_start_matches('\u{feff}');
+            if let Some(captures) = line_regex.captures(raw_line) {
+                let (singer, text) = Self::split_singer(&captures["text"]);
+                singer_tags |= text.len() < captures["text"].len();
+                let (words, end_microseconds) = Self::parse_words(&word_regex, text)?;
+                for time in time_regex.captures_iter(&captures["times"]) {
+                    lines.push(LrcLine {
+                        microseconds: Self::parse_time(&time["time"])?,
+                        end_microseconds,
+                        singer,
+                        words: words.clone(),
+                    });
+                }
+            } else if let Some(captures) = tag_regex.captures(raw_line) {
+                tags.push((
+                    captures["name"].to_string(),
+                    captures["value"].trim().to_string(),
+                ));
+            }
+        }
+
+        let offset_millis: i64 = match tags.iter().find(|(name, _)| name == "offset") {
+            Some((_, value)) => value
+                .trim_start_matches('+')
+                .parse()
+                .wrap_err(format!("{:?}", value))?,
+            None => 0,
+        };
+        let shift = |microseconds: u64| -> u64 {
+            microseconds.saturating_add_signed(-offset_millis.saturating_mul(1000))
+        };
+        for line in &mut lines {
+            line.microseconds = shift(line.microseconds);
+            line.end_microseconds = line.end_microseconds.map(shift);
+            for word in &mut line.words {
+                word.microseconds = word.microseconds.map(shift);
+            }
+        }
+        lines.sort_by_key(|line| line.microseconds);
+        Ok(Self {
+            tags,
+            lines,
+            singer_tags,
+        })
+    }
+
+    /// Creates an `LrcLyrics` struct from the phrases of a chart.
+    ///
+    /// Main and duet phrases are interleaved by start time, and lines are marked `v1:` and `v2:`
+    /// when there are duet phrases. Syllables keep the chart's trailing `-`, which is removed
+    /// when writing, joining the syllables as `Phrase`'s `Display` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `phrases`: the phrases to write.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    /// * `options`: the export settings.
+    ///
+    /// returns: `LrcLyrics`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::lrc::{LrcExportOptions, LrcLyrics};
+    /// use duet_charter_lib::phrases::LyricPhraseCollection;
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let lrc = LrcLyrics::from_phrases(
+    ///     &LyricPhraseCollection::new(chart.get_lyrics()),
+    ///     &TempoMap::from_chart(&chart),
+    ///     &LrcExportOptions { enhanced: true },
+    /// );
+    /// let lrc_file = lrc.to_string();
+    /// ```
+    #[must_use]
+    pub fn from_phrases(
+        phrases: &LyricPhraseCollection,
+        tempo_map: &TempoMap,
+        options: &LrcExportOptions,
+    ) -> Self {
+        let mut timed_phrases: Vec<(Singer, &Phrase)> = [Singer::Main, Singer::Duet]
+            .iter()
+            .flat_map(|singer| {
+                phrases
+                    .get_phrases(*singer)
+                    .iter()
+                    .map(move |phrase| (*singer, phrase))
+            })
+            .filter(|(_, phrase)| !phrase.get_lyrics().is_empty())
+            .collect();
+        timed_phrases.sort_by_key(|(_, phrase)| phrase.get_start_timestamp());
+
+        let mut lines = vec![];
+        for (i, (singer, phrase)) in timed_phrases.iter().enumerate() {
+            let end_microseconds = tempo_map.ticks_to_micros(phrase.get_end_timestamp());
+            lines.push(LrcLine {
+                microseconds: tempo_map.ticks_to_micros(phrase.get_start_timestamp()),
+                end_microseconds: Some(end_microseconds).filter(|_| options.enhanced),
+                singer: *singer,
+                words: phrase
+                    .get_display_syllables()
+                    .into_iter()
+                    .map(|(timestamp, syllable, joined)| LrcWord {
+                        microseconds: Some(tempo_map.ticks_to_micros(timestamp))
+                            .filter(|_| options.enhanced),
+                        text: if joined { syllable + "-" } else { syllable },
+                    })
+                    .collect(),
+            });
+            let next_start = timed_phrases
+                .get(i + 1)
+                .map(|(_, next)| next.get_start_timestamp());
+            if !options.enhanced && next_start.is_none_or(|next| next > phrase.get_end_timestamp())
+            {
+                lines.push(LrcLine {
+                    microseconds: end_microseconds,
+                    end_microseconds: None,
+                    singer: *singer,
+                    words: vec![],
+                });
+            }
+        }
+        Self {
+            tags: vec![],
+            lines,
+            singer_tags: !phrases.get_duet_phrases().is_empty(),
+        }
+    }
+
+    /// Creates an `LrcLyrics` struct from a chart's phrases, with `ti`, `ar`, `al` and `by` tags
+    /// taken from the chart's `Name`, `Artist`, `Album` and `Charter` properties.
+    #[must_use]
+    pub fn from_chart(chart: &Chart, options: &LrcExportOptions) -> Self {
+        let mut lrc = Self::from_phrases(
+            &LyricPhraseCollection::new(chart.get_lyrics()),
+            &TempoMap::from_chart(chart),
+            options,
+        );
+        lrc.tags = [
+            ("ti", "Name"),
+            ("ar", "Artist"),
+            ("al", "Album"),
+            ("by", "Charter"),
+        ]
+        .iter()
+        .filter_map(|(tag, property)| {
+            let value = chart
+                .get_properties()
+                .get(*property)?
+                .trim()
+                .trim_matches('"');
+            Some((tag.to_string(), value.to_string())).filter(|_| !value.is_empty())
+        })
+        .collect();
+        lrc
+    }
+
+    fn format_time(microseconds: u64) -> String {
+        let centiseconds = (microseconds + 5_000) / 10_000;
+        format!(
+            "{:02}:{:02}.{:02}",
+            centiseconds / 6_000,
+            centiseconds / 100 % 60,
+            centiseconds % 100
+        )
+    }
+
+    fn split_singer(text: &str) -> (Singer, &str) {
+        let trimmed = text.trim_start();
+        if let Some(rest) = trimmed.strip_prefix("v1:") {
+            (Singer::Main, rest)
+        } else if let Some(rest) = trimmed.strip_prefix("v2:") {
+            (Singer::Duet, rest)
+        } else {
+            (Singer::Main, text)
+        }
+    }
+
+    /// Reads the words of a line. A word tag directly between two pieces of text splits a word
+    /// into syllables, which are stored with the chart's trailing `-`.
+    fn parse_words(word_regex: &Regex, text: &str) -> Result<(Vec<LrcWord>, Option<u64>)> {
+        fn push_segment(words: &mut Vec<LrcWord>, segment: &str, time: Option<u64>) {
+            for (i, word) in segment.split_whitespace().enumerate() {
+                words.push(LrcWord {
+                    microseconds: if i == 0 { time } else { None },
+                    text: word.to_string(),
+                });
+            }
+        }
+
+        let mut words = vec![];
+        let mut pending_time = None;
+        let mut last_end = 0;
+        for captures in word_regex.captures_iter(text) {
+            let tag = captures
+                .get(0)
+                .ok_or_else(|| eyre!("regex does not contain a match"))?;
+            let segment = &text[last_end..tag.start()];
+            push_segment(&mut words, segment, pending_time);
+            let joined = !segment.is_empty()
+                && !segment.ends_with(char::is_whitespace)
+                && text[tag.end()..].starts_with(|c: char| !c.is_whitespace());
+            if let Some(word) = words.last_mut().filter(|_| joined) {
+                if !word.text.ends_with('-') {
+                    word.text.push('-');
+                }
+            }
+            pending_time = Some(Self::parse_time(&captures["time"])?);
+            last_end = tag.end();
+        }
+        let rest = &text[last_end..];
+        if rest.trim().is_empty() {
+            Ok((words, pending_time))
+        } else {
+            push_segment(&mut words, rest, pending_time);
+            Ok((words, None))
+        }
+    }
+
+    /// Reads an `mm:ss.xx` timestamp into microseconds.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn parse_time(time: &str) -> Result<u64> {
+        let (minutes, seconds) = time
+            .split_once(':')
+            .ok_or_else(|| eyre!("No minutes found in {}", time))?;
+        let minutes: u64 = minutes.parse().wrap_err(format!("{:?}", time))?;
+        let seconds: f64 = seconds
+            .replacen(':', ".", 1)
+            .parse()
+            .wrap_err(format!("{:?}", time))?;
+        Ok(minutes * 60_000_000 + (seconds * 1_000_000.0).round() as u64)
+    }
+
+    /// The ID tags of the file, such as `ar` for the artist, in file order.
+    #[must_use]
+    pub const fn get_tags(&self) -> &Vec<(String, String)> {
+        &self.tags
+    }
+
+    #[must_use]
+    pub const fn get_lines(&self) -> &Vec<LrcLine> {
+        &self.lines
+    }
+
+    /// Converts the lines into phrase start, lyric and phrase end events.
+    ///
+    /// Every line with text becomes one phrase. Words are split into syllables on `-`, keeping
+    /// the trailing `-` the chart format uses to join syllables. Syllables with an enhanced LRC
+    /// timestamp keep it, the others are spread evenly up to the next timed syllable or the end
+    /// of the line, but no longer than `options.max_syllable_micros` each.
+    ///
+    /// # Arguments
+    ///
+    /// * `tempo_map`: the tempo map of the chart, used to turn times into ticks.
+    /// * `options`: the import settings.
+    ///
+    /// returns: `Vec<LyricEvent>`, ordered by timestamp.
+    #[must_use]
+    pub fn to_lyric_events(
+        &self,
+        tempo_map: &TempoMap,
+        options: &LrcImportOptions,
+    ) -> Vec<LyricEvent> {
+        let mut events = vec![];
+        for (i, line) in self.lines.iter().enumerate() {
+            let mut syllables: Vec<(Option<u64>, String)> =
+                line.words
+                    .iter()
+                    .flat_map(|word| {
+                        split_syllables(&word.text).into_iter().enumerate().map(
+                            move |(j, syllable)| (word.microseconds.filter(|_| j == 0), syllable),
+                        )
+                    })
+                    .collect();
+            if syllables.is_empty() {
+                continue;
+            }
+            if syllables[0].0.is_none() {
+                syllables[0].0 = Some(line.microseconds);
+            }
+            let next_line = self.lines.get(i + 1).map(LrcLine::get_microseconds);
+            let line_end = line.end_microseconds.or(next_line);
+            let times = spread_times(&syllables, line_end, options.max_syllable_micros);
+            let last_time = times.last().copied().unwrap_or(line.microseconds);
+            let end = line.end_microseconds.unwrap_or_else(|| {
+                let capped = last_time + options.max_syllable_micros;
+                next_line.map_or(capped, |next| next.min(capped))
+            });
+
+            let start_tick = tempo_map.micros_to_ticks(line.microseconds.min(times[0]));
+            let mut last_tick = start_tick;
+            events.push(match line.singer {
+                Singer::Main => LyricEvent::PhraseStart {
+                    timestamp: start_tick,
+                },
+                Singer::Duet => LyricEvent::DuetPhraseStart {
+                    timestamp: start_tick,
+                },
+            });
+            for ((_, text), time) in syllables.into_iter().zip(times) {
+                let timestamp = tempo_map.micros_to_ticks(time).max(last_tick);
+                last_tick = timestamp;
+                events.push(match line.singer {
+                    Singer::Main => LyricEvent::Lyric { timestamp, text },
+                    Singer::Duet => LyricEvent::DuetLyric { timestamp, text },
+                });
+            }
+            let end_tick = tempo_map.micros_to_ticks(end).max(last_tick + 1);
+            events.push(match line.singer {
+                Singer::Main => LyricEvent::PhraseEnd {
+                    timestamp: end_tick,
+                },
+                Singer::Duet => LyricEvent::DuetPhraseEnd {
+                    timestamp: end_tick,
+                },
+            });
+        }
+        events.sort_by_key(TimestampedEvent::get_timestamp);
+        events
+    }
+}
+
+impl Display for LrcLyrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{}:{}]", name, value)?;
+        }
+        for line in &self.lines {
+            write!(f, "[{}]", Self::format_time(line.microseconds))?;
+            if self.singer_tags && !line.words.is_empty() {
+                match line.singer {
+                    Singer::Main => write!(f, "v1: ")?,
+                    Singer::Duet => write!(f, "v2: ")?,
+                }
+            }
+            let mut separator = "";
+            for word in &line.words {
+                write!(f, "{}", separator)?;
+                if let Some(microseconds) = word.microseconds {
+                    write!(f, "<{}>", Self::format_time(microseconds))?;
+                }
+                match word.text.strip_suffix('-') {
+                    Some(syllable) => {
+                        write!(f, "{}", syllable)?;
+                        separator = "";
+                    }
+                    None => {
+                        write!(f, "{}", word.text)?;
+                        separator = " ";
+                    }
+                }
+            }
+            if let Some(end) = line.end_microseconds {
+                write!(f, "{}<{}>", separator, Self::format_time(end))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a word into chart syllables on `-`, keeping the `-` on all but the last syllable.
+/// Hyphens at the start of a word are kept as part of the text.
+pub(crate) fn split_syllables(word: &str) -> Vec<String> {
+    let body = word.trim_start_matches('-');
+    let prefix = &word[..word.len() - body.len()];
+    let pieces: Vec<&str> = body.split('-').filter(|x| !x.is_empty()).collect();
+    let last = pieces.len().saturating_sub(1);
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(i, piece)| {
+            let prefix = if i == 0 { prefix } else { "" };
+            if i < last || word.ends_with('-') {
+                format!("{}{}-", prefix, piece)
+            } else {
+                format!("{}{}", prefix, piece)
+            }
+        })
+        .collect()
+}
+
+/// Fills in the times of untimed syllables by spreading them evenly between the timed ones.
+fn spread_times(
+    syllables: &[(Option<u64>, String)],
+    line_end: Option<u64>,
+    max_syllable_micros: u64,
+) -> Vec<u64> {
+    let mut times = Vec::with_capacity(syllables.len());
+    let mut i = 0;
+    while i < syllables.len() {
+        let start = syllables[i]
+            .0
+            .unwrap_or(0)
+            .max(times.last().copied().unwrap_or(0));
+        let next = (i + 1..syllables.len()).find(|j| syllables[*j].0.is_some());
+        let count = next.unwrap_or(syllables.len()) - i;
+        let limit = next.and_then(|j| syllables[j].0).or(line_end);
+        let step = limit.map_or(max_syllable_micros, |limit| {
+            (limit.saturating_sub(start) / count as u64).min(max_syllable_micros)
+        });
+        for k in 0..count {
+            times.push(start + step * k as u64);
+        }
+        i += count;
+    }
+    times
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use crate::chart::TempoEvent;
+    use crate::phrases::LyricPhraseCollection;
+
+    use super::*;
+
+    fn tempo_map() -> TempoMap {
+        TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        )
+    }
+
+    #[test]
+    fn line_level_import() -> Result<()> {
+        let lrc = LrcLyrics::new(
+            "[ar:Someone]\n[00:01.00]Hel-lo world\n[00:03.00]\n[00:04.00]v2:Bye now\n",
+        )?;
+        assert_eq!(
+            lrc.get_tags(),
+            &vec![("ar".to_string(), "Someone".to_string())]
+        );
+        let events = lrc.to_lyric_events(&tempo_map(), &LrcImportOptions::default());
+        let phrases = LyricPhraseCollection::new(&events);
+        let main = phrases.get_main_phrases();
+        assert_eq!(main.len(), 1);
+        assert_eq!(main[0].get_start_timestamp(), 384);
+        let timestamps: Vec<u32> = main[0]
+            .get_lyrics()
+            .iter()
+            .map(TimestampedEvent::get_timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![384, 538, 691]);
+        assert_eq!(main[0].get_end_timestamp(), 845);
+        assert!(main[0].to_string().ends_with("phrase: Hello world"));
+        assert_eq!(phrases.get_duet_phrases().len(), 1);
+        assert_eq!(phrases.get_duet_phrases()[0].get_start_timestamp(), 1536);
+        Ok(())
+    }
+
+    #[test]
+    fn enhanced_import() -> Result<()> {
+        let lrc = LrcLyrics::new(
+            "[offset:+500]\n[00:01.50]<00:01.50>Hel-<00:02.00>lo <00:02.50>world<00:03.00>\n",
+        )?;
+        let events = lrc.to_lyric_events(&tempo_map(), &LrcImportOptions::default());
+        assert_eq!(
+            events,
+            vec![
+                LyricEvent::PhraseStart { timestamp: 384 },
+                LyricEvent::Lyric {
+                    timestamp: 384,
+                    text: "Hel-".to_string()
+                },
+                LyricEvent::Lyric {
+                    timestamp: 576,
+                    text: "lo".to_string()
+                },
+                LyricEvent::Lyric {
+                    timestamp: 768,
+                    text: "world".to_string()
+                },
+                LyricEvent::PhraseEnd { timestamp: 960 },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_time_tags() -> Result<()> {
+        let lrc = LrcLyrics::new("[00:10.00][00:02.00]La la\n")?;
+        let starts: Vec<u64> = lrc
+            .get_lines()
+            .iter()
+            .map(LrcLine::get_microseconds)
+            .collect();
+        assert_eq!(starts, vec![2_000_000, 10_000_000]);
+        Ok(())
+    }
+
+    fn example_phrases() -> LyricPhraseCollection {
+        LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 384 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+            LyricEvent::DuetPhraseStart { timestamp: 768 },
+            LyricEvent::DuetLyric {
+                timestamp: 768,
+                text: "Bye".to_string(),
+            },
+            LyricEvent::DuetPhraseEnd { timestamp: 960 },
+        ])
+    }
+
+    #[test]
+    fn line_level_export() {
+        let lrc = LrcLyrics::from_phrases(
+            &example_phrases(),
+            &tempo_map(),
+            &LrcExportOptions::default(),
+        );
+        assert_eq!(
+            lrc.to_string(),
+            "[00:01.00]v1: Hello world\n[00:02.00]v2: Bye\n[00:02.50]\n"
+        );
+    }
+
+    #[test]
+    fn enhanced_export() -> Result<()> {
+        let lrc = LrcLyrics::from_phrases(
+            &example_phrases(),
+            &tempo_map(),
+            &LrcExportOptions { enhanced: true },
+        );
+        let lrc_file = lrc.to_string();
+        assert_eq!(
+            lrc_file,
+            "[00:01.00]v1: <00:01.00>Hel<00:01.25>lo <00:01.50>world <00:02.00>\n\
+             [00:02.00]v2: <00:02.00>Bye <00:02.50>\n"
+        );
+        let events =
+            LrcLyrics::new(&lrc_file)?.to_lyric_events(&tempo_map(), &LrcImportOptions::default());
+        assert_eq!(LyricPhraseCollection::new(&events), example_phrases());
+        Ok(())
+    }
+
+    #[test]
+    fn export_chart_markup() {
+        let phrases = LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 384 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo#".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 528,
+                text: "+".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+        ]);
+        let lrc =
+            LrcLyrics::from_phrases(&phrases, &tempo_map(), &LrcExportOptions { enhanced: true });
+        assert_eq!(
+            lrc.to_string(),
+            "[00:01.00]<00:01.00>Hel<00:01.25>lo <00:01.50>world <00:02.00>\n"
+        );
+    }
+
+    #[test]
+    fn export_round_trip() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            export_round_trip_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn export_round_trip_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let tempo_map = TempoMap::from_chart(&chart);
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        let lrc =
+            LrcLyrics::from_phrases(&phrases, &tempo_map, &LrcExportOptions { enhanced: true });
+        let events = LrcLyrics::new(&lrc.to_string())?
+            .to_lyric_events(&tempo_map, &LrcImportOptions::default());
+        // phrases closer together than the centiseconds of LRC may merge, so compare the words,
+        // and LRC cannot tell a shown hyphen from a syllable break, so leave hyphens out
+        let words = |phrases: &[Phrase]| -> Vec<String> {
+            phrases
+                .iter()
+                .flat_map(|phrase| {
+                    phrase
+                        .get_display_text()
+                        .split_whitespace()
+                        .map(|word| word.replace('-', ""))
+                        .filter(|word| !word.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        assert_eq!(
+            words(LyricPhraseCollection::new(&events).get_main_phrases()),
+            words(phrases.get_main_phrases())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+
+use crate::chart::{Chart, KeyPressEvent, LyricEvent, TempoEvent};
+use crate::phrases::{LyricPhraseCollection, Singer};
+use crate::TimestampedEvent;
+
+/// Stream of lyric events that merges on its own, with the phrases of a singer as its units.
+const MAIN_STREAM: u8 = 0;
+const DUET_STREAM: u8 = 1;
+const OTHER_STREAM: u8 = 2;
+
+/// A range of positions in one stream of events, inclusive.
+type Span = (u8, u64, u64);
+
+/// Events of one range of a stream, where both sides changed something.
+struct Cluster<T> {
+    stream: u8,
+    base: Vec<T>,
+    ours: Vec<T>,
+    theirs: Vec<T>,
+}
+
+/// Three-way merges lists of events. Every position where a side differs from the base is
+/// widened to the spans containing it, such as its phrase, and overlapping changes of both
+/// sides are merged only when they agree, otherwise our side is kept and a conflict returned.
+///
+/// # Arguments
+///
+/// * `versions`: the base, our and their events.
+/// * `key`: the stream of an event and its position in that stream.
+/// * `spans`: the units of each version that change as a whole, such as phrases.
+fn merge_events<T: Clone + PartialEq + TimestampedEvent>(
+    versions: [&[T]; 3],
+    key: impl Fn(&T) -> (u8, u64),
+    spans: [&[Span]; 3],
+) -> (Vec<T>, Vec<Cluster<T>>) {
+    let group = |events: &[T]| {
+        let mut groups: BTreeMap<(u8, u64), Vec<T>> = BTreeMap::new();
+        for event in events {
+            groups.entry(key(event)).or_default().push(event.clone());
+        }
+        groups
+    };
+    let [base, ours, theirs] = versions;
+    let base_groups = group(base);
+    let changed = |side: &[T], side_spans: &[Span]| {
+        let side_groups = group(side);
+        let keys: BTreeSet<&(u8, u64)> = base_groups.keys().chain(side_groups.keys()).collect();
+        keys.into_iter()
+            .filter(|key| base_groups.get(key) != side_groups.get(key))
+            .map(|(stream, position)| {
+                spans[0]
+                    .iter()
+                    .chain(side_spans)
+                    .filter(|(span_stream, start, end)| {
+                        span_stream == stream && (start..=end).contains(&position)
+                    })
+                    .fold(
+                        (*stream, *position, *position),
+                        |(stream, start, end), (_, span_start, span_end)| {
+                            (stream, start.min(*span_start), end.max(*span_end))
+                        },
+                    )
+            })
+            .collect::<Vec<Span>>()
+    };
+    let mut regions: Vec<(Span, bool)> = changed(ours, spans[1])
+        .into_iter()
+        .map(|span| (span, true))
+        .chain(
+            changed(theirs, spans[2])
+                .into_iter()
+                .map(|span| (span, false)),
+        )
+        .collect();
+    regions.sort_by_key(|((stream, start, end), _)| (*stream, *start, *end));
+
+    // (span, changed by us, changed by them)
+    let mut clusters: Vec<(Span, bool, bool)> = vec![];
+    for ((stream, start, end), by_us) in regions {
+        match clusters.last_mut() {
+            Some(((last_stream, _, last_end), ours_changed, theirs_changed))
+                if *last_stream == stream && start <= *last_end =>
+            {
+                *last_end = (*last_end).max(end);
+                *ours_changed |= by_us;
+                *theirs_changed |= !by_us;
+            }
+            _ => clusters.push(((stream, start, end), by_us, !by_us)),
+        }
+    }
+
+    // Clusters are disjoint and sorted, so the one holding a position is found by its start.
+    let cluster_of = |event: &T| {
+        let (stream, position) = key(event);
+        let index = clusters
+            .partition_point(|((span_stream, start, _), _, _)| {
+                (*span_stream, *start) <= (stream, position)
+            })
+            .checked_sub(1)?;
+        let ((span_stream, _, end), _, _) = clusters[index];
+        (span_stream == stream && position <= end).then_some(index)
+    };
+    let split = |events: &[T]| {
+        let mut outside = vec![];
+        let mut inside: Vec<Vec<T>> = vec![vec![]; clusters.len()];
+        for event in events {
+            match cluster_of(event) {
+                Some(index) => inside[index].push(event.clone()),
+                None => outside.push(event.clone()),
+            }
+        }
+        (outside, inside)
+    };
+    let (mut merged, base_inside) = split(base);
+    let (_, ours_inside) = split(ours);
+    let (_, theirs_inside) = split(theirs);
+    let mut conflicts = vec![];
+    for (
+        (((stream, _, _), ours_changed, theirs_changed), base_events),
+        (our_events, their_events),
+    ) in clusters
+        .iter()
+        .zip(base_inside)
+        .zip(ours_inside.into_iter().zip(theirs_inside))
+    {
+        let chosen = match (ours_changed, theirs_changed) {
+            (true, false) => our_events,
+            (false, true) => their_events,
+            _ if our_events == their_events => our_events,
+            _ => {
+                conflicts.push(Cluster {
+                    stream: *stream,
+                    base: base_events,
+                    ours: our_events.clone(),
+                    theirs: their_events,
+                });
+                our_events
+            }
+        };
+        merged.extend(chosen);
+    }
+
+    // Events on the same tick keep their order from our side, or else from theirs.
+    merged.sort_by_key(TimestampedEvent::get_timestamp);
+    let orders = [by_tick(ours), by_tick(theirs), by_tick(base)];
+    let position = |order: &[&T], event: &T| order.iter().position(|other| *other == event);
+    for group in merged.chunk_by_mut(|a, b| a.get_timestamp() == b.get_timestamp()) {
+        let order = orders
+            .iter()
+            .filter_map(|ticks| ticks.get(&group[0].get_timestamp()))
+            .find(|order| group.iter().all(|event| position(order, event).is_some()));
+        if let Some(order) = order {
+            group.sort_by_cached_key(|event| position(order, event));
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Groups events by tick, keeping their order.
+fn by_tick<T: TimestampedEvent>(events: &[T]) -> BTreeMap<u32, Vec<&T>> {
+    let mut ticks: BTreeMap<u32, Vec<&T>> = BTreeMap::new();
+    for event in events {
+        ticks.entry(event.get_timestamp()).or_default().push(event);
+    }
+    ticks
+}
+
+/// Events of a chart part that both sides of a merge changed differently, as written in a
+/// .chart file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictRegion {
+    start: u32,
+    end: u32,
+    base: Vec<String>,
+    ours: Vec<String>,
+    theirs: Vec<String>,
+}
+
+impl ConflictRegion {
+    fn new<T: TimestampedEvent + Display>(cluster: &Cluster<T>) -> Self {
+        let all = || {
+            cluster
+                .base
+                .iter()
+                .chain(&cluster.ours)
+                .chain(&cluster.theirs)
+                .map(TimestampedEvent::get_timestamp)
+        };
+        let lines = |events: &[T]| events.iter().map(ToString::to_string).collect();
+        Self {
+            start: all().min().unwrap_or(0),
+            end: all().max().unwrap_or(0),
+            base: lines(&cluster.base),
+            ours: lines(&cluster.ours),
+            theirs: lines(&cluster.theirs),
+        }
+    }
+
+    /// The first tick of the conflicting events.
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start
+    }
+
+    /// The last tick of the conflicting events.
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end
+    }
+
+    #[must_use]
+    pub const fn get_base(&self) -> &Vec<String> {
+        &self.base
+    }
+
+    #[must_use]
+    pub const fn get_ours(&self) -> &Vec<String> {
+        &self.ours
+    }
+
+    #[must_use]
+    pub const fn get_theirs(&self) -> &Vec<String> {
+        &self.theirs
+    }
+}
+
+impl Display for ConflictRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "at {}", self.start)
+        } else {
+            write!(f, "from {} to {}", self.start, self.end)
+        }
+    }
+}
+
+/// A part of a chart both sides of a merge changed differently. The merged chart keeps our
+/// side of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    Property {
+        name: String,
+        base: Option<String>,
+        ours: Option<String>,
+        theirs: Option<String>,
+    },
+    TempoMap(ConflictRegion),
+    /// One or more phrases of a singer, with the events of the phrases overlapping the edits.
+    Phrase {
+        singer: Singer,
+        region: ConflictRegion,
+    },
+    /// Section and other non-lyric events.
+    Events(ConflictRegion),
+    /// One or more chords or special spans of a track.
+    Notes {
+        track: String,
+        region: ConflictRegion,
+    },
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Property {
+                name, ours, theirs, ..
+            } => write!(
+                f,
+                "[Song] {}: ours {}, theirs {}",
+                name,
+                ours.as_deref().unwrap_or("removed"),
+                theirs.as_deref().unwrap_or("removed")
+            ),
+            Self::TempoMap(region) => write!(f, "[SyncTrack] tempo events {}", region),
+            Self::Phrase { singer, region } => {
+                write!(f, "[Events] {} phrases {}", singer, region)
+            }
+            Self::Events(region) => write!(f, "[Events] events {}", region),
+            Self::Notes { track, region } => write!(f, "[{}] notes {}", track, region),
+        }
+    }
+}
+
+/// Position of a lyric event in its stream. Phrase ends go half a tick early, so a phrase
+/// ending on the tick the next one starts does not overlap it.
+fn lyric_key(event: &LyricEvent) -> (u8, u64) {
+    let (stream, end) = match event {
+        LyricEvent::PhraseStart { .. } | LyricEvent::Lyric { .. } => (MAIN_STREAM, false),
+        LyricEvent::PhraseEnd { .. } => (MAIN_STREAM, true),
+        LyricEvent::DuetPhraseStart { .. } | LyricEvent::DuetLyric { .. } => (DUET_STREAM, false),
+        LyricEvent::DuetPhraseEnd { .. } => (DUET_STREAM, true),
+        LyricEvent::Section { .. } | LyricEvent::OtherLyricEvent { .. } => (OTHER_STREAM, false),
+    };
+    let position = u64::from(event.get_timestamp()) * 2;
+    (
+        stream,
+        if end {
+            position.saturating_sub(1)
+        } else {
+            position
+        },
+    )
+}
+
+fn phrase_spans(lyrics: &[LyricEvent]) -> Vec<Span> {
+    let phrases = LyricPhraseCollection::new(lyrics);
+    [(Singer::Main, MAIN_STREAM), (Singer::Duet, DUET_STREAM)]
+        .into_iter()
+        .flat_map(|(singer, stream)| {
+            phrases.get_phrases(singer).iter().map(move |phrase| {
+                let start = u64::from(phrase.get_start_timestamp()) * 2;
+                let end = u64::from(phrase.get_end_timestamp()) * 2;
+                (stream, start, end.saturating_sub(1).max(start))
+            })
+        })
+        .collect()
+}
+
+fn merge_option<T: Clone + PartialEq>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == base || ours == theirs {
+        Some(theirs.clone())
+    } else if theirs == base {
+        Some(ours.clone())
+    } else {
+        None
+    }
+}
+
+/// The result of merging two edited versions of a chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartMerge {
+    chart: Chart,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl ChartMerge {
+    /// Three-way merges our and their edits of a base chart, such as lyric fixes made while
+    /// the original charter keeps fixing notes.
+    ///
+    /// Properties merge by name, tempo events and notes by tick, and lyrics by phrase, with
+    /// each singer's phrases and the section events merging on their own. An edit only one
+    /// side made is taken as is; edits both sides made to the same property, tick or phrase
+    /// merge when they are the same and are a conflict otherwise, for which our side is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`: the chart both sides started from.
+    /// * `ours`: our edited chart.
+    /// * `theirs`: their edited chart.
+    ///
+    /// returns: `ChartMerge`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::merge::ChartMerge;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let base = Chart::new(&file_content).unwrap();
+    /// let mut ours = base.clone();
+    /// ours.set_property("Charter", "\"us\"".to_string());
+    /// let mut theirs = base.clone();
+    /// theirs.get_key_presses_mut().remove("ExpertSingle");
+    ///
+    /// let merge = ChartMerge::new(&base, &ours, &theirs);
+    /// assert!(merge.is_clean());
+    /// assert_eq!(merge.get_chart().get_properties()["Charter"], "\"us\"");
+    /// assert!(!merge.get_chart().get_key_presses().contains_key("ExpertSingle"));
+    /// ```
+    #[must_use]
+    pub fn new(base: &Chart, ours: &Chart, theirs: &Chart) -> Self {
+        let mut conflicts = vec![];
+
+        let mut properties = ours.get_properties().clone();
+        let names: BTreeSet<&String> = base
+            .get_properties()
+            .keys()
+            .chain(ours.get_properties().keys())
+            .chain(theirs.get_properties().keys())
+            .collect();
+        for name in names {
+            let [base_value, our_value, their_value] =
+                [base, ours, theirs].map(|chart| chart.get_properties().get(name).cloned());
+            match merge_option(&base_value, &our_value, &their_value) {
+                Some(Some(value)) => {
+                    properties.insert(name.clone(), value);
+                }
+                Some(None) => {
+                    properties.remove(name);
+                }
+                None => conflicts.push(MergeConflict::Property {
+                    name: name.clone(),
+                    base: base_value,
+                    ours: our_value,
+                    theirs: their_value,
+                }),
+            }
+        }
+
+        let (tempo_map, tempo_conflicts) = merge_events(
+            [base, ours, theirs].map(|chart| chart.get_tempo_map().as_slice()),
+            |event: &TempoEvent| (0, u64::from(event.get_timestamp())),
+            [&[], &[], &[]],
+        );
+        conflicts.extend(
+            tempo_conflicts
+                .iter()
+                .map(|cluster| MergeConflict::TempoMap(ConflictRegion::new(cluster))),
+        );
+
+        let spans = [base, ours, theirs].map(|chart| phrase_spans(chart.get_lyrics()));
+        let (lyrics, lyric_conflicts) = merge_events(
+            [base, ours, theirs].map(|chart| chart.get_lyrics().as_slice()),
+            lyric_key,
+            [&spans[0], &spans[1], &spans[2]],
+        );
+        conflicts.extend(lyric_conflicts.iter().map(|cluster| {
+            let region = ConflictRegion::new(cluster);
+            match cluster.stream {
+                MAIN_STREAM => MergeConflict::Phrase {
+                    singer: Singer::Main,
+                    region,
+                },
+                DUET_STREAM => MergeConflict::Phrase {
+                    singer: Singer::Duet,
+                    region,
+                },
+                _ => MergeConflict::Events(region),
+            }
+        }));
+
+        let mut names: Vec<&String> = [base, ours, theirs]
+            .iter()
+            .flat_map(|chart| chart.get_key_presses().keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        names.sort_by_key(|name| Chart::track_order(name));
+        let mut key_presses = HashMap::new();
+        let empty: Vec<KeyPressEvent> = vec![];
+        for name in names {
+            let tracks = [base, ours, theirs].map(|chart| chart.get_key_presses().get(name));
+            let (events, track_conflicts) = merge_events(
+                tracks.map(|track| track.unwrap_or(&empty).as_slice()),
+                |event: &KeyPressEvent| (0, u64::from(event.get_timestamp())),
+                [&[], &[], &[]],
+            );
+            conflicts.extend(track_conflicts.iter().map(|cluster| MergeConflict::Notes {
+                track: name.clone(),
+                region: ConflictRegion::new(cluster),
+            }));
+            let [in_base, in_ours, in_theirs] = tracks.map(|track| track.is_some());
+            let kept = merge_option(&in_base, &in_ours, &in_theirs).unwrap_or(true);
+            if kept || !events.is_empty() {
+                key_presses.insert(name.clone(), events);
+            }
+        }
+
+        Self {
+            chart: Chart::from_parts(properties, lyrics, tempo_map, key_presses),
+            conflicts,
+        }
+    }
+
+    /// The merged chart, with our side of every conflict.
+    #[must_use]
+    pub const fn get_chart(&self) -> &Chart {
+        &self.chart
+    }
+
+    /// The parts both sides changed differently, properties first, then the tempo map, lyrics
+    /// and tracks in the order they are written.
+    #[must_use]
+    pub const fn get_conflicts(&self) -> &Vec<MergeConflict> {
+        &self.conflicts
+    }
+
+    /// Whether the sides merged without conflicts.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use super::*;
+    use crate::test_util::{self, phrase};
+
+    fn chart(lyrics: Vec<LyricEvent>, key: u32) -> Chart {
+        test_util::chart(
+            lyrics,
+            vec![
+                KeyPressEvent::Note {
+                    timestamp: 0,
+                    duration: 0,
+                    key,
+                },
+                KeyPressEvent::Note {
+                    timestamp: 192,
+                    duration: 0,
+                    key: 0,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn clean_merge() {
+        let base = chart([phrase(0, &["one"]), phrase(192, &["two"])].concat(), 0);
+        let ours = chart([phrase(0, &["won"]), phrase(192, &["two"])].concat(), 0);
+        let theirs = chart([phrase(0, &["one"]), phrase(192, &["too"])].concat(), 1);
+        let merge = ChartMerge::new(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(
+            merge.get_chart(),
+            &chart([phrase(0, &["won"]), phrase(192, &["too"])].concat(), 1)
+        );
+    }
+
+    #[test]
+    fn conflicting_phrase() {
+        let base = chart([phrase(0, &["one"]), phrase(384, &["two"])].concat(), 0);
+        let ours = chart([phrase(0, &["won"]), phrase(384, &["two"])].concat(), 0);
+        let theirs = chart([phrase(48, &["one"]), phrase(384, &["two"])].concat(), 0);
+        let merge = ChartMerge::new(&base, &ours, &theirs);
+        assert_eq!(merge.get_chart(), &ours);
+        assert_eq!(merge.get_conflicts().len(), 1);
+        let MergeConflict::Phrase { singer, region } = &merge.get_conflicts()[0] else {
+            panic!("expected a phrase conflict");
+        };
+        assert_eq!(*singer, Singer::Main);
+        assert_eq!(region.get_ours()[1], "0 = E \"lyric won\"");
+        assert_eq!(
+            merge.get_conflicts()[0].to_string(),
+            "[Events] main phrases from 0 to 240"
+        );
+    }
+
+    #[test]
+    fn merge_charts() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            merge_charts_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn merge_charts_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let base = Chart::new(&file_content)?;
+
+        let mut ours = base.clone();
+        for event in ours.get_lyrics_mut() {
+            if let LyricEvent::Lyric { text, .. } = event {
+                *text = text.to_uppercase();
+            }
+        }
+        let mut theirs = base.clone();
+        let measure = base.get_resolution() * 4;
+        for event in theirs.get_key_presses_mut().values_mut().flatten() {
+            event.set_timestamp(event.get_timestamp() + measure);
+        }
+
+        let merge = ChartMerge::new(&base, &ours, &theirs);
+        assert!(merge.is_clean());
+        assert_eq!(merge.get_chart().get_lyrics(), ours.get_lyrics());
+        assert_eq!(
+            merge.get_chart().get_key_presses(),
+            theirs.get_key_presses()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use crate::chart::KeyPressEvent;
+
+/// `S` event type marking a star power phrase.
+pub const STAR_POWER: u32 = 2;
+
+/// Note key forcing a guitar note to the opposite of its natural strum or HOPO.
+const FORCED_KEY: u32 = 5;
+/// Note key turning a guitar note into a tap note.
+const TAP_KEY: u32 = 6;
+/// Note key of an open note on guitar tracks.
+pub const OPEN_KEY: u32 = 7;
+
+/// The kind of controller a note track is played on, deciding which note keys are gems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instrument {
+    /// Five fret guitar, bass, rhythm and keys: frets 0 to 4 and the open note 7.
+    Guitar,
+    /// Six fret guitar and bass: frets 0 to 4 and 8, and the open note 7.
+    SixFret,
+    /// Drums: kick 0, pads 1 to 5 and the double kick 32.
+    Drums,
+}
+
+impl Instrument {
+    /// The instrument of a track, from its name such as `ExpertDrums` or `HardGHLGuitar`.
+    #[must_use]
+    pub fn from_track(name: &str) -> Self {
+        if name.ends_with("Drums") {
+            Self::Drums
+        } else if name.contains("GHL") {
+            Self::SixFret
+        } else {
+            Self::Guitar
+        }
+    }
+
+    /// Whether a note key is a gem to hit rather than a modifier of the gems around it.
+    #[must_use]
+    pub const fn is_gem(self, key: u32) -> bool {
+        match self {
+            Self::Guitar => matches!(key, 0..=4 | OPEN_KEY),
+            Self::SixFret => matches!(key, 0..=4 | OPEN_KEY | 8),
+            Self::Drums => matches!(key, 0..=5 | 32),
+        }
+    }
+}
+
+/// The difficulty a note track is played at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Difficulty {
+    Expert,
+    Hard,
+    Medium,
+    Easy,
+}
+
+impl Difficulty {
+    /// The difficulty prefix of track names, such as `Expert` in `ExpertSingle`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Expert => "Expert",
+            Self::Hard => "Hard",
+            Self::Medium => "Medium",
+            Self::Easy => "Easy",
+        }
+    }
+
+    /// Splits a track name such as `HardDrums` into its difficulty and instrument name.
+    #[must_use]
+    pub fn from_track(name: &str) -> Option<(Self, &str)> {
+        [Self::Expert, Self::Hard, Self::Medium, Self::Easy]
+            .into_iter()
+            .find_map(|difficulty| {
+                name.strip_prefix(difficulty.name())
+                    .map(|instrument| (difficulty, instrument))
+            })
+    }
+}
+
+/// How a chord is played on a guitar track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoteKind {
+    Strum,
+    Hopo,
+    Tap,
+}
+
+/// All gems of a track starting at the same tick; a single note is a chord of one key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    timestamp: u32,
+    keys: Vec<u32>,
+    sustain: u32,
+    forced: bool,
+    tap: bool,
+}
+
+impl Chord {
+    /// Constructor for `Chord`; the keys are sorted and deduplicated.
+    #[must_use]
+    pub fn new(timestamp: u32, mut keys: Vec<u32>, sustain: u32, forced: bool, tap: bool) -> Self {
+        keys.sort_unstable();
+        keys.dedup();
+        Self {
+            timestamp,
+            keys,
+            sustain,
+            forced,
+            tap,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// The gem keys, in ascending order.
+    #[must_use]
+    pub const fn get_keys(&self) -> &Vec<u32> {
+        &self.keys
+    }
+
+    /// The longest sustain of the chord's gems, in ticks.
+    #[must_use]
+    pub const fn get_sustain(&self) -> u32 {
+        self.sustain
+    }
+
+    /// Whether the chord carries the forced modifier.
+    #[must_use]
+    pub const fn is_forced(&self) -> bool {
+        self.forced
+    }
+
+    /// Whether the chord carries the tap modifier.
+    #[must_use]
+    pub const fn is_tap(&self) -> bool {
+        self.tap
+    }
+
+    /// Whether more than one gem has to be hit.
+    #[must_use]
+    pub fn is_chord(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.keys == [OPEN_KEY]
+    }
+
+    /// The tick the chord's sustain ends at.
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.timestamp.saturating_add(self.sustain)
+    }
+
+    /// The note events writing the chord to a track, modifiers after the gems. Drum tracks have
+    /// no forced or tap modifiers.
+    #[must_use]
+    pub fn to_events(&self, instrument: Instrument) -> Vec<KeyPressEvent> {
+        let modifier = |key| KeyPressEvent::Note {
+            timestamp: self.timestamp,
+            duration: 0,
+            key,
+        };
+        let mut events: Vec<KeyPressEvent> = self
+            .keys
+            .iter()
+            .map(|key| KeyPressEvent::Note {
+                timestamp: self.timestamp,
+                duration: self.sustain,
+                key: *key,
+            })
+            .collect();
+        if instrument != Instrument::Drums {
+            if self.forced {
+                events.push(modifier(FORCED_KEY));
+            }
+            if self.tap {
+                events.push(modifier(TAP_KEY));
+            }
+        }
+        events
+    }
+}
+
+/// Groups the notes of a track into chords, ordered by timestamp.
+///
+/// Forced and tap modifiers only count on guitar tracks, where they do not form chords of their
+/// own. Modifiers without a gem at their tick are dropped.
+///
+/// # Arguments
+///
+/// * `instrument`: the instrument the track is played on.
+/// * `events`: the events of the track, in any order.
+///
+/// returns: `Vec<Chord>`
+///
+/// # Examples
+///
+/// ```
+/// use duet_charter_lib::chart::KeyPressEvent;
+/// use duet_charter_lib::notes::{chords, Instrument};
+///
+/// let events = [
+///     KeyPressEvent::Note { timestamp: 0, duration: 0, key: 0 },
+///     KeyPressEvent::Note { timestamp: 0, duration: 96, key: 2 },
+///     KeyPressEvent::Note { timestamp: 192, duration: 0, key: 1 },
+///     KeyPressEvent::Note { timestamp: 192, duration: 0, key: 6 },
+/// ];
+/// let chords = chords(Instrument::Guitar, &events);
+/// assert_eq!(chords.len(), 2);
+/// assert!(chords[0].is_chord());
+/// assert_eq!(chords[0].get_sustain(), 96);
+/// assert!(chords[1].is_tap());
+/// ```
+#[must_use]
+pub fn chords(instrument: Instrument, events: &[KeyPressEvent]) -> Vec<Chord> {
+    let mut notes: Vec<(u32, u32, u32)> = events
+        .iter()
+        .filter_map(|event| match event {
+            KeyPressEvent::Note {
+                timestamp,
+                duration,
+                key,
+            } => Some((*timestamp, *key, *duration)),
+            _ => None,
+        })
+        .collect();
+    notes.sort_unstable();
+
+    let mut chords: Vec<Chord> = vec![];
+    let mut start = 0;
+    while start < notes.len() {
+        let timestamp = notes[start].0;
+        let end = start
+            + notes[start..]
+                .iter()
+                .take_while(|(other, _, _)| *other == timestamp)
+                .count();
+        let mut chord = Chord {
+            timestamp,
+            keys: vec![],
+            sustain: 0,
+            forced: false,
+            tap: false,
+        };
+        for (_, key, duration) in &notes[start..end] {
+            if instrument.is_gem(*key) {
+                if !chord.keys.contains(key) {
+                    chord.keys.push(*key);
+                }
+                chord.sustain = chord.sustain.max(*duration);
+            } else if instrument != Instrument::Drums && *key == FORCED_KEY {
+                chord.forced = true;
+            } else if instrument != Instrument::Drums && *key == TAP_KEY {
+                chord.tap = true;
+            }
+        }
+        if !chord.keys.is_empty() {
+            chords.push(chord);
+        }
+        start = end;
+    }
+    chords
+}
+
+/// How each chord of a guitar track is played, in the same order as `chords`.
+///
+/// As in Clone Hero, a single note is a natural HOPO when it starts at most 65/192 of a beat
+/// after the previous chord and is not part of it; the forced modifier flips a chord between
+/// strum and HOPO, and the tap modifier makes it a tap. Every drum chord is a strum.
+///
+/// # Arguments
+///
+/// * `instrument`: the instrument the track is played on.
+/// * `chords`: the chords of the track, ordered by timestamp.
+/// * `resolution`: the number of ticks per beat.
+///
+/// returns: `Vec<NoteKind>`
+#[must_use]
+pub fn note_kinds(instrument: Instrument, chords: &[Chord], resolution: u32) -> Vec<NoteKind> {
+    let threshold = u64::from(resolution) * 65 / 192;
+    chords
+        .iter()
+        .enumerate()
+        .map(|(i, chord)| {
+            if instrument == Instrument::Drums {
+                return NoteKind::Strum;
+            }
+            if chord.tap {
+                return NoteKind::Tap;
+            }
+            let natural_hopo = i > 0 && !chord.is_chord() && {
+                let previous = &chords[i - 1];
+                u64::from(chord.timestamp - previous.timestamp) <= threshold
+                    && !previous.keys.contains(&chord.keys[0])
+            };
+            if natural_hopo == chord.forced {
+                NoteKind::Strum
+            } else {
+                NoteKind::Hopo
+            }
+        })
+        .collect()
+}
+
+/// The star power phrases of a track as `(timestamp, duration)` pairs, ordered by timestamp.
+#[must_use]
+pub fn star_power_phrases(events: &[KeyPressEvent]) -> Vec<(u32, u32)> {
+    let mut phrases: Vec<(u32, u32)> = events
+        .iter()
+        .filter_map(|event| match event {
+            KeyPressEvent::Special {
+                timestamp,
+                special_type: STAR_POWER,
+                duration,
+            } => Some((*timestamp, *duration)),
+            _ => None,
+        })
+        .collect();
+    phrases.sort_unstable();
+    phrases
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drum_modifiers() {
+        let events = [
+            KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 0,
+                key: 5,
+            },
+            KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 0,
+                key: 66,
+            },
+            KeyPressEvent::Note {
+                timestamp: 96,
+                duration: 0,
+                key: 6,
+            },
+            KeyPressEvent::Special {
+                timestamp: 0,
+                special_type: STAR_POWER,
+                duration: 192,
+            },
+        ];
+        let drums = chords(Instrument::from_track("ExpertDrums"), &events);
+        assert_eq!(drums.len(), 1);
+        assert_eq!(drums[0].get_keys(), &vec![5]);
+        assert!(!drums[0].is_forced());
+
+        let guitar = chords(Instrument::from_track("ExpertSingle"), &events);
+        assert!(guitar.is_empty());
+        assert_eq!(star_power_phrases(&events), vec![(0, 192)]);
+    }
+
+    #[test]
+    fn hopos() {
+        let note = |timestamp, key| KeyPressEvent::Note {
+            timestamp,
+            duration: 0,
+            key,
+        };
+        let events = [
+            note(0, 0),
+            note(48, 1),
+            note(96, 1),
+            note(144, 2),
+            note(144, 5),
+            note(384, 0),
+            note(384, 5),
+            note(432, 3),
+            note(432, 6),
+        ];
+        let chords = chords(Instrument::Guitar, &events);
+        assert_eq!(
+            note_kinds(Instrument::Guitar, &chords, 192),
+            vec![
+                NoteKind::Strum,
+                NoteKind::Hopo,
+                NoteKind::Strum,
+                NoteKind::Strum,
+                NoteKind::Hopo,
+                NoteKind::Tap
+            ]
+        );
+    }
+}
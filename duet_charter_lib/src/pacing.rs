@@ -0,0 +1,323 @@
+use std::fmt::{Display, Formatter};
+
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::timing::TempoMap;
+use crate::TimestampedEvent;
+
+/// Limits past which a phrase is hard to read or sing along with. `None` disables a check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacingThresholds {
+    pub max_syllables_per_second: Option<f64>,
+    pub max_line_chars: Option<usize>,
+    /// Shortest time between a phrase appearing and its first syllable, in microseconds.
+    pub min_lead_in_micros: Option<u64>,
+    /// Shortest time a phrase stays on screen, in microseconds.
+    pub min_on_screen_micros: Option<u64>,
+}
+
+impl Default for PacingThresholds {
+    fn default() -> Self {
+        Self {
+            max_syllables_per_second: Some(7.0),
+            max_line_chars: Some(40),
+            min_lead_in_micros: Some(100_000),
+            min_on_screen_micros: Some(1_000_000),
+        }
+    }
+}
+
+/// A threshold a phrase does not meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacingIssue {
+    TooFast,
+    TooLong,
+    ShortLeadIn,
+    ShortOnScreen,
+}
+
+impl Display for PacingIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::TooFast => "too fast",
+                Self::TooLong => "line too long",
+                Self::ShortLeadIn => "short lead-in",
+                Self::ShortOnScreen => "short time on screen",
+            }
+        )
+    }
+}
+
+/// Pacing metrics of a single phrase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhrasePacing {
+    singer: Singer,
+    start_timestamp: u32,
+    text: String,
+    syllables: usize,
+    syllables_per_second: f64,
+    line_chars: usize,
+    lead_in_micros: u64,
+    on_screen_micros: u64,
+    issues: Vec<PacingIssue>,
+}
+
+impl PhrasePacing {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(
+        phrase: &Phrase,
+        singer: Singer,
+        tempo_map: &TempoMap,
+        thresholds: &PacingThresholds,
+    ) -> Self {
+        let start_micros = tempo_map.ticks_to_micros(phrase.get_start_timestamp());
+        let end_micros = tempo_map.ticks_to_micros(phrase.get_end_timestamp());
+        let first_micros = phrase.get_lyrics().first().map_or(start_micros, |lyric| {
+            tempo_map.ticks_to_micros(lyric.get_timestamp())
+        });
+        let syllables = phrase.get_display_syllables().len();
+        let sung_micros = end_micros.saturating_sub(first_micros);
+        let syllables_per_second = if sung_micros == 0 {
+            0.0
+        } else {
+            syllables as f64 * 1_000_000.0 / sung_micros as f64
+        };
+        let text = phrase.get_display_text();
+        let mut pacing = Self {
+            singer,
+            start_timestamp: phrase.get_start_timestamp(),
+            line_chars: text.chars().count(),
+            text,
+            syllables,
+            syllables_per_second,
+            lead_in_micros: first_micros.saturating_sub(start_micros),
+            on_screen_micros: end_micros.saturating_sub(start_micros),
+            issues: vec![],
+        };
+        if thresholds
+            .max_syllables_per_second
+            .is_some_and(|max| pacing.syllables_per_second > max)
+        {
+            pacing.issues.push(PacingIssue::TooFast);
+        }
+        if thresholds
+            .max_line_chars
+            .is_some_and(|max| pacing.line_chars > max)
+        {
+            pacing.issues.push(PacingIssue::TooLong);
+        }
+        if thresholds
+            .min_lead_in_micros
+            .is_some_and(|min| pacing.lead_in_micros < min)
+        {
+            pacing.issues.push(PacingIssue::ShortLeadIn);
+        }
+        if thresholds
+            .min_on_screen_micros
+            .is_some_and(|min| pacing.on_screen_micros < min)
+        {
+            pacing.issues.push(PacingIssue::ShortOnScreen);
+        }
+        pacing
+    }
+
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    /// The phrase as shown on screen.
+    #[must_use]
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    pub const fn get_syllables(&self) -> usize {
+        self.syllables
+    }
+
+    /// Syllables sung per second, from the first syllable to the end of the phrase.
+    #[must_use]
+    pub const fn get_syllables_per_second(&self) -> f64 {
+        self.syllables_per_second
+    }
+
+    #[must_use]
+    pub const fn get_line_chars(&self) -> usize {
+        self.line_chars
+    }
+
+    /// Time from the phrase start to its first syllable, in microseconds.
+    #[must_use]
+    pub const fn get_lead_in_micros(&self) -> u64 {
+        self.lead_in_micros
+    }
+
+    /// Time from the phrase start to the phrase end, in microseconds.
+    #[must_use]
+    pub const fn get_on_screen_micros(&self) -> u64 {
+        self.on_screen_micros
+    }
+
+    #[must_use]
+    pub const fn get_issues(&self) -> &Vec<PacingIssue> {
+        &self.issues
+    }
+}
+
+/// Pacing metrics of every phrase, per singer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacingReport {
+    main_phrases: Vec<PhrasePacing>,
+    duet_phrases: Vec<PhrasePacing>,
+}
+
+impl PacingReport {
+    /// Constructor for `PacingReport` from the phrases of a chart.
+    ///
+    /// # Arguments
+    ///
+    /// * `phrases`: the phrases to measure.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    /// * `thresholds`: the limits to flag phrases by.
+    ///
+    /// returns: `PacingReport`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::pacing::{PacingReport, PacingThresholds};
+    /// use duet_charter_lib::phrases::LyricPhraseCollection;
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let report = PacingReport::new(
+    ///     &LyricPhraseCollection::new(chart.get_lyrics()),
+    ///     &TempoMap::from_chart(&chart),
+    ///     &PacingThresholds::default(),
+    /// );
+    /// for phrase in report.flagged() {
+    ///     println!("{}: {:?}", phrase.get_text(), phrase.get_issues());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn new(
+        phrases: &LyricPhraseCollection,
+        tempo_map: &TempoMap,
+        thresholds: &PacingThresholds,
+    ) -> Self {
+        let measure = |singer: Singer| {
+            phrases
+                .get_phrases(singer)
+                .iter()
+                .map(|phrase| PhrasePacing::new(phrase, singer, tempo_map, thresholds))
+                .collect()
+        };
+        Self {
+            main_phrases: measure(Singer::Main),
+            duet_phrases: measure(Singer::Duet),
+        }
+    }
+
+    /// Metrics of the singer's phrases, in the same order as `LyricPhraseCollection::get_phrases`.
+    #[must_use]
+    pub const fn get_phrases(&self, singer: Singer) -> &Vec<PhrasePacing> {
+        match singer {
+            Singer::Main => &self.main_phrases,
+            Singer::Duet => &self.duet_phrases,
+        }
+    }
+
+    /// Phrases of both singers with at least one issue, ordered by start.
+    #[must_use]
+    pub fn flagged(&self) -> Vec<&PhrasePacing> {
+        let mut flagged: Vec<&PhrasePacing> = self
+            .main_phrases
+            .iter()
+            .chain(&self.duet_phrases)
+            .filter(|phrase| !phrase.issues.is_empty() && phrase.syllables > 0)
+            .collect();
+        flagged.sort_by_key(|phrase| phrase.start_timestamp);
+        flagged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chart::{LyricEvent, TempoEvent};
+
+    use super::*;
+
+    #[test]
+    fn pacing_metrics() {
+        let phrases = LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 0 },
+            LyricEvent::Lyric {
+                timestamp: 192,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 240,
+                text: "lo#".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 288,
+                text: "+".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 576 },
+            LyricEvent::DuetPhraseStart { timestamp: 576 },
+            LyricEvent::DuetLyric {
+                timestamp: 576,
+                text: "a".to_string(),
+            },
+            LyricEvent::DuetLyric {
+                timestamp: 588,
+                text: "b".to_string(),
+            },
+            LyricEvent::DuetPhraseEnd { timestamp: 600 },
+        ]);
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let report = PacingReport::new(&phrases, &tempo_map, &PacingThresholds::default());
+        let main = &report.get_phrases(Singer::Main)[0];
+        assert_eq!(main.get_text(), "Hello");
+        assert_eq!(main.get_syllables(), 2);
+        assert!((main.get_syllables_per_second() - 2.0).abs() < 1e-9);
+        assert_eq!(main.get_lead_in_micros(), 500_000);
+        assert_eq!(main.get_on_screen_micros(), 1_500_000);
+        assert!(main.get_issues().is_empty());
+
+        let flagged = report.flagged();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(
+            flagged[0].get_issues(),
+            &vec![
+                PacingIssue::TooFast,
+                PacingIssue::ShortLeadIn,
+                PacingIssue::ShortOnScreen
+            ]
+        );
+    }
+}
@@ -0,0 +1,531 @@
+use crate::chart::{Chart, LyricEvent};
+use crate::phrases::{display_syllable, Singer};
+use crate::timing::TempoMap;
+
+/// Characters ending a lyric line when they end a word.
+const LINE_END_PUNCTUATION: [char; 6] = ['.', ',', '!', '?', ';', ':'];
+
+/// The shortest silence between two lyrics that starts a new phrase.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GapThreshold {
+    /// A gap in beats, following the chart's resolution.
+    Beats(f64),
+    /// A gap in milliseconds of song time, following the chart's tempo map.
+    Millis(u64),
+}
+
+/// Settings for inferring phrases from lyrics. Only the phrase events already in the chart
+/// and the gap split phrases inside a word, the other rules only break between words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhraseInferenceOptions {
+    pub gap: Option<GapThreshold>,
+    /// Break after words ending in `.`, `,`, `!`, `?`, `;` or `:`.
+    pub punctuation: bool,
+    /// Break before capitalised words, apart from the English "I".
+    pub capitalization: bool,
+    /// Break before a word that would make the line longer than this many characters.
+    pub max_line_chars: Option<usize>,
+}
+
+impl Default for PhraseInferenceOptions {
+    fn default() -> Self {
+        Self {
+            gap: Some(GapThreshold::Beats(2.0)),
+            punctuation: true,
+            capitalization: true,
+            max_line_chars: Some(40),
+        }
+    }
+}
+
+/// Why an inferred phrase starts where it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhraseBoundary {
+    /// The first lyric of the singer, not preceded by a phrase start.
+    FirstLyric,
+    /// A phrase start or end already in the chart.
+    Existing,
+    Gap,
+    Punctuation,
+    Capitalization,
+    LineLength,
+}
+
+/// A phrase proposed by `infer_phrases`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredPhrase {
+    singer: Singer,
+    start_timestamp: u32,
+    end_timestamp: u32,
+    lyric_count: usize,
+    boundary: PhraseBoundary,
+}
+
+impl InferredPhrase {
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_lyric_count(&self) -> usize {
+        self.lyric_count
+    }
+
+    #[must_use]
+    pub const fn get_boundary(&self) -> PhraseBoundary {
+        self.boundary
+    }
+}
+
+/// Proposes phrases for all lyrics of both singers, splitting up the phrases already in the
+/// chart wherever the options call for a break.
+///
+/// Phrases start at their existing phrase start or at their first lyric, and end at their
+/// existing phrase end or one beat after their last lyric, but never after the next phrase
+/// starts.
+///
+/// # Arguments
+///
+/// * `lyrics_events`: the chart's lyric events.
+/// * `tempo_map`: the tempo map of the chart, used to measure gaps.
+/// * `options`: the rules to break phrases by.
+///
+/// returns: `Vec<InferredPhrase>`, ordered by start
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::phrase_inference::{infer_phrases, PhraseInferenceOptions};
+/// use duet_charter_lib::timing::TempoMap;
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// let chart = Chart::new(&file_content).unwrap();
+/// let phrases = infer_phrases(
+///     chart.get_lyrics(),
+///     &TempoMap::from_chart(&chart),
+///     &PhraseInferenceOptions::default(),
+/// );
+/// ```
+#[must_use]
+pub fn infer_phrases(
+    lyrics_events: &[LyricEvent],
+    tempo_map: &TempoMap,
+    options: &PhraseInferenceOptions,
+) -> Vec<InferredPhrase> {
+    let mut phrases: Vec<InferredPhrase> = [Singer::Main, Singer::Duet]
+        .iter()
+        .flat_map(|singer| infer_singer_phrases(lyrics_events, *singer, tempo_map, options))
+        .collect();
+    phrases.sort_by_key(InferredPhrase::get_start_timestamp);
+    phrases
+}
+
+/// Replaces the phrase events of a chart with the phrases proposed by `infer_phrases`. Phrases
+/// without any lyrics are dropped.
+pub fn write_inferred_phrases(
+    chart: &mut Chart,
+    options: &PhraseInferenceOptions,
+) -> Vec<InferredPhrase> {
+    let phrases = infer_phrases(chart.get_lyrics(), &TempoMap::from_chart(chart), options);
+    let mut vocal_events: Vec<LyricEvent> = chart
+        .get_lyrics()
+        .iter()
+        .filter(|event| {
+            matches!(
+                event,
+                LyricEvent::Lyric { .. } | LyricEvent::DuetLyric { .. }
+            )
+        })
+        .cloned()
+        .collect();
+    for phrase in &phrases {
+        let (start_timestamp, end_timestamp) = (phrase.start_timestamp, phrase.end_timestamp);
+        vocal_events.extend(match phrase.singer {
+            Singer::Main => [
+                LyricEvent::PhraseStart {
+                    timestamp: start_timestamp,
+                },
+                LyricEvent::PhraseEnd {
+                    timestamp: end_timestamp,
+                },
+            ],
+            Singer::Duet => [
+                LyricEvent::DuetPhraseStart {
+                    timestamp: start_timestamp,
+                },
+                LyricEvent::DuetPhraseEnd {
+                    timestamp: end_timestamp,
+                },
+            ],
+        });
+    }
+    chart.replace_vocal_events(vocal_events);
+    phrases
+}
+
+fn infer_singer_phrases(
+    lyrics_events: &[LyricEvent],
+    singer: Singer,
+    tempo_map: &TempoMap,
+    options: &PhraseInferenceOptions,
+) -> Vec<InferredPhrase> {
+    let mut lyrics: Vec<(u32, &str)> = vec![];
+    let mut starts = vec![];
+    let mut ends = vec![];
+    for event in lyrics_events {
+        match (event, singer) {
+            (LyricEvent::Lyric { timestamp, text }, Singer::Main)
+            | (LyricEvent::DuetLyric { timestamp, text }, Singer::Duet) => {
+                lyrics.push((*timestamp, text));
+            }
+            (LyricEvent::PhraseStart { timestamp }, Singer::Main)
+            | (LyricEvent::DuetPhraseStart { timestamp }, Singer::Duet) => starts.push(*timestamp),
+            (LyricEvent::PhraseEnd { timestamp }, Singer::Main)
+            | (LyricEvent::DuetPhraseEnd { timestamp }, Singer::Duet) => ends.push(*timestamp),
+            _ => {}
+        }
+    }
+    lyrics.sort_by_key(|(timestamp, _)| *timestamp);
+    starts.sort_unstable();
+    ends.sort_unstable();
+    let Some(first) = lyrics.first() else {
+        return vec![];
+    };
+
+    let mut groups: Vec<(usize, PhraseBoundary)> = vec![(
+        0,
+        if starts.first().is_some_and(|start| *start <= first.0) {
+            PhraseBoundary::Existing
+        } else {
+            PhraseBoundary::FirstLyric
+        },
+    )];
+    // line length is counted the way the line is shown, where a hold syllable takes no room
+    // and leaves the previous syllable joined to the next one as it was
+    let syllables: Vec<Option<(String, bool)>> = lyrics
+        .iter()
+        .map(|(_, text)| display_syllable(text))
+        .collect();
+    let display_chars = |i: usize| {
+        syllables[i]
+            .as_ref()
+            .map_or(0, |(syllable, _)| syllable.chars().count())
+    };
+    let mut joined: Vec<bool> = Vec::with_capacity(syllables.len());
+    for syllable in &syllables {
+        let previous = joined.last().copied().unwrap_or(false);
+        joined.push(syllable.as_ref().map_or(previous, |(_, joined)| *joined));
+    }
+
+    let mut line_chars = display_chars(0);
+    for i in 1..lyrics.len() {
+        let (previous_timestamp, previous_text) = lyrics[i - 1];
+        let (timestamp, text) = lyrics[i];
+        let in_word = joined[i - 1] || syllables[i].is_none();
+        let word_chars: usize = (i..joined[i..]
+            .iter()
+            .position(|joined| !joined)
+            .map_or(lyrics.len(), |end| i + end + 1))
+            .map(display_chars)
+            .sum();
+
+        let boundary = if starts
+            .get(starts.partition_point(|start| *start <= previous_timestamp))
+            .is_some_and(|start| *start <= timestamp)
+            || ends
+                .get(ends.partition_point(|end| *end < previous_timestamp))
+                .is_some_and(|end| *end < timestamp)
+        {
+            Some(PhraseBoundary::Existing)
+        } else if timestamp == previous_timestamp {
+            None
+        } else if options
+            .gap
+            .is_some_and(|gap| is_gap(tempo_map, previous_timestamp, timestamp, gap))
+        {
+            Some(PhraseBoundary::Gap)
+        } else if in_word {
+            None
+        } else if options.punctuation && previous_text.trim_end().ends_with(LINE_END_PUNCTUATION) {
+            Some(PhraseBoundary::Punctuation)
+        } else if options.capitalization && is_capitalized(text) {
+            Some(PhraseBoundary::Capitalization)
+        } else if options
+            .max_line_chars
+            .is_some_and(|max| line_chars + 1 + word_chars > max)
+        {
+            Some(PhraseBoundary::LineLength)
+        } else {
+            None
+        };
+
+        match boundary {
+            Some(boundary) => {
+                groups.push((i, boundary));
+                line_chars = display_chars(i);
+            }
+            None => line_chars += usize::from(!in_word) + display_chars(i),
+        }
+    }
+
+    let resolution = tempo_map.get_resolution();
+    let mut phrases: Vec<InferredPhrase> = vec![];
+    for (group_index, (first_lyric, boundary)) in groups.iter().enumerate() {
+        let last_lyric = groups
+            .get(group_index + 1)
+            .map_or(lyrics.len(), |(next, _)| *next)
+            - 1;
+        let first_timestamp = lyrics[*first_lyric].0;
+        let last_timestamp = lyrics[last_lyric].0;
+        let previous_timestamp = first_lyric
+            .checked_sub(1)
+            .map(|previous| lyrics[previous].0);
+        let start_timestamp = starts[..starts.partition_point(|start| *start <= first_timestamp)]
+            .last()
+            .filter(|start| previous_timestamp.is_none_or(|prev| **start > prev))
+            .copied()
+            .unwrap_or(first_timestamp);
+        if let Some(previous) = phrases.last_mut() {
+            previous.end_timestamp = previous.end_timestamp.min(start_timestamp);
+        }
+        let next_timestamp = lyrics.get(last_lyric + 1).map(|(timestamp, _)| *timestamp);
+        let end_timestamp = ends
+            .get(ends.partition_point(|end| *end < last_timestamp))
+            .filter(|end| next_timestamp.is_none_or(|next| **end < next))
+            .copied()
+            .unwrap_or_else(|| {
+                let beat_later = last_timestamp + resolution;
+                next_timestamp.map_or(beat_later, |next| next.min(beat_later))
+            })
+            .max(start_timestamp);
+        phrases.push(InferredPhrase {
+            singer,
+            start_timestamp,
+            end_timestamp,
+            lyric_count: last_lyric + 1 - first_lyric,
+            boundary: *boundary,
+        });
+    }
+    phrases
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn is_gap(tempo_map: &TempoMap, from: u32, to: u32, gap: GapThreshold) -> bool {
+    match gap {
+        GapThreshold::Beats(beats) => {
+            f64::from(to - from) >= beats * f64::from(tempo_map.get_resolution())
+        }
+        GapThreshold::Millis(millis) => {
+            tempo_map.ticks_to_micros(to) - tempo_map.ticks_to_micros(from) >= millis * 1000
+        }
+    }
+}
+
+fn is_capitalized(text: &str) -> bool {
+    let word = text.trim_start_matches(|c: char| !c.is_alphabetic());
+    let is_i = word == "I"
+        || word.starts_with("I'")
+        || word.starts_with("I\u{2019}")
+        || word
+            .strip_prefix('I')
+            .is_some_and(|rest| rest.starts_with(|c: char| !c.is_alphanumeric()));
+    !is_i && word.starts_with(char::is_uppercase)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::chart::TempoEvent;
+    use crate::phrases::LyricPhraseCollection;
+
+    use super::*;
+
+    fn lyric(timestamp: u32, text: &str) -> LyricEvent {
+        LyricEvent::Lyric {
+            timestamp,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn boundaries() {
+        let events = vec![
+            LyricEvent::PhraseStart { timestamp: 0 },
+            lyric(10, "Hel-"),
+            lyric(20, "lo,"),
+            lyric(30, "I"),
+            lyric(40, "see"),
+            lyric(1000, "you"),
+            lyric(1010, "Now"),
+            lyric(1020, "la"),
+            lyric(1030, "la"),
+            lyric(1040, "la"),
+            LyricEvent::PhraseEnd { timestamp: 1100 },
+        ];
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let phrases = infer_phrases(
+            &events,
+            &tempo_map,
+            &PhraseInferenceOptions {
+                max_line_chars: Some(6),
+                ..PhraseInferenceOptions::default()
+            },
+        );
+        let summary: Vec<(u32, u32, usize, PhraseBoundary)> = phrases
+            .iter()
+            .map(|phrase| {
+                (
+                    phrase.get_start_timestamp(),
+                    phrase.get_end_timestamp(),
+                    phrase.get_lyric_count(),
+                    phrase.get_boundary(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 30, 2, PhraseBoundary::Existing),
+                (30, 232, 2, PhraseBoundary::Punctuation),
+                (1000, 1010, 1, PhraseBoundary::Gap),
+                (1010, 1030, 2, PhraseBoundary::Capitalization),
+                (1030, 1100, 2, PhraseBoundary::LineLength),
+            ]
+        );
+    }
+
+    #[test]
+    fn displayed_line_length() {
+        let events = vec![
+            lyric(0, "la#"),
+            lyric(10, "+"),
+            lyric(20, "la^"),
+            lyric(30, "la"),
+        ];
+        let tempo_map = TempoMap::new(&[], 192);
+        let phrases = infer_phrases(
+            &events,
+            &tempo_map,
+            &PhraseInferenceOptions {
+                max_line_chars: Some(6),
+                ..PhraseInferenceOptions::default()
+            },
+        );
+        let starts: Vec<(u32, PhraseBoundary)> = phrases
+            .iter()
+            .map(|phrase| (phrase.get_start_timestamp(), phrase.get_boundary()))
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                (0, PhraseBoundary::FirstLyric),
+                (30, PhraseBoundary::LineLength),
+            ]
+        );
+    }
+
+    #[test]
+    fn written_line_order() {
+        let mut chart = Chart::from_parts(
+            HashMap::from([("Resolution".to_string(), "192".to_string())]),
+            vec![
+                lyric(10, "Hel-"),
+                lyric(20, "lo,"),
+                lyric(30, "I"),
+                lyric(40, "see"),
+            ],
+            vec![],
+            HashMap::new(),
+        );
+        write_inferred_phrases(&mut chart, &PhraseInferenceOptions::default());
+        let written = chart.to_string();
+        assert!(written.contains(
+            "  30 = E \"phrase_end\"\r\n  30 = E \"phrase_start\"\r\n  30 = E \"lyric I\"\r\n"
+        ));
+    }
+
+    #[test]
+    fn inferred_phrases_cover_lyrics() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            inferred_phrases_cover_lyrics_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn inferred_phrases_cover_lyrics_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let mut chart = Chart::new(&file_content)?;
+        let inferred = write_inferred_phrases(&mut chart, &PhraseInferenceOptions::default());
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        for singer in [Singer::Main, Singer::Duet] {
+            let lyric_count = chart
+                .get_lyrics()
+                .iter()
+                .filter(|event| match singer {
+                    Singer::Main => matches!(event, LyricEvent::Lyric { .. }),
+                    Singer::Duet => matches!(event, LyricEvent::DuetLyric { .. }),
+                })
+                .count();
+            let singer_phrases = phrases.get_phrases(singer);
+            assert_eq!(
+                singer_phrases
+                    .iter()
+                    .map(|phrase| phrase.get_lyrics().len())
+                    .collect::<Vec<_>>(),
+                inferred
+                    .iter()
+                    .filter(|phrase| phrase.get_singer() == singer)
+                    .map(InferredPhrase::get_lyric_count)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                singer_phrases
+                    .iter()
+                    .map(|phrase| phrase.get_lyrics().len())
+                    .sum::<usize>(),
+                lyric_count
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,31 @@
 use std::fmt::{Display, Formatter};
 use std::ops::Add;
 
+use serde::Serialize;
+
 use crate::chart::LyricEvent;
 use crate::TimestampedEvent;
 
-#[derive(Debug)]
+/// The two vocal parts of a duet chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Singer {
+    /// Sung from the regular `phrase_start`/`lyric`/`phrase_end` events.
+    Main,
+    /// Sung from the `duet_phrase_start`/`duet_lyric`/`duet_phrase_end` events.
+    Duet,
+}
+
+impl Display for Singer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Main => f.pad("main"),
+            Self::Duet => f.pad("duet"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PhraseLyric {
     timestamp: u32,
     text: String,
@@ -14,17 +35,45 @@ impl TimestampedEvent for PhraseLyric {
     fn get_timestamp(&self) -> u32 {
         self.timestamp
     }
+
+    fn set_timestamp(&mut self, timestamp: u32) {
+        self.timestamp = timestamp;
+    }
 }
 
-#[derive(Debug)]
+impl PhraseLyric {
+    #[must_use]
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Phrase {
     start_timestamp: u32,
     end_timestamp: u32,
     lyrics: Vec<PhraseLyric>,
 }
 
-impl Display for Phrase {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl Phrase {
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_lyrics(&self) -> &Vec<PhraseLyric> {
+        &self.lyrics
+    }
+
+    /// The sung line, with syllables ending in `-` joined to the next and words separated by spaces.
+    #[must_use]
+    pub fn get_text(&self) -> String {
         let line = self
             .lyrics
             .iter()
@@ -34,16 +83,127 @@ impl Display for Phrase {
                 x.strip_suffix('-').unwrap_or(y.as_str()).to_string()
             })
             .collect::<String>();
-        let clean_line = line.strip_suffix(' ').unwrap_or(line.as_str()).to_string();
+        line.strip_suffix(' ').unwrap_or(line.as_str()).to_string()
+    }
+
+    /// The syllables as shown on screen, with their timestamps and whether each joins the next
+    /// one without a space.
+    ///
+    /// `+` hold syllables only continue the previous syllable on a new pitch, so they are left
+    /// out. The `#` and `^` pitch markers are removed, and `=` is shown as a hyphen that joins
+    /// the next syllable.
+    #[must_use]
+    pub fn get_display_syllables(&self) -> Vec<(u32, String, bool)> {
+        self.lyrics
+            .iter()
+            .filter_map(|lyric| {
+                display_syllable(&lyric.text).map(|(syllable, joined)| {
+                    (lyric.timestamp, syllable, joined)
+                })
+            })
+            .collect()
+    }
+
+    /// The line as shown on screen, see `get_display_syllables`.
+    #[must_use]
+    pub fn get_display_text(&self) -> String {
+        let mut line = String::new();
+        for (_, syllable, joined) in self.get_display_syllables() {
+            line.push_str(&syllable);
+            if !joined {
+                line.push(' ');
+            }
+        }
+        line.trim_end().to_string()
+    }
+}
+
+impl Display for Phrase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "from {} to {}, phrase: {}",
-            self.start_timestamp, self.end_timestamp, clean_line
+            self.start_timestamp,
+            self.end_timestamp,
+            self.get_text()
         )
     }
 }
 
-#[derive(Debug)]
+/// A lyric event's syllable as shown on screen and whether it joins the next one, or `None` for
+/// a `+` hold syllable, see `Phrase::get_display_syllables`.
+pub(crate) fn display_syllable(text: &str) -> Option<(String, bool)> {
+    if text.trim_end_matches('-') == "+" {
+        return None;
+    }
+    let (syllable, joined) = text
+        .strip_suffix('-')
+        .map_or((text, text.ends_with('=')), |syllable| (syllable, true));
+    let syllable = syllable
+        .chars()
+        .filter(|c| !matches!(c, '#' | '^'))
+        .map(|c| if c == '=' { '-' } else { c })
+        .collect();
+    Some((syllable, joined))
+}
+
+/// Incrementally builds phrases from time-ordered phrase start, phrase end and lyric events.
+///
+/// A phrase runs from its start event up to the first end event after it, falling back to the
+/// next phrase start, or to just after its last lyric for the final phrase.
+#[derive(Default)]
+struct PhraseBuilder {
+    phrases: Vec<Phrase>,
+    current: Option<(u32, Option<u32>, Vec<PhraseLyric>)>,
+}
+
+impl PhraseBuilder {
+    fn start(&mut self, timestamp: u32) {
+        if let Some((start_timestamp, end, lyrics)) = self.current.take() {
+            self.phrases.push(Phrase {
+                start_timestamp,
+                end_timestamp: end.unwrap_or(timestamp),
+                lyrics,
+            });
+        }
+        self.current = Some((timestamp, None, vec![]));
+    }
+
+    fn end(&mut self, timestamp: u32) {
+        if let Some((start_timestamp, end @ None, _)) = &mut self.current {
+            if timestamp > *start_timestamp {
+                *end = Some(timestamp);
+            }
+        }
+    }
+
+    fn lyric(&mut self, timestamp: u32, text: &str) {
+        if let Some((_, _, lyrics)) = &mut self.current {
+            lyrics.push(PhraseLyric {
+                timestamp,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    fn finish(mut self) -> Vec<Phrase> {
+        if let Some((start_timestamp, end, lyrics)) = self.current.take() {
+            let end_timestamp = end.unwrap_or_else(|| {
+                lyrics
+                    .last()
+                    .map_or(start_timestamp + 1, |lyric| lyric.timestamp + 1)
+            });
+            self.phrases.push(Phrase {
+                start_timestamp,
+                end_timestamp,
+                lyrics,
+            });
+        }
+        self.phrases
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LyricPhraseCollection {
     main_phrases: Vec<Phrase>,
     duet_phrases: Vec<Phrase>,
@@ -77,80 +237,27 @@ impl LyricPhraseCollection {
     /// let phrases = LyricPhraseCollection::new(chart.get_lyrics());
     /// ```
     #[must_use] pub fn new(lyrics_events: &[LyricEvent]) -> Self {
-        let duet_only = lyrics_events
-            .iter()
-            .filter_map(|event| match event {
-                LyricEvent::DuetPhraseStart { timestamp } => Some(LyricEvent::PhraseStart {
-                    timestamp: *timestamp,
-                }),
-                LyricEvent::DuetPhraseEnd { timestamp } => Some(LyricEvent::PhraseEnd {
-                    timestamp: *timestamp,
-                }),
-                LyricEvent::DuetLyric { timestamp, text } => Some(LyricEvent::Lyric {
-                    timestamp: *timestamp,
-                    text: text.clone(),
-                }),
-                LyricEvent::PhraseStart { .. }
-                | LyricEvent::PhraseEnd { .. }
-                | LyricEvent::Lyric { .. }
-                | LyricEvent::Section { .. }
-                | LyricEvent::OtherLyricEvent { .. } => None,
-            })
-            .collect::<Vec<LyricEvent>>();
-        let main = Self::parse_phrases_from(lyrics_events);
-        let duet = Self::parse_phrases_from(&duet_only);
-        Self { main_phrases: main, duet_phrases: duet }
-    }
+        let mut sorted_events: Vec<&LyricEvent> = lyrics_events.iter().collect();
+        // stable, and linear on already sorted input, which every chart in practice is
+        sorted_events.sort_by_key(|event| (event.get_timestamp(), event.processing_rank()));
 
-    fn parse_phrases_from(lyric_events: &[LyricEvent]) -> Vec<Phrase> {
-        let timestamps: Vec<u32> = lyric_events
-            .iter()
-            .filter_map(|x| match x {
-                LyricEvent::PhraseStart { timestamp } => Some(*timestamp),
-                _ => None,
-            })
-            .collect();
-        timestamps
-            .iter()
-            .enumerate()
-            .map(|(i, low)| {
-                let high = timestamps.get(i + 1);
-                let lyrics: Vec<PhraseLyric> = lyric_events
-                    .iter()
-                    .filter(|lyric| {
-                        (high.is_none() || high.unwrap_or(&0) > &lyric.get_timestamp())
-                            && lyric.get_timestamp() >= *low
-                    })
-                    .filter_map(|lyric| match lyric {
-                        LyricEvent::Lyric { timestamp, text } => Some(PhraseLyric {
-                            timestamp: *timestamp,
-                            text: text.clone(),
-                        }),
-                        _ => None,
-                    })
-                    .collect();
-                let maybe_timestamp = lyric_events.iter().find(|x| {
-                    (high.is_none() || high.unwrap_or(&0) >= &x.get_timestamp())
-                        && x.get_timestamp() > *low
-                        && matches!(x, LyricEvent::PhraseEnd { .. })
-                });
-                let end_timestamp = match maybe_timestamp {
-                    Some(x) => x.get_timestamp(),
-                    None => match high {
-                        Some(y) => *y,
-                        None => match lyrics.last() {
-                            Some(z) => z.timestamp + 1,
-                            None => low + 1,
-                        },
-                    },
-                };
-                Phrase {
-                    start_timestamp: *low,
-                    end_timestamp,
-                    lyrics,
-                }
-            })
-            .collect()
+        let mut main = PhraseBuilder::default();
+        let mut duet = PhraseBuilder::default();
+        for event in sorted_events {
+            match event {
+                LyricEvent::PhraseStart { timestamp } => main.start(*timestamp),
+                LyricEvent::PhraseEnd { timestamp } => main.end(*timestamp),
+                LyricEvent::Lyric { timestamp, text } => main.lyric(*timestamp, text),
+                LyricEvent::DuetPhraseStart { timestamp } => duet.start(*timestamp),
+                LyricEvent::DuetPhraseEnd { timestamp } => duet.end(*timestamp),
+                LyricEvent::DuetLyric { timestamp, text } => duet.lyric(*timestamp, text),
+                LyricEvent::Section { .. } | LyricEvent::OtherLyricEvent { .. } => {}
+            }
+        }
+        Self {
+            main_phrases: main.finish(),
+            duet_phrases: duet.finish(),
+        }
     }
 
     #[must_use] pub const fn get_main_phrases(&self) -> &Vec<Phrase> {
@@ -160,6 +267,14 @@ impl LyricPhraseCollection {
     #[must_use] pub const fn get_duet_phrases(&self) -> &Vec<Phrase> {
         &self.duet_phrases
     }
+
+    #[must_use]
+    pub const fn get_phrases(&self, singer: Singer) -> &Vec<Phrase> {
+        match singer {
+            Singer::Main => &self.main_phrases,
+            Singer::Duet => &self.duet_phrases,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -241,4 +356,148 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn phrase_equivalence() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            phrase_equivalence_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn phrase_equivalence_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        let duet_only = chart
+            .get_lyrics()
+            .iter()
+            .filter_map(|event| match event {
+                LyricEvent::DuetPhraseStart { timestamp } => Some(LyricEvent::PhraseStart {
+                    timestamp: *timestamp,
+                }),
+                LyricEvent::DuetPhraseEnd { timestamp } => Some(LyricEvent::PhraseEnd {
+                    timestamp: *timestamp,
+                }),
+                LyricEvent::DuetLyric { timestamp, text } => Some(LyricEvent::Lyric {
+                    timestamp: *timestamp,
+                    text: text.clone(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<LyricEvent>>();
+        assert_eq!(
+            phrases.get_main_phrases(),
+            &reference_parse_phrases_from(chart.get_lyrics())
+        );
+        assert_eq!(
+            phrases.get_duet_phrases(),
+            &reference_parse_phrases_from(&duet_only)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn phrase_edge_cases() {
+        let events = vec![
+            LyricEvent::Lyric { timestamp: 0, text: "dropped".to_string() },
+            LyricEvent::Lyric { timestamp: 10, text: "same-".to_string() },
+            LyricEvent::PhraseStart { timestamp: 10 },
+            LyricEvent::PhraseEnd { timestamp: 10 },
+            LyricEvent::PhraseStart { timestamp: 20 },
+            LyricEvent::PhraseStart { timestamp: 20 },
+            LyricEvent::Lyric { timestamp: 25, text: "tick".to_string() },
+            LyricEvent::PhraseStart { timestamp: 30 },
+            LyricEvent::PhraseEnd { timestamp: 30 },
+            LyricEvent::Lyric { timestamp: 40, text: "last".to_string() },
+        ];
+        let phrases = LyricPhraseCollection::new(&events);
+        assert_eq!(phrases.get_main_phrases(), &reference_parse_phrases_from(&events));
+    }
+
+    #[test]
+    fn display_text() {
+        let phrases = LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 0 },
+            LyricEvent::Lyric { timestamp: 0, text: "Hel-".to_string() },
+            LyricEvent::Lyric { timestamp: 48, text: "lo#".to_string() },
+            LyricEvent::Lyric { timestamp: 96, text: "+".to_string() },
+            LyricEvent::Lyric { timestamp: 144, text: "well=".to_string() },
+            LyricEvent::Lyric { timestamp: 192, text: "known^".to_string() },
+            LyricEvent::PhraseEnd { timestamp: 240 },
+        ]);
+        let phrase = &phrases.get_main_phrases()[0];
+        assert_eq!(phrase.get_display_text(), "Hello well-known");
+        assert_eq!(
+            phrase.get_display_syllables(),
+            vec![
+                (0, "Hel".to_string(), true),
+                (48, "lo".to_string(), false),
+                (144, "well-".to_string(), true),
+                (192, "known".to_string(), false),
+            ]
+        );
+    }
+
+    /// The original quadratic phrase construction, kept as the reference behaviour.
+    fn reference_parse_phrases_from(lyric_events: &[LyricEvent]) -> Vec<Phrase> {
+        let timestamps: Vec<u32> = lyric_events
+            .iter()
+            .filter_map(|x| match x {
+                LyricEvent::PhraseStart { timestamp } => Some(*timestamp),
+                _ => None,
+            })
+            .collect();
+        timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, low)| {
+                let high = timestamps.get(i + 1);
+                let lyrics: Vec<PhraseLyric> = lyric_events
+                    .iter()
+                    .filter(|lyric| {
+                        (high.is_none() || high.unwrap_or(&0) > &lyric.get_timestamp())
+                            && lyric.get_timestamp() >= *low
+                    })
+                    .filter_map(|lyric| match lyric {
+                        LyricEvent::Lyric { timestamp, text } => Some(PhraseLyric {
+                            timestamp: *timestamp,
+                            text: text.clone(),
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+                let maybe_timestamp = lyric_events.iter().find(|x| {
+                    (high.is_none() || high.unwrap_or(&0) >= &x.get_timestamp())
+                        && x.get_timestamp() > *low
+                        && matches!(x, LyricEvent::PhraseEnd { .. })
+                });
+                let end_timestamp = match maybe_timestamp {
+                    Some(x) => x.get_timestamp(),
+                    None => match high {
+                        Some(y) => *y,
+                        None => match lyrics.last() {
+                            Some(z) => z.timestamp + 1,
+                            None => low + 1,
+                        },
+                    },
+                };
+                Phrase {
+                    start_timestamp: *low,
+                    end_timestamp,
+                    lyrics,
+                }
+            })
+            .collect()
+    }
 }
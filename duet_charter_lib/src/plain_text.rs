@@ -0,0 +1,374 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+
+use crate::chart::LyricEvent;
+use crate::lrc::split_syllables;
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::TimestampedEvent;
+
+/// A line of a plain text lyric file whose syllables could not be put in a phrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyllableMismatch {
+    line_number: usize,
+    phrase_start_timestamp: Option<u32>,
+    phrase_syllables: usize,
+    line_syllables: usize,
+}
+
+impl SyllableMismatch {
+    /// Line of the text file, counting from 1.
+    #[must_use]
+    pub const fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Start of the phrase the line was meant for, or `None` if the text has more lines than
+    /// the chart has phrases.
+    #[must_use]
+    pub const fn get_phrase_start_timestamp(&self) -> Option<u32> {
+        self.phrase_start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_phrase_syllables(&self) -> usize {
+        self.phrase_syllables
+    }
+
+    #[must_use]
+    pub const fn get_line_syllables(&self) -> usize {
+        self.line_syllables
+    }
+}
+
+impl Display for SyllableMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.phrase_start_timestamp {
+            Some(timestamp) => write!(
+                f,
+                "line {}: {} syllables, but the phrase at {} has {}",
+                self.line_number, self.line_syllables, timestamp, self.phrase_syllables
+            ),
+            None => write!(
+                f,
+                "line {}: {} syllables, but there are no phrases left",
+                self.line_number, self.line_syllables
+            ),
+        }
+    }
+}
+
+/// The outcome of filling a chart's phrases with plain text lyrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainTextReport {
+    filled_phrases: usize,
+    unfilled_phrases: usize,
+    mismatches: Vec<SyllableMismatch>,
+}
+
+impl PlainTextReport {
+    #[must_use]
+    pub const fn get_filled_phrases(&self) -> usize {
+        self.filled_phrases
+    }
+
+    /// Phrases left after the last line of text.
+    #[must_use]
+    pub const fn get_unfilled_phrases(&self) -> usize {
+        self.unfilled_phrases
+    }
+
+    #[must_use]
+    pub const fn get_mismatches(&self) -> &Vec<SyllableMismatch> {
+        &self.mismatches
+    }
+}
+
+/// Lyrics as plain text, one line per phrase with the syllables of a word split by `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainTextLyrics {
+    /// Non-empty lines with their line number, split into chart syllables.
+    lines: Vec<(usize, Vec<String>)>,
+}
+
+impl PlainTextLyrics {
+    /// Creates a `PlainTextLyrics` struct from the contents of a text file, skipping empty lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::plain_text::PlainTextLyrics;
+    ///
+    /// let text = PlainTextLyrics::new("Hel-lo world\n\nbye");
+    /// assert_eq!(text.get_lines()[0], &["Hel-", "lo", "world"]);
+    /// ```
+    #[must_use]
+    pub fn new(text_file: &str) -> Self {
+        let lines = text_file
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                (
+                    i + 1,
+                    line.trim_start_matches('\u{feff}')
+                        .split_whitespace()
+                        .flat_map(split_syllables)
+                        .collect::<Vec<String>>(),
+                )
+            })
+            .filter(|(_, syllables)| !syllables.is_empty())
+            .collect();
+        Self { lines }
+    }
+
+    /// Constructor for `PlainTextLyrics` from the phrases of both singers, in the order they
+    /// start.
+    #[must_use]
+    pub fn from_phrases(phrases: &LyricPhraseCollection) -> Self {
+        let lines = Self::ordered_phrases(phrases)
+            .iter()
+            .map(|(_, phrase)| {
+                phrase
+                    .get_lyrics()
+                    .iter()
+                    .map(|lyric| lyric.get_text().to_string())
+                    .collect()
+            })
+            .enumerate()
+            .map(|(i, syllables)| (i + 1, syllables))
+            .collect();
+        Self { lines }
+    }
+
+    #[must_use]
+    pub fn get_lines(&self) -> Vec<&Vec<String>> {
+        self.lines.iter().map(|(_, syllables)| syllables).collect()
+    }
+
+    /// Phrases with lyrics of both singers, sorted by start.
+    fn ordered_phrases(phrases: &LyricPhraseCollection) -> Vec<(Singer, &Phrase)> {
+        let mut ordered: Vec<(Singer, &Phrase)> = [Singer::Main, Singer::Duet]
+            .iter()
+            .flat_map(|singer| {
+                phrases
+                    .get_phrases(*singer)
+                    .iter()
+                    .filter(|phrase| !phrase.get_lyrics().is_empty())
+                    .map(|phrase| (*singer, phrase))
+            })
+            .collect();
+        ordered.sort_by_key(|(_, phrase)| phrase.get_start_timestamp());
+        ordered
+    }
+
+    /// Replaces the text of the chart's lyrics, line by line, keeping every lyric timestamp.
+    ///
+    /// Lines are matched with the phrases holding lyrics, main and duet alike, in the order the
+    /// phrases start. A phrase is only refilled if its line has as many syllables as the phrase
+    /// has lyrics, otherwise it is left untouched and the line is reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `lyrics_events`: the chart's lyric events, changed in place.
+    ///
+    /// returns: `PlainTextReport`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::plain_text::PlainTextLyrics;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// let report = PlainTextLyrics::new("La la la").fill_phrases(chart.get_lyrics_mut());
+    /// for mismatch in report.get_mismatches() {
+    ///     println!("{}", mismatch);
+    /// }
+    /// ```
+    pub fn fill_phrases(&self, lyrics_events: &mut [LyricEvent]) -> PlainTextReport {
+        let phrases = LyricPhraseCollection::new(lyrics_events);
+        let ordered = Self::ordered_phrases(&phrases);
+
+        let mut event_indices: HashMap<(Singer, u32, String), VecDeque<usize>> = HashMap::new();
+        for (i, event) in lyrics_events.iter().enumerate() {
+            let key = match event {
+                LyricEvent::Lyric { timestamp, text } => (Singer::Main, *timestamp, text.clone()),
+                LyricEvent::DuetLyric { timestamp, text } => {
+                    (Singer::Duet, *timestamp, text.clone())
+                }
+                _ => continue,
+            };
+            event_indices.entry(key).or_default().push_back(i);
+        }
+
+        let mut filled_phrases = 0;
+        let mut mismatches = vec![];
+        for (i, (line_number, syllables)) in self.lines.iter().enumerate() {
+            let Some((singer, phrase)) = ordered.get(i) else {
+                mismatches.push(SyllableMismatch {
+                    line_number: *line_number,
+                    phrase_start_timestamp: None,
+                    phrase_syllables: 0,
+                    line_syllables: syllables.len(),
+                });
+                continue;
+            };
+            let lyrics = phrase.get_lyrics();
+            if lyrics.len() != syllables.len() {
+                mismatches.push(SyllableMismatch {
+                    line_number: *line_number,
+                    phrase_start_timestamp: Some(phrase.get_start_timestamp()),
+                    phrase_syllables: lyrics.len(),
+                    line_syllables: syllables.len(),
+                });
+                continue;
+            }
+            for (lyric, syllable) in lyrics.iter().zip(syllables) {
+                let index = event_indices
+                    .get_mut(&(*singer, lyric.get_timestamp(), lyric.get_text().to_string()))
+                    .and_then(VecDeque::pop_front);
+                if let Some(LyricEvent::Lyric { text, .. } | LyricEvent::DuetLyric { text, .. }) =
+                    index.and_then(|index| lyrics_events.get_mut(index))
+                {
+                    *text = syllable.clone();
+                }
+            }
+            filled_phrases += 1;
+        }
+        PlainTextReport {
+            filled_phrases,
+            unfilled_phrases: ordered.len().saturating_sub(self.lines.len()),
+            mismatches,
+        }
+    }
+}
+
+impl Display for PlainTextLyrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (_, syllables) in &self.lines {
+            let mut separator = "";
+            for syllable in syllables {
+                write!(f, "{}{}", separator, syllable)?;
+                separator = if syllable.ends_with('-') { "" } else { " " };
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::chart::Chart;
+
+    use super::*;
+
+    fn example_events() -> Vec<LyricEvent> {
+        vec![
+            LyricEvent::PhraseStart { timestamp: 0 },
+            LyricEvent::Lyric {
+                timestamp: 0,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 10,
+                text: "lo".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 20 },
+            LyricEvent::DuetPhraseStart { timestamp: 30 },
+            LyricEvent::DuetLyric {
+                timestamp: 30,
+                text: "world".to_string(),
+            },
+            LyricEvent::DuetPhraseEnd { timestamp: 40 },
+            LyricEvent::PhraseStart { timestamp: 50 },
+            LyricEvent::Lyric {
+                timestamp: 50,
+                text: "bye".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 60 },
+        ]
+    }
+
+    #[test]
+    fn fill_phrases() {
+        let mut events = example_events();
+        let report = PlainTextLyrics::new("Hal-lo\n\nWelt\nauf Wie-der-seh-en\nextra")
+            .fill_phrases(&mut events);
+        assert_eq!(report.get_filled_phrases(), 2);
+        assert_eq!(report.get_unfilled_phrases(), 0);
+        assert_eq!(
+            report
+                .get_mismatches()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "line 4: 5 syllables, but the phrase at 50 has 1",
+                "line 5: 1 syllables, but there are no phrases left",
+            ]
+        );
+        let mut expected = example_events();
+        expected[1] = LyricEvent::Lyric {
+            timestamp: 0,
+            text: "Hal-".to_string(),
+        };
+        expected[5] = LyricEvent::DuetLyric {
+            timestamp: 30,
+            text: "Welt".to_string(),
+        };
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn text_round_trip() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            text_round_trip_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn text_round_trip_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        // syllables that are blank, only hyphens or hold spaces cannot be written as plain text
+        let writable = chart.get_lyrics().iter().all(|event| match event {
+            LyricEvent::Lyric { text, .. } | LyricEvent::DuetLyric { text, .. } => {
+                !text.trim_matches('-').is_empty() && !text.contains(char::is_whitespace)
+            }
+            _ => true,
+        });
+        if !writable {
+            return Ok(());
+        }
+        let text = PlainTextLyrics::from_phrases(&LyricPhraseCollection::new(chart.get_lyrics()));
+        let text = PlainTextLyrics::new(&text.to_string());
+        let mut lyrics = chart.get_lyrics().clone();
+        let report = text.fill_phrases(&mut lyrics);
+        assert!(report.get_mismatches().is_empty());
+        assert_eq!(&lyrics, chart.get_lyrics());
+        Ok(())
+    }
+}
@@ -0,0 +1,547 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chart::{Chart, KeyPressEvent, LyricEvent};
+use crate::phrases::Singer;
+use crate::resolution::{tick_collisions, TickCollision};
+use crate::TimestampedEvent;
+
+/// A grid to snap events to, named like the step setting of chart editors: `1/16` is a
+/// sixteenth note, a quarter of a beat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapGrid {
+    Quarter,
+    Eighth,
+    Twelfth,
+    Sixteenth,
+    TwentyFourth,
+    ThirtySecond,
+}
+
+impl SnapGrid {
+    /// The grid with the given step, such as `16` for `1/16`.
+    #[must_use]
+    pub const fn from_step(step: u32) -> Option<Self> {
+        match step {
+            4 => Some(Self::Quarter),
+            8 => Some(Self::Eighth),
+            12 => Some(Self::Twelfth),
+            16 => Some(Self::Sixteenth),
+            24 => Some(Self::TwentyFourth),
+            32 => Some(Self::ThirtySecond),
+            _ => None,
+        }
+    }
+
+    /// The number of grid lines in a whole note, four beats.
+    #[must_use]
+    pub const fn get_step(self) -> u32 {
+        match self {
+            Self::Quarter => 4,
+            Self::Eighth => 8,
+            Self::Twelfth => 12,
+            Self::Sixteenth => 16,
+            Self::TwentyFourth => 24,
+            Self::ThirtySecond => 32,
+        }
+    }
+
+    /// The grid line nearest to a tick. Grid lines that fall between ticks at the chart's
+    /// resolution are rounded to the nearest tick.
+    #[must_use]
+    pub fn nearest(self, resolution: u32, timestamp: u32) -> u32 {
+        let step = u64::from(self.get_step());
+        let whole = u64::from(resolution.max(1)) * 4;
+        let line = |index: u64| (index * whole + step / 2) / step;
+        let index = (u64::from(timestamp) * step + whole / 2) / whole;
+        [index.saturating_sub(1), index, index + 1]
+            .into_iter()
+            .map(line)
+            .min_by_key(|tick| tick.abs_diff(u64::from(timestamp)))
+            .and_then(|tick| u32::try_from(tick).ok())
+            .unwrap_or(timestamp)
+    }
+}
+
+/// Settings for `snap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapOptions {
+    pub grid: SnapGrid,
+    /// Leave events further than this many ticks from the grid where they are, such as
+    /// triplets on a straight grid.
+    pub max_distance: Option<u32>,
+    /// Also snap the notes and special spans of every track.
+    pub notes: bool,
+}
+
+/// An event moved by `snap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapMove {
+    section: String,
+    from: u32,
+    to: u32,
+}
+
+impl SnapMove {
+    /// The chart section the event is in: `Events` or a track name such as `ExpertSingle`.
+    #[must_use]
+    pub fn get_section(&self) -> &str {
+        &self.section
+    }
+
+    #[must_use]
+    pub const fn get_from(&self) -> u32 {
+        self.from
+    }
+
+    #[must_use]
+    pub const fn get_to(&self) -> u32 {
+        self.to
+    }
+}
+
+impl Display for SnapMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {} -> {}", self.section, self.from, self.to)
+    }
+}
+
+/// A phrase start or end that snapping moved past a lyric of the same singer, so the lyric
+/// now belongs to a different phrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhraseCrossing {
+    singer: Singer,
+    marker: SnapMove,
+    lyric: SnapMove,
+}
+
+impl PhraseCrossing {
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    /// The move of the phrase start or end.
+    #[must_use]
+    pub const fn get_marker(&self) -> &SnapMove {
+        &self.marker
+    }
+
+    /// The move of the lyric it crossed, which may not have moved at all.
+    #[must_use]
+    pub const fn get_lyric(&self) -> &SnapMove {
+        &self.lyric
+    }
+}
+
+impl Display for PhraseCrossing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[Events] {} phrase marker {} -> {} crosses lyric {} -> {}",
+            self.singer, self.marker.from, self.marker.to, self.lyric.from, self.lyric.to
+        )
+    }
+}
+
+/// What `snap` changed in a chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapReport {
+    moves: Vec<SnapMove>,
+    collisions: Vec<TickCollision>,
+    crossings: Vec<PhraseCrossing>,
+}
+
+impl SnapReport {
+    /// Every moved event, lyrics first and then tracks in the order they are written.
+    #[must_use]
+    pub const fn get_moves(&self) -> &Vec<SnapMove> {
+        &self.moves
+    }
+
+    /// Lyrics of the same singer, or chords of the same track, that were apart and now share a
+    /// tick.
+    #[must_use]
+    pub const fn get_collisions(&self) -> &Vec<TickCollision> {
+        &self.collisions
+    }
+
+    /// Phrase starts and ends that moved past a lyric of the same singer.
+    #[must_use]
+    pub const fn get_crossings(&self) -> &Vec<PhraseCrossing> {
+        &self.crossings
+    }
+}
+
+/// Finds the lyrics a phrase marker moved past. A lyric on the tick of a phrase start or end
+/// comes after it, as phrase events are processed first.
+///
+/// # Arguments
+///
+/// * `lyrics`: the moves of the lyrics of a singer, ordered by their original tick.
+/// * `markers`: the moves of the phrase starts and ends of the singer.
+fn phrase_crossings(
+    singer: Singer,
+    lyrics: &[(u32, u32)],
+    markers: &[(u32, u32)],
+) -> Vec<PhraseCrossing> {
+    let furthest = lyrics
+        .iter()
+        .map(|(from, to)| from.abs_diff(*to))
+        .chain(markers.iter().map(|(from, to)| from.abs_diff(*to)))
+        .max()
+        .unwrap_or(0);
+    let mut crossings = vec![];
+    for (marker_from, marker_to) in markers {
+        // only lyrics this close to the marker can end up on its other side
+        let low = marker_from.min(marker_to).saturating_sub(furthest);
+        let high = marker_from.max(marker_to).saturating_add(furthest);
+        let first = lyrics.partition_point(|(from, _)| *from < low);
+        for (lyric_from, lyric_to) in lyrics[first..].iter().take_while(|(from, _)| *from <= high) {
+            if (lyric_from >= marker_from) != (lyric_to >= marker_to) {
+                crossings.push(PhraseCrossing {
+                    singer,
+                    marker: SnapMove {
+                        section: "Events".to_string(),
+                        from: *marker_from,
+                        to: *marker_to,
+                    },
+                    lyric: SnapMove {
+                        section: "Events".to_string(),
+                        from: *lyric_from,
+                        to: *lyric_to,
+                    },
+                });
+            }
+        }
+    }
+    crossings
+}
+
+/// Snaps the lyrics and phrase events of a chart, and optionally its notes, to a grid, such as
+/// after importing lyrics from an LRC file or tapping them in by hand.
+///
+/// Sustains and special spans get their end snapped as well, so notes stay inside the star
+/// power phrases they were in. Sections and other events keep their ticks.
+///
+/// # Arguments
+///
+/// * `chart`: the chart to snap.
+/// * `options`: the grid, the largest distance to move an event by and whether to snap notes.
+///
+/// returns: the moved events, the collisions snapping created and the lyrics that moved to
+/// another phrase.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::quantize::{snap, SnapGrid, SnapOptions};
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// let mut chart = Chart::new(&file_content).unwrap();
+/// let options = SnapOptions {
+///     grid: SnapGrid::Sixteenth,
+///     max_distance: Some(12),
+///     notes: false,
+/// };
+/// snap(&mut chart, &options);
+/// assert!(snap(&mut chart, &options).get_moves().is_empty());
+/// ```
+pub fn snap(chart: &mut Chart, options: &SnapOptions) -> SnapReport {
+    let resolution = chart.get_resolution();
+    let snap_tick = |timestamp: u32| {
+        let snapped = options.grid.nearest(resolution, timestamp);
+        if options
+            .max_distance
+            .is_some_and(|max| snapped.abs_diff(timestamp) > max)
+        {
+            timestamp
+        } else {
+            snapped
+        }
+    };
+    let mut moves = vec![];
+    let mut collisions = vec![];
+
+    let mut main_lyrics = vec![];
+    let mut duet_lyrics = vec![];
+    let mut main_markers = vec![];
+    let mut duet_markers = vec![];
+    let lyrics = chart.get_lyrics_mut();
+    for event in lyrics.iter_mut() {
+        let from = event.get_timestamp();
+        let to = match event {
+            LyricEvent::Lyric { .. } => {
+                let to = snap_tick(from);
+                main_lyrics.push((from, to));
+                to
+            }
+            LyricEvent::DuetLyric { .. } => {
+                let to = snap_tick(from);
+                duet_lyrics.push((from, to));
+                to
+            }
+            LyricEvent::PhraseStart { .. } | LyricEvent::PhraseEnd { .. } => {
+                let to = snap_tick(from);
+                main_markers.push((from, to));
+                to
+            }
+            LyricEvent::DuetPhraseStart { .. } | LyricEvent::DuetPhraseEnd { .. } => {
+                let to = snap_tick(from);
+                duet_markers.push((from, to));
+                to
+            }
+            LyricEvent::Section { .. } | LyricEvent::OtherLyricEvent { .. } => from,
+        };
+        if to != from {
+            event.set_timestamp(to);
+            moves.push(SnapMove {
+                section: "Events".to_string(),
+                from,
+                to,
+            });
+        }
+    }
+    lyrics.sort_by_key(|event| (event.get_timestamp(), event.processing_rank()));
+    main_lyrics.sort_unstable();
+    duet_lyrics.sort_unstable();
+    let mut crossings = phrase_crossings(Singer::Main, &main_lyrics, &main_markers);
+    crossings.extend(phrase_crossings(Singer::Duet, &duet_lyrics, &duet_markers));
+    collisions.extend(tick_collisions("Events", main_lyrics.into_iter()));
+    collisions.extend(tick_collisions("Events", duet_lyrics.into_iter()));
+
+    if options.notes {
+        let mut names: Vec<String> = chart.get_key_presses().keys().cloned().collect();
+        names.sort_by_key(|name| Chart::track_order(name));
+        for name in names {
+            let Some(events) = chart.get_key_presses_mut().get_mut(&name) else {
+                continue;
+            };
+            let mut chords = vec![];
+            for event in events.iter_mut() {
+                let from = event.get_timestamp();
+                match event {
+                    KeyPressEvent::Note { duration, .. }
+                    | KeyPressEvent::Special { duration, .. } => {
+                        let to = snap_tick(from);
+                        if *duration > 0 {
+                            *duration =
+                                snap_tick(from.saturating_add(*duration)).saturating_sub(to);
+                        }
+                        if matches!(event, KeyPressEvent::Note { .. }) {
+                            chords.push((from, to));
+                        }
+                        if to != from {
+                            event.set_timestamp(to);
+                            moves.push(SnapMove {
+                                section: name.clone(),
+                                from,
+                                to,
+                            });
+                        }
+                    }
+                    KeyPressEvent::TextEvent { .. } | KeyPressEvent::OtherKeyPress { .. } => {}
+                }
+            }
+            events.sort_by_key(TimestampedEvent::get_timestamp);
+            collisions.extend(tick_collisions(&name, chords.into_iter()));
+        }
+    }
+    SnapReport {
+        moves,
+        collisions,
+        crossings,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use super::*;
+
+    #[test]
+    fn nearest() {
+        assert_eq!(SnapGrid::Sixteenth.nearest(192, 47), 48);
+        assert_eq!(SnapGrid::Sixteenth.nearest(192, 25), 48);
+        assert_eq!(SnapGrid::Sixteenth.nearest(192, 23), 0);
+        assert_eq!(SnapGrid::Twelfth.nearest(192, 70), 64);
+        assert_eq!(SnapGrid::Twelfth.nearest(100, 34), 33);
+        assert_eq!(SnapGrid::from_step(24), Some(SnapGrid::TwentyFourth));
+        assert_eq!(SnapGrid::from_step(5), None);
+    }
+
+    #[test]
+    fn snap_lyrics() {
+        let mut chart = Chart::from_parts(
+            HashMap::from([("Resolution".to_string(), "192".to_string())]),
+            vec![
+                LyricEvent::PhraseStart { timestamp: 5 },
+                LyricEvent::Lyric {
+                    timestamp: 5,
+                    text: "one".to_string(),
+                },
+                LyricEvent::Lyric {
+                    timestamp: 40,
+                    text: "two".to_string(),
+                },
+                LyricEvent::Lyric {
+                    timestamp: 52,
+                    text: "three".to_string(),
+                },
+                LyricEvent::Lyric {
+                    timestamp: 130,
+                    text: "four".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 200 },
+            ],
+            vec![],
+            HashMap::from([(
+                "ExpertSingle".to_string(),
+                vec![KeyPressEvent::Note {
+                    timestamp: 5,
+                    duration: 90,
+                    key: 0,
+                }],
+            )]),
+        );
+        let report = snap(
+            &mut chart,
+            &SnapOptions {
+                grid: SnapGrid::Sixteenth,
+                max_distance: Some(10),
+                notes: false,
+            },
+        );
+        let timestamps: Vec<u32> = chart
+            .get_lyrics()
+            .iter()
+            .map(TimestampedEvent::get_timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![0, 0, 48, 48, 130, 192]);
+        assert_eq!(report.get_moves().len(), 5);
+        assert_eq!(report.get_moves()[0].to_string(), "[Events] 5 -> 0");
+        assert_eq!(report.get_collisions().len(), 1);
+        assert_eq!(
+            report.get_collisions()[0].get_original_timestamps(),
+            &vec![40, 52]
+        );
+        assert_eq!(
+            chart.get_key_presses()["ExpertSingle"][0].get_timestamp(),
+            5
+        );
+
+        snap(
+            &mut chart,
+            &SnapOptions {
+                grid: SnapGrid::Sixteenth,
+                max_distance: None,
+                notes: true,
+            },
+        );
+        assert_eq!(
+            chart.get_key_presses()["ExpertSingle"][0],
+            KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 96,
+                key: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn snap_phrase_crossing() {
+        let mut chart = Chart::from_parts(
+            HashMap::from([("Resolution".to_string(), "192".to_string())]),
+            vec![
+                LyricEvent::PhraseStart { timestamp: 0 },
+                LyricEvent::Lyric {
+                    timestamp: 47,
+                    text: "one".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 50 },
+                LyricEvent::PhraseStart { timestamp: 100 },
+                LyricEvent::Lyric {
+                    timestamp: 100,
+                    text: "two".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 150 },
+            ],
+            vec![],
+            HashMap::new(),
+        );
+        let report = snap(
+            &mut chart,
+            &SnapOptions {
+                grid: SnapGrid::Sixteenth,
+                max_distance: None,
+                notes: false,
+            },
+        );
+        assert_eq!(report.get_crossings().len(), 1);
+        assert_eq!(report.get_crossings()[0].get_singer(), Singer::Main);
+        assert_eq!(
+            report.get_crossings()[0].to_string(),
+            "[Events] main phrase marker 50 -> 48 crosses lyric 47 -> 48"
+        );
+        assert_eq!(
+            chart.get_lyrics()[1],
+            LyricEvent::PhraseEnd { timestamp: 48 }
+        );
+        assert_eq!(
+            chart.get_lyrics()[2],
+            LyricEvent::Lyric {
+                timestamp: 48,
+                text: "one".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn snap_charts() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            snap_charts_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn snap_charts_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let mut chart = Chart::new(&file_content)?;
+        let options = SnapOptions {
+            grid: SnapGrid::ThirtySecond,
+            max_distance: None,
+            notes: true,
+        };
+        snap(&mut chart, &options);
+        let step = chart.get_resolution() / 8;
+        assert!(chart
+            .get_lyrics()
+            .iter()
+            .filter(|event| matches!(event, LyricEvent::Lyric { .. }))
+            .all(|event| event.get_timestamp() % step == 0));
+        assert!(snap(&mut chart, &options).get_moves().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::chart::{Chart, KeyPressEvent};
+use crate::notes::{
+    chords, note_kinds, star_power_phrases, Chord, Difficulty, Instrument, NoteKind, OPEN_KEY,
+};
+use crate::TimestampedEvent;
+
+/// Drum kick, thinned out of chords before any pad.
+const KICK_KEY: u32 = 0;
+/// Second kick pedal, only played on Expert+.
+const DOUBLE_KICK_KEY: u32 = 32;
+
+/// How far a difficulty strips down an expert track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReductionRules {
+    /// Shortest distance between two notes, in ticks.
+    min_gap: u32,
+    max_chord_size: usize,
+    /// Number of frets a five fret guitar track is collapsed onto.
+    frets: u32,
+    hopos: bool,
+    taps: bool,
+}
+
+impl ReductionRules {
+    const fn new(difficulty: Difficulty, resolution: u32) -> Self {
+        match difficulty {
+            Difficulty::Expert => Self {
+                min_gap: 0,
+                max_chord_size: usize::MAX,
+                frets: 5,
+                hopos: true,
+                taps: true,
+            },
+            Difficulty::Hard => Self {
+                min_gap: resolution / 3,
+                max_chord_size: 3,
+                frets: 5,
+                hopos: true,
+                taps: true,
+            },
+            Difficulty::Medium => Self {
+                min_gap: resolution / 2,
+                max_chord_size: 2,
+                frets: 4,
+                hopos: true,
+                taps: false,
+            },
+            Difficulty::Easy => Self {
+                min_gap: resolution,
+                max_chord_size: 1,
+                frets: 3,
+                hopos: false,
+                taps: false,
+            },
+        }
+    }
+}
+
+/// How strongly a tick falls on the beat: 4 on the beat, 3 on the half beat, 2 on sixteenths
+/// and triplets, 1 anywhere else.
+const fn beat_strength(timestamp: u32, resolution: u32) -> u8 {
+    let offset = timestamp % resolution;
+    if offset == 0 {
+        4
+    } else if offset * 2 == resolution {
+        3
+    } else if (offset * 4).is_multiple_of(resolution) || (offset * 3).is_multiple_of(resolution) {
+        2
+    } else {
+        1
+    }
+}
+
+/// The pad a drum modifier such as a cymbal, accent or ghost flag applies to.
+const fn modified_pad(key: u32) -> Option<u32> {
+    match key {
+        66..=68 => Some(key - 64),
+        34..=38 => Some(key - 33),
+        40..=44 => Some(key - 39),
+        _ => None,
+    }
+}
+
+/// Derives a lower difficulty track from the events of an expert track.
+///
+/// Notes are removed weakest beat first until no two notes are closer than the difficulty
+/// allows, keeping at least one note in every star power phrase. Chords are thinned to their
+/// outer gems, five fret tracks are collapsed onto fewer frets, and sustains are cut short of
+/// the next note. Notes keep being played as strums, HOPOs or taps where the difficulty allows
+/// it, with forced flags rewritten to match. Star power, solo markers and other events are
+/// kept as they are.
+///
+/// # Arguments
+///
+/// * `instrument`: the instrument the track is played on.
+/// * `events`: the events of the expert track, in any order.
+/// * `resolution`: the number of ticks per beat.
+/// * `difficulty`: the difficulty to reduce the track to.
+///
+/// returns: `Vec<KeyPressEvent>`
+///
+/// # Examples
+///
+/// ```
+/// use duet_charter_lib::chart::KeyPressEvent;
+/// use duet_charter_lib::notes::{Difficulty, Instrument};
+/// use duet_charter_lib::reduction::reduce_track;
+///
+/// let events: Vec<KeyPressEvent> = (0..8)
+///     .map(|i| KeyPressEvent::Note { timestamp: i * 96, duration: 0, key: 4 })
+///     .collect();
+/// let easy = reduce_track(Instrument::Guitar, &events, 192, Difficulty::Easy);
+/// assert_eq!(easy.len(), 4);
+/// assert_eq!(easy[1], KeyPressEvent::Note { timestamp: 192, duration: 0, key: 2 });
+/// ```
+#[must_use]
+pub fn reduce_track(
+    instrument: Instrument,
+    events: &[KeyPressEvent],
+    resolution: u32,
+    difficulty: Difficulty,
+) -> Vec<KeyPressEvent> {
+    let resolution = resolution.max(1);
+    let rules = ReductionRules::new(difficulty, resolution);
+    let expert = chords(instrument, events);
+    let expert_kinds = note_kinds(instrument, &expert, resolution);
+
+    let mut by_strength: Vec<usize> = (0..expert.len()).collect();
+    by_strength.sort_by_key(|i| {
+        (
+            Reverse(beat_strength(expert[*i].get_timestamp(), resolution)),
+            Reverse(expert[*i].get_sustain()),
+            expert[*i].get_timestamp(),
+        )
+    });
+    let mut kept: BTreeSet<(u32, usize)> = BTreeSet::new();
+    let gap = rules.min_gap.max(1);
+    let too_close = |kept: &BTreeSet<(u32, usize)>, timestamp: u32| {
+        kept.range((timestamp, 0)..)
+            .next()
+            .is_some_and(|(next, _)| next - timestamp < gap)
+            || kept
+                .range(..(timestamp, 0))
+                .next_back()
+                .is_some_and(|(previous, _)| timestamp - previous < gap)
+    };
+    for i in &by_strength {
+        let timestamp = expert[*i].get_timestamp();
+        if !too_close(&kept, timestamp) {
+            kept.insert((timestamp, *i));
+        }
+    }
+    for (start, duration) in star_power_phrases(events) {
+        let end = start.saturating_add(duration.max(1));
+        if kept.range((start, 0)..(end, 0)).next().is_some() {
+            continue;
+        }
+        if let Some(i) = by_strength.iter().find(|i| {
+            let timestamp = expert[**i].get_timestamp();
+            timestamp >= start && timestamp < end
+        }) {
+            let timestamp = expert[*i].get_timestamp();
+            kept.retain(|(other, _)| other.abs_diff(timestamp) >= gap);
+            kept.insert((timestamp, *i));
+        }
+    }
+
+    let kept: Vec<usize> = kept.into_iter().map(|(_, i)| i).collect();
+    let mut reduced: Vec<Chord> = kept
+        .iter()
+        .enumerate()
+        .map(|(position, i)| {
+            let chord = &expert[*i];
+            let keys = thin_chord(instrument, chord.get_keys(), &rules);
+            let next = kept
+                .get(position + 1)
+                .map(|next| expert[*next].get_timestamp());
+            let mut sustain = chord.get_sustain();
+            if let Some(next) = next {
+                let latest_end = next.saturating_sub(resolution / 8);
+                sustain = sustain.min(latest_end.saturating_sub(chord.get_timestamp()));
+            }
+            if sustain < rules.min_gap {
+                sustain = 0;
+            }
+            Chord::new(chord.get_timestamp(), keys, sustain, false, false)
+        })
+        .collect();
+
+    let natural_kinds = note_kinds(instrument, &reduced, resolution);
+    for ((chord, i), natural) in reduced.iter_mut().zip(&kept).zip(natural_kinds) {
+        let wanted = match expert_kinds[*i] {
+            NoteKind::Tap if rules.taps => NoteKind::Tap,
+            NoteKind::Tap | NoteKind::Hopo if rules.hopos => NoteKind::Hopo,
+            _ => NoteKind::Strum,
+        };
+        *chord = Chord::new(
+            chord.get_timestamp(),
+            chord.get_keys().clone(),
+            chord.get_sustain(),
+            wanted != NoteKind::Tap && wanted != natural,
+            wanted == NoteKind::Tap,
+        );
+    }
+
+    let mut drum_modifiers: HashMap<u32, Vec<u32>> = HashMap::new();
+    if instrument == Instrument::Drums {
+        for event in events {
+            if let KeyPressEvent::Note { timestamp, key, .. } = event {
+                if modified_pad(*key).is_some() {
+                    drum_modifiers.entry(*timestamp).or_default().push(*key);
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<KeyPressEvent> = vec![];
+    for chord in &reduced {
+        result.extend(chord.to_events(instrument));
+        if let Some(modifiers) = drum_modifiers.get(&chord.get_timestamp()) {
+            for key in modifiers {
+                if modified_pad(*key).is_some_and(|pad| chord.get_keys().contains(&pad)) {
+                    result.push(KeyPressEvent::Note {
+                        timestamp: chord.get_timestamp(),
+                        duration: 0,
+                        key: *key,
+                    });
+                }
+            }
+        }
+    }
+    result.extend(
+        events
+            .iter()
+            .filter(|event| !matches!(event, KeyPressEvent::Note { .. }))
+            .cloned(),
+    );
+    result.sort_by_key(|event| {
+        (
+            event.get_timestamp(),
+            match event {
+                KeyPressEvent::Note { .. } => 0,
+                KeyPressEvent::Special { .. } => 1,
+                KeyPressEvent::TextEvent { .. } => 2,
+                KeyPressEvent::OtherKeyPress { .. } => 3,
+            },
+        )
+    });
+    result
+}
+
+/// Collapses the frets of a chord and drops gems until it fits the difficulty.
+fn thin_chord(instrument: Instrument, keys: &[u32], rules: &ReductionRules) -> Vec<u32> {
+    match instrument {
+        Instrument::Drums => {
+            let mut pads: Vec<u32> = keys
+                .iter()
+                .copied()
+                .filter(|key| *key != KICK_KEY && *key != DOUBLE_KICK_KEY)
+                .collect();
+            if keys.contains(&KICK_KEY) || keys.contains(&DOUBLE_KICK_KEY) {
+                pads.push(KICK_KEY);
+            }
+            pads.truncate(rules.max_chord_size);
+            pads
+        }
+        Instrument::Guitar | Instrument::SixFret => {
+            let mut frets: Vec<u32> = keys
+                .iter()
+                .map(|key| {
+                    if instrument == Instrument::Guitar && *key != OPEN_KEY {
+                        (key * (rules.frets - 1) + 2) / 4
+                    } else {
+                        *key
+                    }
+                })
+                .collect();
+            frets.sort_unstable();
+            frets.dedup();
+            let size = frets.len().min(rules.max_chord_size);
+            if size <= 1 {
+                frets.truncate(1);
+                return frets;
+            }
+            (0..size)
+                .map(|i| frets[i * (frets.len() - 1) / (size - 1)])
+                .collect()
+        }
+    }
+}
+
+/// Writes Hard, Medium and Easy tracks derived from every expert track of a chart.
+///
+/// # Arguments
+///
+/// * `chart`: the chart to add tracks to.
+/// * `overwrite`: whether to replace tracks the chart already has.
+///
+/// returns: the names of the tracks written, in the order they were written.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::reduction::generate_difficulties;
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// let mut chart = Chart::new(&file_content).unwrap();
+/// let written = generate_difficulties(&mut chart, false);
+/// assert_eq!(written, vec!["HardSingle", "MediumSingle", "EasySingle"]);
+/// ```
+pub fn generate_difficulties(chart: &mut Chart, overwrite: bool) -> Vec<String> {
+    let resolution = chart.get_resolution();
+    let mut experts: Vec<(String, Vec<KeyPressEvent>)> = chart
+        .get_key_presses()
+        .iter()
+        .filter(|(name, _)| name.starts_with(Difficulty::Expert.name()))
+        .map(|(name, events)| (name.clone(), events.clone()))
+        .collect();
+    experts.sort_by_key(|(name, _)| Chart::track_order(name));
+
+    let mut written = vec![];
+    for (name, events) in experts {
+        let instrument_name = &name[Difficulty::Expert.name().len()..];
+        let instrument = Instrument::from_track(&name);
+        for difficulty in [Difficulty::Hard, Difficulty::Medium, Difficulty::Easy] {
+            let track = format!("{}{}", difficulty.name(), instrument_name);
+            if !overwrite && chart.get_key_presses().contains_key(&track) {
+                continue;
+            }
+            chart.insert_track(
+                &track,
+                reduce_track(instrument, &events, resolution, difficulty),
+            );
+            written.push(track);
+        }
+    }
+    written
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use super::*;
+
+    fn note(timestamp: u32, duration: u32, key: u32) -> KeyPressEvent {
+        KeyPressEvent::Note {
+            timestamp,
+            duration,
+            key,
+        }
+    }
+
+    #[test]
+    fn reduce_guitar() {
+        let events = vec![
+            note(0, 0, 0),
+            note(0, 0, 2),
+            note(0, 0, 4),
+            note(48, 0, 1),
+            note(96, 0, 3),
+            note(144, 0, 2),
+            note(192, 300, 4),
+            note(240, 0, 3),
+            note(240, 0, 6),
+            note(384, 0, 0),
+            KeyPressEvent::Special {
+                timestamp: 230,
+                special_type: 2,
+                duration: 20,
+            },
+        ];
+        assert_eq!(
+            reduce_track(Instrument::Guitar, &events, 192, Difficulty::Hard),
+            vec![
+                note(0, 0, 0),
+                note(0, 0, 2),
+                note(0, 0, 4),
+                note(96, 0, 3),
+                note(96, 0, 5),
+                KeyPressEvent::Special {
+                    timestamp: 230,
+                    special_type: 2,
+                    duration: 20,
+                },
+                note(240, 0, 3),
+                note(240, 0, 6),
+                note(384, 0, 0),
+            ]
+        );
+        assert_eq!(
+            reduce_track(Instrument::Guitar, &events, 192, Difficulty::Medium),
+            vec![
+                note(0, 0, 0),
+                note(0, 0, 3),
+                note(96, 0, 2),
+                note(96, 0, 5),
+                KeyPressEvent::Special {
+                    timestamp: 230,
+                    special_type: 2,
+                    duration: 20,
+                },
+                note(240, 0, 2),
+                note(240, 0, 5),
+                note(384, 0, 0),
+            ]
+        );
+        assert_eq!(
+            reduce_track(Instrument::Guitar, &events, 192, Difficulty::Easy),
+            vec![
+                note(0, 0, 0),
+                KeyPressEvent::Special {
+                    timestamp: 230,
+                    special_type: 2,
+                    duration: 20,
+                },
+                note(240, 0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn reduce_drums() {
+        let events = vec![
+            note(0, 0, 0),
+            note(0, 0, 32),
+            note(0, 0, 2),
+            note(0, 0, 66),
+            note(0, 0, 4),
+            note(0, 0, 68),
+        ];
+        assert_eq!(
+            reduce_track(Instrument::Drums, &events, 192, Difficulty::Medium),
+            vec![note(0, 0, 2), note(0, 0, 4), note(0, 0, 66), note(0, 0, 68)]
+        );
+        assert_eq!(
+            reduce_track(Instrument::Drums, &events, 192, Difficulty::Hard),
+            vec![
+                note(0, 0, 0),
+                note(0, 0, 2),
+                note(0, 0, 4),
+                note(0, 0, 66),
+                note(0, 0, 68)
+            ]
+        );
+    }
+
+    #[test]
+    fn generated_difficulties() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            generated_difficulties_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn generated_difficulties_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let mut chart = Chart::new(&file_content)?;
+        let resolution = chart.get_resolution();
+        let written = generate_difficulties(&mut chart, false);
+        for name in &written {
+            let (difficulty, _) = Difficulty::from_track(name).unwrap_or((Difficulty::Expert, ""));
+            let rules = ReductionRules::new(difficulty, resolution);
+            let instrument = Instrument::from_track(name);
+            let track = &chart.get_key_presses()[name];
+            let reduced = chords(instrument, track);
+            assert!(reduced
+                .iter()
+                .all(|chord| chord.get_keys().len() <= rules.max_chord_size));
+            assert_eq!(
+                star_power_phrases(track).len(),
+                star_power_phrases(
+                    &chart.get_key_presses()
+                        [&format!("Expert{}", &name[difficulty.name().len()..])]
+                )
+                .len()
+            );
+            if !rules.taps {
+                assert!(reduced.iter().all(|chord| !chord.is_tap()));
+            }
+        }
+        assert_eq!(Chart::new(&chart.to_string())?, chart);
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chart::{Chart, KeyPressEvent};
+use crate::TimestampedEvent;
+
+/// Events of one part of a chart at different ticks that land on the same tick after rescaling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickCollision {
+    section: String,
+    timestamp: u32,
+    original_timestamps: Vec<u32>,
+}
+
+impl TickCollision {
+    /// The chart section the events are in: `SyncTrack`, `Events` or a track name such as
+    /// `ExpertSingle`.
+    #[must_use]
+    pub fn get_section(&self) -> &str {
+        &self.section
+    }
+
+    /// The tick the events land on at the new resolution.
+    #[must_use]
+    pub const fn get_timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// The ticks the events were at before rescaling, ascending.
+    #[must_use]
+    pub const fn get_original_timestamps(&self) -> &Vec<u32> {
+        &self.original_timestamps
+    }
+}
+
+impl Display for TickCollision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let original: Vec<String> = self
+            .original_timestamps
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        write!(
+            f,
+            "[{}] ticks {} all land on {}",
+            self.section,
+            original.join(", "),
+            self.timestamp
+        )
+    }
+}
+
+/// Groups the ticks of a part of a chart by the tick they move to, keeping the groups of more
+/// than one tick.
+///
+/// # Arguments
+///
+/// * `section`: the chart section the ticks are in.
+/// * `moves`: pairs of the tick of an event and the tick it moves to.
+pub(crate) fn tick_collisions(
+    section: &str,
+    moves: impl Iterator<Item = (u32, u32)>,
+) -> Vec<TickCollision> {
+    let mut moves: Vec<(u32, u32)> = moves.collect();
+    moves.sort_unstable_by_key(|(timestamp, moved)| (*moved, *timestamp));
+    moves.dedup();
+    let mut collisions: Vec<TickCollision> = vec![];
+    for (timestamp, moved) in moves {
+        match collisions.last_mut() {
+            Some(last) if last.timestamp == moved => last.original_timestamps.push(timestamp),
+            _ => collisions.push(TickCollision {
+                section: section.to_string(),
+                timestamp: moved,
+                original_timestamps: vec![timestamp],
+            }),
+        }
+    }
+    collisions.retain(|collision| collision.original_timestamps.len() > 1);
+    collisions
+}
+
+/// Converts ticks between resolutions, rounding to the nearest tick.
+#[derive(Debug, Clone, Copy)]
+struct Scale {
+    from: u64,
+    to: u64,
+}
+
+impl Scale {
+    fn tick(self, timestamp: u32) -> u32 {
+        u32::try_from((u64::from(timestamp) * self.to + self.from / 2) / self.from)
+            .unwrap_or(u32::MAX)
+    }
+
+    /// The ticks of a part of a chart that would land on the same tick.
+    fn collisions<T: TimestampedEvent>(self, section: &str, events: &[T]) -> Vec<TickCollision> {
+        tick_collisions(
+            section,
+            events.iter().map(|event| {
+                let timestamp = event.get_timestamp();
+                (timestamp, self.tick(timestamp))
+            }),
+        )
+    }
+
+    fn retime<T: TimestampedEvent>(self, event: &mut T) {
+        event.set_timestamp(self.tick(event.get_timestamp()));
+    }
+
+    /// Rescales a key press, keeping the end of a sustain or special span on the nearest tick
+    /// to its old end.
+    fn key_press(self, event: &mut KeyPressEvent) {
+        match event {
+            KeyPressEvent::Note {
+                timestamp,
+                duration,
+                ..
+            }
+            | KeyPressEvent::Special {
+                timestamp,
+                duration,
+                ..
+            } => {
+                let end = self.tick(timestamp.saturating_add(*duration));
+                *timestamp = self.tick(*timestamp);
+                *duration = end - *timestamp;
+            }
+            KeyPressEvent::TextEvent { .. } | KeyPressEvent::OtherKeyPress { .. } => {
+                self.retime(event);
+            }
+        }
+    }
+}
+
+/// Rescales every timestamp and duration of a chart to another resolution and updates its
+/// `Resolution` property, so charts of different resolutions can be merged and compared.
+///
+/// Timestamps are rounded to the nearest tick of the new resolution, so lowering the
+/// resolution can put events that were apart on the same tick; those are returned so they can
+/// be checked by hand. Raising the resolution to a multiple of the old one is exact.
+///
+/// # Arguments
+///
+/// * `chart`: the chart to rescale.
+/// * `resolution`: the new number of ticks per beat.
+///
+/// returns: the ticks that collided, by chart section in the order sections are written.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::resolution::rescale;
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// let mut chart = Chart::new(&file_content).unwrap();
+/// let original = chart.clone();
+/// assert!(rescale(&mut chart, original.get_resolution() * 4).is_empty());
+/// assert!(rescale(&mut chart, original.get_resolution()).is_empty());
+/// assert_eq!(chart, original);
+/// ```
+pub fn rescale(chart: &mut Chart, resolution: u32) -> Vec<TickCollision> {
+    let scale = Scale {
+        from: u64::from(chart.get_resolution().max(1)),
+        to: u64::from(resolution.max(1)),
+    };
+    let mut collisions = scale.collisions("SyncTrack", chart.get_tempo_map());
+    collisions.extend(scale.collisions("Events", chart.get_lyrics()));
+    let mut names: Vec<&String> = chart.get_key_presses().keys().collect();
+    names.sort_by_key(|name| Chart::track_order(name));
+    for name in names {
+        collisions.extend(scale.collisions(name, &chart.get_key_presses()[name]));
+    }
+
+    chart
+        .get_tempo_map_mut()
+        .iter_mut()
+        .for_each(|event| scale.retime(event));
+    chart
+        .get_lyrics_mut()
+        .iter_mut()
+        .for_each(|event| scale.retime(event));
+    chart
+        .get_key_presses_mut()
+        .values_mut()
+        .flatten()
+        .for_each(|event| scale.key_press(event));
+    chart.set_property("Resolution", resolution.max(1).to_string());
+    collisions
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use super::*;
+    use crate::chart::{LyricEvent, TempoEvent};
+
+    #[test]
+    fn lower_resolution() {
+        let mut chart = Chart::from_parts(
+            HashMap::from([("Resolution".to_string(), "480".to_string())]),
+            vec![
+                LyricEvent::PhraseStart { timestamp: 480 },
+                LyricEvent::Lyric {
+                    timestamp: 481,
+                    text: "la".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 960 },
+            ],
+            vec![TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            HashMap::from([(
+                "ExpertSingle".to_string(),
+                vec![
+                    KeyPressEvent::Note {
+                        timestamp: 240,
+                        duration: 481,
+                        key: 0,
+                    },
+                    KeyPressEvent::Special {
+                        timestamp: 240,
+                        special_type: 2,
+                        duration: 720,
+                    },
+                ],
+            )]),
+        );
+        let collisions = rescale(&mut chart, 192);
+        assert_eq!(chart.get_resolution(), 192);
+        assert_eq!(
+            chart.get_lyrics(),
+            &vec![
+                LyricEvent::PhraseStart { timestamp: 192 },
+                LyricEvent::Lyric {
+                    timestamp: 192,
+                    text: "la".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 384 },
+            ]
+        );
+        assert_eq!(
+            chart.get_key_presses()["ExpertSingle"],
+            vec![
+                KeyPressEvent::Note {
+                    timestamp: 96,
+                    duration: 192,
+                    key: 0,
+                },
+                KeyPressEvent::Special {
+                    timestamp: 96,
+                    special_type: 2,
+                    duration: 288,
+                },
+            ]
+        );
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].get_section(), "Events");
+        assert_eq!(collisions[0].get_original_timestamps(), &vec![480, 481]);
+        assert_eq!(
+            collisions[0].to_string(),
+            "[Events] ticks 480, 481 all land on 192"
+        );
+    }
+
+    #[test]
+    fn rescale_round_trip() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            rescale_round_trip_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn rescale_round_trip_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let mut rescaled = chart.clone();
+        assert!(rescale(&mut rescaled, chart.get_resolution() * 5).is_empty());
+        assert!(rescale(&mut rescaled, chart.get_resolution()).is_empty());
+        assert_eq!(rescaled.get_tempo_map(), chart.get_tempo_map());
+        assert_eq!(rescaled.get_lyrics(), chart.get_lyrics());
+        assert_eq!(rescaled.get_key_presses(), chart.get_key_presses());
+        Ok(())
+    }
+}
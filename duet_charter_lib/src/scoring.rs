@@ -0,0 +1,388 @@
+use serde::Serialize;
+
+use crate::chart::{Chart, KeyPressEvent};
+use crate::notes::{chords, star_power_phrases, Chord, Instrument};
+use crate::timing::TempoMap;
+
+/// Points for every gem hit.
+pub const NOTE_POINTS: u64 = 50;
+/// Points for every beat a gem is sustained, at a 1x multiplier.
+pub const SUSTAIN_POINTS_PER_BEAT: u64 = 25;
+/// Notes to hit in a row for every step of the multiplier.
+pub(crate) const NOTES_PER_MULTIPLIER: u64 = 10;
+pub(crate) const MAX_MULTIPLIER: u64 = 4;
+/// Share of the star power bar gained by completing a star power phrase.
+pub(crate) const PHRASE_STAR_POWER: f64 = 0.25;
+/// Share of the star power bar needed to activate it.
+pub(crate) const MIN_ACTIVATION: f64 = 0.5;
+/// Measures a full star power bar lasts once activated.
+pub(crate) const DRAIN_MEASURES: f64 = 8.0;
+/// Beats of whammying star power sustains that fill a whole bar.
+pub(crate) const WHAMMY_BEATS_PER_BAR: f64 = 30.0;
+/// Margin for the floating point star power bar.
+pub(crate) const EPSILON: f64 = 1e-9;
+
+/// Score needed for one to six stars, as multiples of the base score, following Clone Hero's
+/// guitar thresholds.
+pub const STAR_MULTIPLIERS: [f64; 6] = [0.1, 0.5, 1.0, 2.0, 2.8, 3.6];
+
+/// When a simulated player activates star power.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivationStrategy {
+    /// Star power is never activated.
+    Never,
+    /// Star power is activated as soon as the bar is half full.
+    Earliest,
+    /// Star power is activated at these ticks, ordered ascending; ticks where it is already
+    /// active or the bar is less than half full are skipped.
+    At(Vec<u32>),
+}
+
+/// A stretch of time star power was active.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Activation {
+    start: u32,
+    end: u32,
+    bar: f64,
+}
+
+impl Activation {
+    #[must_use]
+    pub const fn get_start(&self) -> u32 {
+        self.start
+    }
+
+    /// The tick the bar ran out at.
+    #[must_use]
+    pub const fn get_end(&self) -> u32 {
+        self.end
+    }
+
+    /// The share of the bar that was filled at activation, between 0.5 and 1.
+    #[must_use]
+    pub const fn get_bar(&self) -> f64 {
+        self.bar
+    }
+}
+
+/// The outcome of playing a track without missing a note.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Simulation {
+    note_score: u64,
+    sustain_score: u64,
+    activations: Vec<Activation>,
+}
+
+impl Simulation {
+    #[must_use]
+    pub const fn get_score(&self) -> u64 {
+        self.note_score + self.sustain_score
+    }
+
+    /// Points from hitting gems, with multipliers.
+    #[must_use]
+    pub const fn get_note_score(&self) -> u64 {
+        self.note_score
+    }
+
+    /// Points from holding sustains, with multipliers.
+    #[must_use]
+    pub const fn get_sustain_score(&self) -> u64 {
+        self.sustain_score
+    }
+
+    #[must_use]
+    pub const fn get_activations(&self) -> &Vec<Activation> {
+        &self.activations
+    }
+}
+
+/// A guitar track prepared for scoring, following Clone Hero's rules.
+///
+/// Every gem is worth 50 points and every beat a gem is sustained 25 points, counted per tick
+/// using the chart's resolution. The multiplier grows by one for every ten notes hit in a row,
+/// up to 4x, and is doubled while star power is active. Completing a star power phrase fills a
+/// quarter of the bar and whammying its sustains fills a bar every 30 beats. A half full bar
+/// can be activated and drains over eight measures when full.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoringTrack {
+    name: String,
+    tempo_map: TempoMap,
+    chords: Vec<Chord>,
+    phrases: Vec<(u32, u32)>,
+    in_phrase: Vec<bool>,
+    completes_phrase: Vec<bool>,
+}
+
+impl ScoringTrack {
+    /// Constructor for `ScoringTrack` from the events of a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the track name, such as `ExpertSingle`.
+    /// * `events`: the events of the track, in any order.
+    /// * `tempo_map`: the tempo map of the chart, used to count beats and measures.
+    ///
+    /// returns: `ScoringTrack`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::chart::{KeyPressEvent, TempoEvent};
+    /// use duet_charter_lib::scoring::{ActivationStrategy, ScoringTrack};
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let tempo_map = TempoMap::new(&[TempoEvent::Beat { timestamp: 0, milli_bpm: 120_000 }], 192);
+    /// let events: Vec<KeyPressEvent> = (0..20)
+    ///     .map(|i| KeyPressEvent::Note { timestamp: i * 96, duration: 0, key: 0 })
+    ///     .collect();
+    /// let track = ScoringTrack::new("ExpertSingle", &events, &tempo_map);
+    /// assert_eq!(track.base_score(), 1000);
+    /// assert_eq!(track.simulate(&ActivationStrategy::Never).get_score(), 1500);
+    /// ```
+    #[must_use]
+    pub fn new(name: &str, events: &[KeyPressEvent], tempo_map: &TempoMap) -> Self {
+        let chords = chords(Instrument::from_track(name), events);
+        let phrases = star_power_phrases(events);
+        let phrase_of = |chord: &Chord| {
+            phrases.iter().position(|(start, duration)| {
+                chord.get_timestamp() >= *start
+                    && chord.get_timestamp() < start.saturating_add((*duration).max(1))
+            })
+        };
+        let in_phrase: Vec<bool> = chords
+            .iter()
+            .map(|chord| phrase_of(chord).is_some())
+            .collect();
+        let completes_phrase = chords
+            .iter()
+            .enumerate()
+            .map(|(i, chord)| {
+                phrase_of(chord).is_some_and(|phrase| {
+                    chords
+                        .get(i + 1)
+                        .is_none_or(|next| phrase_of(next) != Some(phrase))
+                })
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            tempo_map: tempo_map.clone(),
+            chords,
+            phrases,
+            in_phrase,
+            completes_phrase,
+        }
+    }
+
+    /// Constructor for `ScoringTrack` from a track of a chart, if the chart has a guitar track
+    /// of that name.
+    #[must_use]
+    pub fn from_chart(chart: &Chart, name: &str) -> Option<Self> {
+        if Instrument::from_track(name) == Instrument::Drums {
+            return None;
+        }
+        let events = chart.get_key_presses().get(name)?;
+        Some(Self::new(name, events, &TempoMap::from_chart(chart)))
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn get_tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    #[must_use]
+    pub const fn get_chords(&self) -> &Vec<Chord> {
+        &self.chords
+    }
+
+    /// The star power phrases as `(timestamp, duration)` pairs, ordered by timestamp.
+    #[must_use]
+    pub const fn get_star_power_phrases(&self) -> &Vec<(u32, u32)> {
+        &self.phrases
+    }
+
+    /// Whether the chord at the given index lies in a star power phrase.
+    #[must_use]
+    pub fn is_in_phrase(&self, chord: usize) -> bool {
+        self.in_phrase.get(chord).copied().unwrap_or(false)
+    }
+
+    /// Whether the chord at the given index is the last one of a star power phrase.
+    #[must_use]
+    pub fn completes_phrase(&self, chord: usize) -> bool {
+        self.completes_phrase.get(chord).copied().unwrap_or(false)
+    }
+
+    /// The score of the track at a constant 1x multiplier without star power, which star
+    /// thresholds are based on.
+    #[must_use]
+    pub fn base_score(&self) -> u64 {
+        let resolution = u64::from(self.tempo_map.get_resolution());
+        let gems = |chord: &Chord| chord.get_keys().len() as u64;
+        let notes: u64 = self
+            .chords
+            .iter()
+            .map(|chord| NOTE_POINTS * gems(chord))
+            .sum();
+        let sustains: u64 = self
+            .chords
+            .iter()
+            .map(|chord| SUSTAIN_POINTS_PER_BEAT * gems(chord) * u64::from(chord.get_sustain()))
+            .sum();
+        notes + (sustains + resolution / 2) / resolution
+    }
+
+    /// The least score for one to six stars.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn star_thresholds(&self) -> [u64; 6] {
+        let base = self.base_score() as f64;
+        STAR_MULTIPLIERS.map(|multiplier| (base * multiplier).ceil() as u64)
+    }
+
+    /// Plays the track without missing a note, activating star power following `strategy`.
+    #[must_use]
+    pub fn simulate(&self, strategy: &ActivationStrategy) -> Simulation {
+        let resolution = self.tempo_map.get_resolution();
+        let requests: &[u32] = match strategy {
+            ActivationStrategy::At(ticks) => ticks,
+            _ => &[],
+        };
+        let mut next_request = 0;
+        let mut next_chord = 0;
+        let mut streak: u64 = 0;
+        let mut bar: f64 = 0.0;
+        let mut active: Option<(u32, f64)> = None;
+        let mut activations = vec![];
+        // (end tick, gems, whammy gains star power)
+        let mut sustains: Vec<(u32, u64, bool)> = vec![];
+        let mut note_score = 0;
+        // sustain points times the resolution, to count single ticks exactly
+        let mut sustain_scaled: u64 = 0;
+
+        let Some(first) = self.chords.first() else {
+            return Simulation {
+                note_score: 0,
+                sustain_score: 0,
+                activations,
+            };
+        };
+        let mut tick = first.get_timestamp();
+        if let Some(request) = requests.first() {
+            tick = tick.min(*request);
+        }
+        loop {
+            let mut wants_activation = matches!(strategy, ActivationStrategy::Earliest);
+            while requests
+                .get(next_request)
+                .is_some_and(|request| *request <= tick)
+            {
+                wants_activation = true;
+                next_request += 1;
+            }
+            if wants_activation && active.is_none() && bar >= MIN_ACTIVATION - EPSILON {
+                active = Some((tick, bar));
+            }
+            let multiplier = |streak: u64, active: bool| {
+                (1 + streak / NOTES_PER_MULTIPLIER).min(MAX_MULTIPLIER) * if active { 2 } else { 1 }
+            };
+
+            if let Some(chord) = self
+                .chords
+                .get(next_chord)
+                .filter(|chord| chord.get_timestamp() == tick)
+            {
+                let gems = chord.get_keys().len() as u64;
+                note_score += NOTE_POINTS * gems * multiplier(streak, active.is_some());
+                streak += 1;
+                if chord.get_sustain() > 0 {
+                    sustains.push((chord.get_end_timestamp(), gems, self.in_phrase[next_chord]));
+                }
+                if self.completes_phrase[next_chord] {
+                    bar = (bar + PHRASE_STAR_POWER).min(1.0);
+                }
+                next_chord += 1;
+            }
+
+            let current = multiplier(streak, active.is_some());
+            for (_, gems, whammy) in &sustains {
+                sustain_scaled += SUSTAIN_POINTS_PER_BEAT * gems * current;
+                if *whammy {
+                    bar += 1.0 / (WHAMMY_BEATS_PER_BAR * f64::from(resolution));
+                }
+            }
+            bar = bar.min(1.0);
+            if let Some((start, start_bar)) = active {
+                bar -=
+                    1.0 / (DRAIN_MEASURES * f64::from(self.tempo_map.ticks_per_measure_at(tick)));
+                if bar <= EPSILON {
+                    bar = 0.0;
+                    active = None;
+                    activations.push(Activation {
+                        start,
+                        end: tick + 1,
+                        bar: start_bar,
+                    });
+                }
+            }
+            tick += 1;
+            sustains.retain(|(end, _, _)| *end > tick);
+
+            if sustains.is_empty() && active.is_none() {
+                let next = [
+                    self.chords.get(next_chord).map(Chord::get_timestamp),
+                    requests.get(next_request).copied(),
+                ]
+                .into_iter()
+                .flatten()
+                .min();
+                match next {
+                    Some(next) => tick = tick.max(next),
+                    None => break,
+                }
+            }
+        }
+        Simulation {
+            note_score,
+            sustain_score: (sustain_scaled + u64::from(resolution) / 2) / u64::from(resolution),
+            activations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::scoring_track;
+
+    use super::*;
+
+    #[test]
+    fn scoring() {
+        let notes: Vec<u32> = (0..20).map(|i| i * 96).collect();
+        let track = scoring_track(&notes, 192, &[(0, 192), (192, 192)]);
+        assert_eq!(track.base_score(), 1025);
+        assert_eq!(track.star_thresholds(), [103, 513, 1025, 2050, 2870, 3690]);
+        assert_eq!(track.simulate(&ActivationStrategy::Never).get_score(), 1575);
+
+        let earliest = track.simulate(&ActivationStrategy::Earliest);
+        assert_eq!(earliest.get_note_score(), 2800);
+        assert_eq!(earliest.get_sustain_score(), 150);
+        assert_eq!(earliest.get_activations().len(), 1);
+        assert_eq!(earliest.get_activations()[0].get_start(), 384);
+        assert_eq!(earliest.get_activations()[0].get_end(), 3456);
+
+        let late = track.simulate(&ActivationStrategy::At(vec![100, 960]));
+        assert_eq!(late.get_activations()[0].get_start(), 960);
+        assert_eq!(late.get_score(), 2000 + 500 + 150);
+    }
+}
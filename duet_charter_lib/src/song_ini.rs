@@ -0,0 +1,534 @@
+use std::fmt::{Display, Formatter};
+
+use eyre::{eyre, Result};
+
+use crate::chart::Chart;
+use crate::difficulty::chart_difficulties;
+
+/// How a value linked between `song.ini` and the `[Song]` block of a chart is written on both
+/// sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    /// Plain text in `song.ini`, quoted in the chart.
+    Text,
+    /// Plain text in `song.ini`, quoted with a leading `, ` in the chart, as editors write it.
+    Year,
+    /// Milliseconds in `song.ini`, seconds in the chart.
+    Millis,
+}
+
+impl FieldKind {
+    /// The value of a chart property in the form `song.ini` uses, if it has one.
+    #[allow(clippy::cast_possible_truncation)]
+    fn parse_chart(self, value: &str) -> Option<String> {
+        let text = value.trim().trim_matches('"').trim();
+        match self {
+            Self::Text => Some(text.to_string()),
+            Self::Year => Some(text.trim_start_matches(',').trim().to_string()),
+            Self::Millis => text
+                .parse::<f64>()
+                .ok()
+                .map(|seconds| ((seconds * 1000.0).round() as i64).to_string()),
+        }
+        .filter(|value| !value.is_empty())
+    }
+
+    /// A `song.ini` value as the chart writes it, if it has one.
+    #[allow(clippy::cast_possible_truncation)]
+    fn parse_ini(self, value: &str) -> Option<String> {
+        let text = value.trim();
+        match self {
+            Self::Text | Self::Year => Some(text.to_string()),
+            Self::Millis => text
+                .parse::<f64>()
+                .ok()
+                .map(|millis| (millis.round() as i64).to_string()),
+        }
+        .filter(|value| !value.is_empty())
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn format_chart(self, value: &str) -> String {
+        match self {
+            Self::Text => format!("\"{}\"", value),
+            Self::Year => format!("\", {}\"", value),
+            Self::Millis => (value.parse::<i64>().unwrap_or_default() as f64 / 1000.0).to_string(),
+        }
+    }
+}
+
+/// `song.ini` keys with the `[Song]` property holding the same information.
+const LINKED_FIELDS: [(&str, &str, FieldKind); 8] = [
+    ("name", "Name", FieldKind::Text),
+    ("artist", "Artist", FieldKind::Text),
+    ("charter", "Charter", FieldKind::Text),
+    ("album", "Album", FieldKind::Text),
+    ("year", "Year", FieldKind::Year),
+    ("genre", "Genre", FieldKind::Text),
+    ("delay", "Offset", FieldKind::Millis),
+    ("preview_start_time", "PreviewStart", FieldKind::Millis),
+];
+
+/// Expert tracks with the `song.ini` key of their difficulty.
+const DIFFICULTY_FIELDS: [(&str, &str); 8] = [
+    ("ExpertSingle", "diff_guitar"),
+    ("ExpertDoubleGuitar", "diff_guitar_coop"),
+    ("ExpertDoubleBass", "diff_bass"),
+    ("ExpertDoubleRhythm", "diff_rhythm"),
+    ("ExpertKeyboard", "diff_keys"),
+    ("ExpertDrums", "diff_drums"),
+    ("ExpertGHLGuitar", "diff_guitarghl"),
+    ("ExpertGHLBass", "diff_bassghl"),
+];
+
+/// Which side wins when `song.ini` and the chart disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertySource {
+    Chart,
+    SongIni,
+}
+
+/// A value `song.ini` and the `[Song]` block of a chart disagree on, as each side writes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyConflict {
+    key: String,
+    property: String,
+    ini_value: String,
+    chart_value: String,
+}
+
+impl PropertyConflict {
+    /// The `song.ini` key, such as `delay`.
+    #[must_use]
+    pub fn get_key(&self) -> &str {
+        &self.key
+    }
+
+    /// The `[Song]` property, such as `Offset`.
+    #[must_use]
+    pub fn get_property(&self) -> &str {
+        &self.property
+    }
+
+    #[must_use]
+    pub fn get_ini_value(&self) -> &str {
+        &self.ini_value
+    }
+
+    #[must_use]
+    pub fn get_chart_value(&self) -> &str {
+        &self.chart_value
+    }
+}
+
+impl Display for PropertyConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "song.ini {} = {}, [Song] {} = {}",
+            self.key, self.ini_value, self.property, self.chart_value
+        )
+    }
+}
+
+/// A line of a `song.ini` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum IniLine {
+    /// A `key = value` pair of the `[song]` section, with the line as written.
+    Entry {
+        key: String,
+        value: String,
+        line: String,
+    },
+    /// Any other line, such as a comment, a section header or a line of another section.
+    Other(String),
+}
+
+fn is_section_header(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('[') && line.ends_with(']')
+}
+
+/// A `song.ini` file. Only the `[song]` section is read, every other line is kept as it is
+/// and written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongIni {
+    lines: Vec<IniLine>,
+    /// Index of the `[song]` header in `lines`.
+    header: usize,
+    bom: bool,
+    crlf: bool,
+}
+
+impl Default for SongIni {
+    fn default() -> Self {
+        Self {
+            lines: vec![IniLine::Other("[song]".to_string())],
+            header: 0,
+            bom: false,
+            crlf: true,
+        }
+    }
+}
+
+impl SongIni {
+    /// Constructor for `SongIni` from the contents of a `song.ini` file.
+    ///
+    /// Comments, blank lines and other sections are kept, so writing the file back only
+    /// changes the keys that were set or removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ini_file`: the contents of the file.
+    ///
+    /// returns: `Result<SongIni>`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no `[song]` section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::song_ini::SongIni;
+    ///
+    /// let mut ini = SongIni::new("[Song]\nname=Second Sight\n; by hand\ndelay = 0\n").unwrap();
+    /// assert_eq!(ini.get("Name"), Some("Second Sight"));
+    /// ini.set("delay", "20".to_string());
+    /// assert_eq!(ini.to_string(), "[Song]\nname=Second Sight\n; by hand\ndelay = 20\n");
+    /// ```
+    pub fn new(ini_file: &str) -> Result<Self> {
+        let text = ini_file.strip_prefix('\u{feff}');
+        let bom = text.is_some();
+        let text = text.unwrap_or(ini_file);
+        let mut lines = vec![];
+        let mut header = None;
+        let mut in_song = false;
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if is_section_header(trimmed) {
+                in_song = header.is_none()
+                    && trimmed[1..trimmed.len() - 1]
+                        .trim()
+                        .eq_ignore_ascii_case("song");
+                if in_song {
+                    header = Some(lines.len());
+                }
+                lines.push(IniLine::Other(line.to_string()));
+                continue;
+            }
+            let entry = trimmed
+                .split_once('=')
+                .filter(|_| in_song && !trimmed.starts_with([';', '#']));
+            lines.push(match entry {
+                Some((key, value)) => IniLine::Entry {
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                    line: line.to_string(),
+                },
+                None => IniLine::Other(line.to_string()),
+            });
+        }
+        Ok(Self {
+            lines,
+            header: header.ok_or_else(|| eyre!("song.ini has no [song] section"))?,
+            bom,
+            crlf: ini_file.contains("\r\n") || !ini_file.contains('\n'),
+        })
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, &str, &str)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| match line {
+                IniLine::Entry { key, value, .. } => Some((index, key.as_str(), value.as_str())),
+                IniLine::Other(_) => None,
+            })
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries()
+            .find(|(_, name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(index, _, _)| index)
+    }
+
+    /// The value of a key of the `[song]` section, ignoring case as games do.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .find(|(_, name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, _, value)| value)
+    }
+
+    /// The keys and values of the `[song]` section, in the order they are written.
+    #[must_use]
+    pub fn get_entries(&self) -> Vec<(&str, &str)> {
+        self.entries().map(|(_, key, value)| (key, value)).collect()
+    }
+
+    /// Sets the value of a key, keeping its place if it exists and adding it after the last key
+    /// of the `[song]` section otherwise, returning the value it replaces.
+    pub fn set(&mut self, key: &str, value: String) -> Option<String> {
+        let line = format!("{} = {}", key, value);
+        if let Some(index) = self.position(key) {
+            let IniLine::Entry {
+                value: old,
+                line: old_line,
+                ..
+            } = &mut self.lines[index]
+            else {
+                return None;
+            };
+            *old_line = line;
+            return Some(std::mem::replace(old, value));
+        }
+        let index = self
+            .entries()
+            .last()
+            .map_or(self.header, |(index, _, _)| index)
+            + 1;
+        self.lines.insert(
+            index,
+            IniLine::Entry {
+                key: key.to_string(),
+                value,
+                line,
+            },
+        );
+        None
+    }
+
+    /// Removes a key, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.position(key)?;
+        match self.lines.remove(index) {
+            IniLine::Entry { value, .. } => Some(value),
+            IniLine::Other(_) => None,
+        }
+    }
+
+    /// Merges the metadata of `song.ini` with the `[Song]` block of a chart.
+    ///
+    /// A value only one side has is copied to the other. Values both sides have are compared
+    /// after converting units, so `delay = 250` matches `Offset = 0.25`. Where they differ a
+    /// conflict is returned, and the side in `prefer` overwrites the other; without it both
+    /// keep their value.
+    ///
+    /// # Arguments
+    ///
+    /// * `chart`: the chart of the song folder.
+    /// * `prefer`: the side that wins conflicts, if any.
+    ///
+    /// returns: the values the two sides disagreed on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::song_ini::{PropertySource, SongIni};
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// let mut ini = SongIni::new("[song]\ndelay = 120\nloading_phrase = Hi\n").unwrap();
+    /// let conflicts = ini.sync_with_chart(&mut chart, Some(PropertySource::SongIni));
+    /// assert_eq!(conflicts[0].to_string(), "song.ini delay = 120, [Song] Offset = 0");
+    /// assert_eq!(chart.get_properties()["Offset"], "0.12");
+    /// assert_eq!(ini.get("name"), Some("Second Sight"));
+    /// ```
+    pub fn sync_with_chart(
+        &mut self,
+        chart: &mut Chart,
+        prefer: Option<PropertySource>,
+    ) -> Vec<PropertyConflict> {
+        let mut conflicts = vec![];
+        for (key, property, kind) in LINKED_FIELDS {
+            let ini_value = self.get(key).and_then(|value| kind.parse_ini(value));
+            let chart_value = chart
+                .get_properties()
+                .get(property)
+                .and_then(|value| kind.parse_chart(value));
+            let source = match (&ini_value, &chart_value) {
+                (Some(ini_value), Some(chart_value)) if ini_value != chart_value => {
+                    conflicts.push(PropertyConflict {
+                        key: key.to_string(),
+                        property: property.to_string(),
+                        ini_value: self.get(key).unwrap_or_default().to_string(),
+                        chart_value: chart.get_properties()[property].clone(),
+                    });
+                    prefer
+                }
+                (Some(_), None) => Some(PropertySource::SongIni),
+                (None, Some(_)) => Some(PropertySource::Chart),
+                _ => None,
+            };
+            match (source, ini_value, chart_value) {
+                (Some(PropertySource::SongIni), Some(value), _) => {
+                    chart.set_property(property, kind.format_chart(&value));
+                }
+                (Some(PropertySource::Chart), _, Some(value)) => {
+                    self.set(key, value);
+                }
+                _ => {}
+            }
+        }
+        conflicts
+    }
+
+    /// Sets the `diff_*` keys of the Expert tracks of a chart to their estimated difficulty
+    /// tier, and to `-1` for tracks the chart lacks whose key is present.
+    ///
+    /// # Arguments
+    ///
+    /// * `chart`: the chart of the song folder.
+    pub fn update_difficulties(&mut self, chart: &Chart) {
+        let difficulties = chart_difficulties(chart);
+        for (track, key) in DIFFICULTY_FIELDS {
+            match difficulties
+                .iter()
+                .find(|track_difficulty| track_difficulty.get_name() == track)
+            {
+                Some(difficulty) => {
+                    self.set(key, difficulty.get_tier().to_string());
+                }
+                None => {
+                    if self.get(key).is_some() {
+                        self.set(key, "-1".to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Display for SongIni {
+    /// Serialises the file back into the `song.ini` format, with its original line endings.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.bom {
+            write!(f, "\u{feff}")?;
+        }
+        let line_ending = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            match line {
+                IniLine::Entry { line, .. } | IniLine::Other(line) => {
+                    write!(f, "{}{}", line, line_ending)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::WrapErr;
+
+    use super::*;
+    use crate::chart::KeyPressEvent;
+
+    #[test]
+    fn parse_ini() -> Result<()> {
+        let file = "\u{feff}; made by hand\r\n[Song]\r\nName = Sleeper\r\nicon=ohm\r\n\r\n[other]\r\nx = 1\r\n";
+        let mut ini = SongIni::new(file)?;
+        assert_eq!(
+            ini.get_entries(),
+            vec![("Name", "Sleeper"), ("icon", "ohm")]
+        );
+        assert_eq!(ini.get("x"), None);
+        assert_eq!(ini.to_string(), file);
+
+        ini.set("delay", "100".to_string());
+        ini.remove("name");
+        assert_eq!(
+            ini.to_string(),
+            "\u{feff}; made by hand\r\n[Song]\r\nicon=ohm\r\ndelay = 100\r\n\r\n[other]\r\nx = 1\r\n"
+        );
+        assert!(SongIni::new("name = x").is_err());
+        assert_eq!(SongIni::default().to_string(), "[song]\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn sync_properties() -> Result<()> {
+        let mut chart = Chart::from_parts(
+            HashMap::from([
+                ("Name".to_string(), "\"Sleeper\"".to_string()),
+                ("Year".to_string(), "\", 2013\"".to_string()),
+                ("Offset".to_string(), "0.25".to_string()),
+                ("PreviewStart".to_string(), "30".to_string()),
+            ]),
+            vec![],
+            vec![],
+            HashMap::from([(
+                "ExpertSingle".to_string(),
+                vec![KeyPressEvent::Note {
+                    timestamp: 0,
+                    duration: 0,
+                    key: 0,
+                }],
+            )]),
+        );
+        let mut ini = SongIni::new(
+            "[song]\nname = Sleeper\nyear = 2013\ndelay = 250\npreview_start_time = 20000\nartist = After the Burial\ndiff_bass = 3\n",
+        )?;
+        let conflicts = ini.sync_with_chart(&mut chart, None);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].get_property(), "PreviewStart");
+        assert_eq!(chart.get_properties()["PreviewStart"], "30");
+        assert_eq!(ini.get("preview_start_time"), Some("20000"));
+        assert_eq!(chart.get_properties()["Artist"], "\"After the Burial\"");
+
+        ini.sync_with_chart(&mut chart, Some(PropertySource::Chart));
+        assert_eq!(ini.get("preview_start_time"), Some("30000"));
+        assert!(ini.sync_with_chart(&mut chart, None).is_empty());
+
+        ini.update_difficulties(&chart);
+        assert!(ini.get("diff_guitar").is_some());
+        assert_eq!(ini.get("diff_bass"), Some("-1"));
+        assert_eq!(ini.get("diff_drums"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn ini_from_charts() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            ini_from_charts_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn ini_from_charts_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let mut chart = Chart::new(&file_content)?;
+        let original = chart.clone();
+
+        let mut ini = SongIni::default();
+        assert!(ini.sync_with_chart(&mut chart, None).is_empty());
+        assert_eq!(chart, original);
+        let mut ini = SongIni::new(&ini.to_string())?;
+        assert!(ini.sync_with_chart(&mut chart, None).is_empty());
+        assert_eq!(chart, original);
+        Ok(())
+    }
+}
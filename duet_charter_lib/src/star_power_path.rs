@@ -0,0 +1,843 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+use crate::chart::Chart;
+use crate::notes::{Chord, Instrument};
+use crate::scoring::{
+    ScoringTrack, DRAIN_MEASURES, EPSILON, MAX_MULTIPLIER, MIN_ACTIVATION, NOTES_PER_MULTIPLIER,
+    NOTE_POINTS, PHRASE_STAR_POWER, SUSTAIN_POINTS_PER_BEAT, WHAMMY_BEATS_PER_BAR,
+};
+use crate::timing::TempoMap;
+
+/// Microseconds a note can be hit before its timestamp, following Clone Hero's hit window.
+pub const DEFAULT_HIT_WINDOW_MICROS: u64 = 70_000;
+
+/// A stretch of a track between two ticks where a chord starts, a sustain ends or the time
+/// signature changes, so sustain points, whammy gain and drain are constant within it.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: u32,
+    end: u32,
+    /// Measures from the start of the chart to the start of the segment.
+    measure: f64,
+    chord: Option<usize>,
+    /// Sustain points per tick without star power, times the resolution.
+    sustain_rate: u64,
+    /// Share of the bar gained per tick by whammying star power sustains.
+    whammy_rate: f64,
+    /// Share of the bar drained per tick while star power is active.
+    drain_rate: f64,
+}
+
+/// The outcome of activating star power at a chord, before the points are rounded.
+#[derive(Debug, Clone, Copy)]
+struct Walk {
+    bar: f64,
+    end: u32,
+    next_chord: usize,
+    squeezed: usize,
+    note_points: u64,
+    /// Extra sustain points, times the resolution.
+    sustain_points: u64,
+}
+
+/// The most extra points from each state, and the chord to activate star power before to
+/// earn them.
+type Memo = HashMap<(usize, usize), (u64, Option<usize>)>;
+
+/// Searches the activations of a track that earn the most points.
+///
+/// The search runs over states where the bar is empty: the start of the track and the end of
+/// every activation. A state is named by the first chord after the bar ran out and the first
+/// chord that is not doubled yet, which differ by the squeezed chords. From each state, star
+/// power may be activated right before any chord the bar is half full at, up to the first chord
+/// that would complete a phrase while the bar is already full. Activations are played out to
+/// the tick the bar runs out at, counting phrases completed and sustains whammied along the
+/// way, and the best continuation of every state is remembered.
+///
+/// A state fills the bar from the first chord after the bar ran out, leaving out whammy between
+/// the end of the activation and that chord. That only makes the bar lower than it is, so the
+/// chosen path is played out again exactly afterwards.
+struct PathFinder<'a> {
+    track: &'a ScoringTrack,
+    hit_window_micros: u64,
+    segments: Vec<Segment>,
+    chord_segments: Vec<usize>,
+    /// The segments a phrase is completed at or star power sustains are whammied in.
+    gain_segments: Vec<usize>,
+    /// The points of the chords before each chord without star power.
+    value_prefix: Vec<u64>,
+    /// The sustain points of the segments before each segment without star power, times the
+    /// resolution.
+    sustain_prefix: Vec<u64>,
+    chord_micros: Vec<u64>,
+}
+
+impl<'a> PathFinder<'a> {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(track: &'a ScoringTrack, hit_window_micros: u64) -> Self {
+        let chords = track.get_chords();
+        let tempo_map = track.get_tempo_map();
+        let resolution = f64::from(tempo_map.get_resolution());
+        let multiplier =
+            |streak: usize| (1 + streak as u64 / NOTES_PER_MULTIPLIER).min(MAX_MULTIPLIER);
+        let mut value_prefix = vec![0];
+        for (i, chord) in chords.iter().enumerate() {
+            value_prefix.push(
+                value_prefix[i] + NOTE_POINTS * chord.get_keys().len() as u64 * multiplier(i),
+            );
+        }
+
+        let mut breakpoints: Vec<u32> = chords
+            .iter()
+            .flat_map(|chord| [chord.get_timestamp(), chord.get_end_timestamp()])
+            .chain(tempo_map.time_signature_timestamps())
+            .filter(|tick| {
+                chords
+                    .first()
+                    .is_some_and(|first| *tick >= first.get_timestamp())
+            })
+            .collect();
+        breakpoints.sort_unstable();
+        breakpoints.dedup();
+
+        let mut segments = Vec::with_capacity(breakpoints.len());
+        let mut chord_segments = Vec::with_capacity(chords.len());
+        // (end tick, gems, whammy gains star power)
+        let mut sustains: Vec<(u32, u64, bool)> = vec![];
+        let mut next_chord = 0;
+        for (i, start) in breakpoints.iter().copied().enumerate() {
+            sustains.retain(|(end, _, _)| *end > start);
+            let chord = chords
+                .get(next_chord)
+                .filter(|chord| chord.get_timestamp() == start)
+                .map(|chord| {
+                    if chord.get_sustain() > 0 {
+                        sustains.push((
+                            chord.get_end_timestamp(),
+                            chord.get_keys().len() as u64,
+                            track.is_in_phrase(next_chord),
+                        ));
+                    }
+                    chord_segments.push(segments.len());
+                    next_chord += 1;
+                    next_chord - 1
+                });
+            let current = multiplier(next_chord);
+            let whammies = sustains.iter().filter(|(_, _, whammy)| *whammy).count();
+            segments.push(Segment {
+                start,
+                end: breakpoints.get(i + 1).copied().unwrap_or(u32::MAX),
+                measure: tempo_map.measure_at(start),
+                chord,
+                sustain_rate: sustains
+                    .iter()
+                    .map(|(_, gems, _)| SUSTAIN_POINTS_PER_BEAT * gems * current)
+                    .sum(),
+                whammy_rate: whammies as f64 / (WHAMMY_BEATS_PER_BAR * resolution),
+                drain_rate: 1.0
+                    / (DRAIN_MEASURES * f64::from(tempo_map.ticks_per_measure_at(start))),
+            });
+        }
+        let gain_segments = segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| {
+                segment.whammy_rate > 0.0
+                    || segment
+                        .chord
+                        .is_some_and(|chord| track.completes_phrase(chord))
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let mut sustain_prefix = vec![0];
+        for (i, segment) in segments.iter().enumerate() {
+            sustain_prefix.push(
+                sustain_prefix[i] + segment.sustain_rate * u64::from(segment.end - segment.start),
+            );
+        }
+        Self {
+            track,
+            hit_window_micros,
+            segments,
+            chord_segments,
+            gain_segments,
+            value_prefix,
+            sustain_prefix,
+            chord_micros: chords
+                .iter()
+                .map(|chord| tempo_map.ticks_to_micros(chord.get_timestamp()))
+                .collect(),
+        }
+    }
+
+    /// The index of the segment holding a tick, or of the first segment for ticks before it.
+    fn segment_at(&self, tick: u32) -> usize {
+        self.segments
+            .partition_point(|segment| segment.start <= tick)
+            .saturating_sub(1)
+    }
+
+    /// The chords from `first` on that star power can be activated before when the bar is
+    /// empty at `from`, with the bar at each of them.
+    fn candidates(&self, from: u32, first: usize) -> Vec<(usize, f64)> {
+        let last_gain = self.gain_segments.last().copied().unwrap_or(0);
+        let mut bar: f64 = 0.0;
+        let mut candidates = vec![];
+        for (index, segment) in self.segments.iter().enumerate().skip(self.segment_at(from)) {
+            if index > last_gain && bar < MIN_ACTIVATION - EPSILON {
+                break;
+            }
+            if let Some(chord) = segment.chord.filter(|_| segment.start >= from) {
+                if chord >= first && bar >= MIN_ACTIVATION - EPSILON {
+                    candidates.push((chord, bar));
+                }
+                if self.track.completes_phrase(chord) {
+                    if chord >= first && bar >= 1.0 - EPSILON {
+                        break;
+                    }
+                    bar = (bar + PHRASE_STAR_POWER).min(1.0);
+                }
+            }
+            if segment.end == u32::MAX {
+                break;
+            }
+            bar = (bar + segment.whammy_rate * f64::from(segment.end - segment.start.max(from)))
+                .min(1.0);
+        }
+        candidates
+    }
+
+    /// The bar right before a chord when it is empty at `from` and star power is not activated
+    /// in between.
+    fn bar_before(&self, from: u32, chord: usize) -> f64 {
+        let mut bar: f64 = 0.0;
+        for segment in &self.segments[self.segment_at(from)..self.chord_segments[chord]] {
+            if segment
+                .chord
+                .is_some_and(|chord| segment.start >= from && self.track.completes_phrase(chord))
+            {
+                bar = (bar + PHRASE_STAR_POWER).min(1.0);
+            }
+            bar = (bar + segment.whammy_rate * f64::from(segment.end - segment.start.max(from)))
+                .min(1.0);
+        }
+        bar
+    }
+
+    /// The tick within a segment the bar runs out at while draining from `bar`, if it does.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn run_out(segment: &Segment, bar: f64) -> Option<u32> {
+        let net = segment.whammy_rate - segment.drain_rate;
+        let ticks = segment.end - segment.start;
+        (bar + net * f64::from(ticks) <= EPSILON)
+            .then(|| segment.start + (((bar - EPSILON) / -net).ceil() as u32).clamp(1, ticks))
+    }
+
+    /// Plays out star power activated right before a chord with the given bar.
+    ///
+    /// Between two segments that add to the bar it only drains, by an eighth per measure, so
+    /// the walk jumps from one such segment to the next and sums points with prefix sums.
+    fn activate(&self, chord: usize, bar: f64) -> Walk {
+        let bar_at_start = bar;
+        let start_segment = self.chord_segments[chord];
+        let mut index = start_segment;
+        let mut bar = bar;
+        let (end_segment, end) = loop {
+            let segment = &self.segments[index];
+            if segment
+                .chord
+                .is_some_and(|chord| self.track.completes_phrase(chord))
+            {
+                bar = (bar + PHRASE_STAR_POWER).min(1.0);
+            }
+            if segment.whammy_rate > 0.0 {
+                if let Some(end) = Self::run_out(segment, bar) {
+                    break (index, end);
+                }
+                bar = (bar
+                    + (segment.whammy_rate - segment.drain_rate)
+                        * f64::from(segment.end - segment.start))
+                .min(1.0);
+                index += 1;
+                continue;
+            }
+            let next_gain = self
+                .gain_segments
+                .get(self.gain_segments.partition_point(|gain| *gain <= index))
+                .copied();
+            if let Some(next_gain) = next_gain {
+                let drained = (self.segments[next_gain].measure - segment.measure) / DRAIN_MEASURES;
+                if bar - drained > EPSILON {
+                    bar -= drained;
+                    index = next_gain;
+                    continue;
+                }
+            }
+            let run_out_measure = segment.measure + (bar - EPSILON) * DRAIN_MEASURES;
+            let last = next_gain.unwrap_or(self.segments.len());
+            let within = index
+                + self.segments[index..last]
+                    .partition_point(|later| later.measure < run_out_measure)
+                    .max(1)
+                - 1;
+            let within_segment = &self.segments[within];
+            let within_bar = bar - (within_segment.measure - segment.measure) / DRAIN_MEASURES;
+            let end = Self::run_out(within_segment, within_bar).unwrap_or(within_segment.end);
+            break (within, end);
+        };
+
+        let chords = self.track.get_chords();
+        let mut next_chord =
+            chord + chords[chord..].partition_point(|chord| chord.get_timestamp() < end);
+        let last_active = self
+            .track
+            .get_tempo_map()
+            .ticks_to_micros(end.saturating_sub(1));
+        let mut squeezed = 0;
+        while self
+            .chord_micros
+            .get(next_chord)
+            .is_some_and(|micros| *micros <= last_active + self.hit_window_micros)
+        {
+            squeezed += 1;
+            next_chord += 1;
+        }
+        Walk {
+            bar: bar_at_start,
+            end,
+            next_chord,
+            squeezed,
+            note_points: self.value_prefix[next_chord] - self.value_prefix[chord],
+            sustain_points: self.sustain_prefix[end_segment] - self.sustain_prefix[start_segment]
+                + self.segments[end_segment].sustain_rate
+                    * u64::from(end - self.segments[end_segment].start),
+        }
+    }
+
+    /// The most extra points, times the resolution, earned by activations from the state
+    /// where `after` is the first chord after the bar ran out and `first` the first chord not
+    /// doubled yet.
+    fn best(&self, after: usize, first: usize, memo: &mut Memo) -> u64 {
+        if let Some((points, _)) = memo.get(&(after, first)) {
+            return *points;
+        }
+        let resolution = u64::from(self.track.get_tempo_map().get_resolution());
+        let from = self
+            .track
+            .get_chords()
+            .get(after)
+            .map_or(u32::MAX, Chord::get_timestamp);
+        let mut best = (0, None);
+        for (chord, bar) in self.candidates(from, first) {
+            let walk = self.activate(chord, bar);
+            let points = walk.note_points * resolution
+                + walk.sustain_points
+                + self.best(walk.next_chord - walk.squeezed, walk.next_chord, memo);
+            if points > best.0 {
+                best = (points, Some(chord));
+            }
+        }
+        memo.insert((after, first), best);
+        best.0
+    }
+}
+
+/// A stretch of time star power is active on an optimal path.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PathActivation {
+    start: u32,
+    end: u32,
+    squeezed_end: u32,
+    start_measure: f64,
+    end_measure: f64,
+    phrases: usize,
+    bar: f64,
+    squeezed: usize,
+    points: u64,
+}
+
+impl PathActivation {
+    /// The tick of the first chord played with star power.
+    #[must_use]
+    pub const fn get_start(&self) -> u32 {
+        self.start
+    }
+
+    /// The tick the bar runs out at.
+    #[must_use]
+    pub const fn get_end(&self) -> u32 {
+        self.end
+    }
+
+    /// The tick right after the last squeezed chord, or the end when no chord is squeezed.
+    #[must_use]
+    pub const fn get_squeezed_end(&self) -> u32 {
+        self.squeezed_end
+    }
+
+    /// The measure star power is activated in, counting from 1 with the fraction of the measure.
+    #[must_use]
+    pub const fn get_start_measure(&self) -> f64 {
+        self.start_measure
+    }
+
+    /// The measure the bar runs out in, counting from 1 with the fraction of the measure.
+    #[must_use]
+    pub const fn get_end_measure(&self) -> f64 {
+        self.end_measure
+    }
+
+    /// The star power phrases completed since the bar was last empty.
+    #[must_use]
+    pub const fn get_phrases(&self) -> usize {
+        self.phrases
+    }
+
+    /// The share of the bar that is filled at activation, between 0.5 and 1.
+    #[must_use]
+    pub const fn get_bar(&self) -> f64 {
+        self.bar
+    }
+
+    /// The chords after the bar runs out that are hit early to still be doubled.
+    #[must_use]
+    pub const fn get_squeezed(&self) -> usize {
+        self.squeezed
+    }
+
+    /// The points the activation adds to the score.
+    #[must_use]
+    pub const fn get_points(&self) -> u64 {
+        self.points
+    }
+}
+
+impl Display for PathActivation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "activate at measure {:.1}, {} phrase{} ({:.0}% bar), until measure {:.1}",
+            self.start_measure,
+            self.phrases,
+            if self.phrases == 1 { "" } else { "s" },
+            self.bar * 100.0,
+            self.end_measure
+        )?;
+        if self.squeezed > 0 {
+            write!(
+                f,
+                ", squeezing {} note{}",
+                self.squeezed,
+                if self.squeezed == 1 { "" } else { "s" }
+            )?;
+        }
+        write!(f, ", +{} points", self.points)
+    }
+}
+
+/// The activations of star power that give a track its highest score, in the manner of CHOpt.
+///
+/// Activations are placed right before a chord. Phrases completed and star power sustains
+/// whammied while star power is active extend it, so activations can run into each other, and
+/// chords just after the bar runs out are squeezed in by hitting them early within the hit
+/// window. The score follows the rules of `ScoringTrack`, apart from the squeezes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StarPowerPath {
+    name: String,
+    activations: Vec<PathActivation>,
+    no_star_power_score: u64,
+    score: u64,
+}
+
+impl StarPowerPath {
+    /// Constructor for `StarPowerPath`, searching the best activations of a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track`: the track to play.
+    /// * `hit_window_micros`: how early a note can be hit to squeeze it into an activation, or
+    ///   0 to never squeeze.
+    ///
+    /// returns: `StarPowerPath`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::chart::{KeyPressEvent, TempoEvent};
+    /// use duet_charter_lib::scoring::ScoringTrack;
+    /// use duet_charter_lib::star_power_path::StarPowerPath;
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let tempo_map = TempoMap::new(&[TempoEvent::Beat { timestamp: 0, milli_bpm: 120_000 }], 192);
+    /// let mut events: Vec<KeyPressEvent> = (0..8)
+    ///     .map(|i| KeyPressEvent::Note { timestamp: i * 192, duration: 0, key: 0 })
+    ///     .collect();
+    /// events.push(KeyPressEvent::Special { timestamp: 0, special_type: 2, duration: 192 });
+    /// events.push(KeyPressEvent::Special { timestamp: 192, special_type: 2, duration: 192 });
+    /// let path = StarPowerPath::new(&ScoringTrack::new("ExpertSingle", &events, &tempo_map), 0);
+    /// assert_eq!(path.get_activations()[0].get_start(), 384);
+    /// assert_eq!(path.get_score(), 400 + 300);
+    /// ```
+    #[must_use]
+    pub fn new(track: &ScoringTrack, hit_window_micros: u64) -> Self {
+        let finder = PathFinder::new(track, hit_window_micros);
+        let tempo_map = track.get_tempo_map();
+        let resolution = u64::from(tempo_map.get_resolution());
+        let chords = track.get_chords();
+        let mut memo = Memo::new();
+
+        let mut activations = vec![];
+        let mut note_points: u64 = finder.value_prefix.last().copied().unwrap_or(0);
+        let mut sustain_points: u64 = finder.sustain_prefix.last().copied().unwrap_or(0);
+        let no_star_power_score = note_points + (sustain_points + resolution / 2) / resolution;
+        let (mut from, mut after, mut first) = (0, 0, 0);
+        loop {
+            finder.best(after, first, &mut memo);
+            let Some(chord) = memo[&(after, first)].1 else {
+                break;
+            };
+            let walk = finder.activate(chord, finder.bar_before(from, chord));
+            let start = chords[chord].get_timestamp();
+            let phrases = (chords.partition_point(|chord| chord.get_timestamp() < from)..chord)
+                .filter(|chord| track.completes_phrase(*chord))
+                .count();
+            activations.push(PathActivation {
+                start,
+                end: walk.end,
+                squeezed_end: match walk.squeezed {
+                    0 => walk.end,
+                    _ => chords[walk.next_chord - 1].get_timestamp() + 1,
+                },
+                start_measure: tempo_map.measure_at(start) + 1.0,
+                end_measure: tempo_map.measure_at(walk.end) + 1.0,
+                phrases,
+                bar: walk.bar,
+                squeezed: walk.squeezed,
+                points: walk.note_points + (walk.sustain_points + resolution / 2) / resolution,
+            });
+            note_points += walk.note_points;
+            sustain_points += walk.sustain_points;
+            (from, after, first) = (walk.end, walk.next_chord - walk.squeezed, walk.next_chord);
+        }
+        Self {
+            name: track.get_name().to_string(),
+            activations,
+            no_star_power_score,
+            score: note_points + (sustain_points + resolution / 2) / resolution,
+        }
+    }
+
+    /// Constructor for `StarPowerPath` from a track of a chart with Clone Hero's hit window, if
+    /// the chart has a guitar track of that name.
+    #[must_use]
+    pub fn from_chart(chart: &Chart, name: &str) -> Option<Self> {
+        ScoringTrack::from_chart(chart, name)
+            .map(|track| Self::new(&track, DEFAULT_HIT_WINDOW_MICROS))
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn get_activations(&self) -> &Vec<PathActivation> {
+        &self.activations
+    }
+
+    /// The ticks star power is activated at, to simulate the path with
+    /// `ActivationStrategy::At`.
+    #[must_use]
+    pub fn activation_ticks(&self) -> Vec<u32> {
+        self.activations
+            .iter()
+            .map(PathActivation::get_start)
+            .collect()
+    }
+
+    /// The score of a full combo without activating star power.
+    #[must_use]
+    pub const fn get_no_star_power_score(&self) -> u64 {
+        self.no_star_power_score
+    }
+
+    /// The score of a full combo following the path.
+    #[must_use]
+    pub const fn get_score(&self) -> u64 {
+        self.score
+    }
+}
+
+impl Display for StarPowerPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} points, +{} over no star power",
+            self.name,
+            self.score,
+            self.score - self.no_star_power_score
+        )?;
+        for activation in &self.activations {
+            write!(f, "\n  {}", activation)?;
+        }
+        Ok(())
+    }
+}
+
+/// The optimal star power paths of every guitar track of a chart, squeezing notes within the
+/// given hit window, in the order the tracks are written to a chart file. Drum tracks are left
+/// out.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::star_power_path::{chart_paths, DEFAULT_HIT_WINDOW_MICROS};
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// for path in chart_paths(&Chart::new(&file_content).unwrap(), DEFAULT_HIT_WINDOW_MICROS) {
+///     println!("{}", path);
+/// }
+/// ```
+#[must_use]
+pub fn chart_paths(chart: &Chart, hit_window_micros: u64) -> Vec<StarPowerPath> {
+    let mut names: Vec<&String> = chart
+        .get_key_presses()
+        .keys()
+        .filter(|name| Instrument::from_track(name) != Instrument::Drums)
+        .collect();
+    names.sort_by_key(|name| Chart::track_order(name));
+    names
+        .into_iter()
+        .filter_map(|name| ScoringTrack::from_chart(chart, name))
+        .map(|track| StarPowerPath::new(&track, hit_window_micros))
+        .collect()
+}
+
+/// The best score and star thresholds of a guitar track.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackScore {
+    name: String,
+    base_score: u64,
+    no_star_power_score: u64,
+    max_score: u64,
+    star_thresholds: [u64; 6],
+}
+
+impl TrackScore {
+    /// Constructor for `TrackScore`, taking the max score from the optimal star power path
+    /// with Clone Hero's hit window, so it matches the score `StarPowerPath` reports.
+    #[must_use]
+    pub fn new(track: &ScoringTrack) -> Self {
+        let path = StarPowerPath::new(track, DEFAULT_HIT_WINDOW_MICROS);
+        Self {
+            name: track.get_name().to_string(),
+            base_score: track.base_score(),
+            no_star_power_score: path.get_no_star_power_score(),
+            max_score: path.get_score(),
+            star_thresholds: track.star_thresholds(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The score at a constant 1x multiplier without star power.
+    #[must_use]
+    pub const fn get_base_score(&self) -> u64 {
+        self.base_score
+    }
+
+    /// The score of a full combo without activating star power.
+    #[must_use]
+    pub const fn get_no_star_power_score(&self) -> u64 {
+        self.no_star_power_score
+    }
+
+    /// The score of a full combo following the optimal star power path.
+    #[must_use]
+    pub const fn get_max_score(&self) -> u64 {
+        self.max_score
+    }
+
+    /// The least score for one to six stars.
+    #[must_use]
+    pub const fn get_star_thresholds(&self) -> [u64; 6] {
+        self.star_thresholds
+    }
+}
+
+/// The scores of every guitar track of a chart, in the order the tracks are written to a chart
+/// file. Drum tracks are left out.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use std::io::Read;
+/// use duet_charter_lib::chart::Chart;
+/// use duet_charter_lib::star_power_path::chart_scores;
+///
+/// let mut file_content = String::new();
+/// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+///     .unwrap()
+///     .read_to_string(&mut file_content)
+///     .expect("file reading failed");
+///
+/// for score in chart_scores(&Chart::new(&file_content).unwrap()) {
+///     println!("{}: {} points, 5 stars at {}", score.get_name(), score.get_max_score(), score.get_star_thresholds()[4]);
+/// }
+/// ```
+#[must_use]
+pub fn chart_scores(chart: &Chart) -> Vec<TrackScore> {
+    let tempo_map = TempoMap::from_chart(chart);
+    let mut names: Vec<&String> = chart
+        .get_key_presses()
+        .keys()
+        .filter(|name| Instrument::from_track(name) != Instrument::Drums)
+        .collect();
+    names.sort_by_key(|name| Chart::track_order(name));
+    names
+        .into_iter()
+        .map(|name| {
+            TrackScore::new(&ScoringTrack::new(
+                name,
+                &chart.get_key_presses()[name],
+                &tempo_map,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::scoring::ActivationStrategy;
+    use crate::test_util::scoring_track;
+
+    use super::*;
+
+    fn track(notes: &[u32], sustain_last: u32) -> ScoringTrack {
+        scoring_track(notes, sustain_last, &[(0, 192), (192, 192)])
+    }
+
+    #[test]
+    fn path() {
+        let notes: Vec<u32> = (0..20).map(|i| i * 96).collect();
+        let track = track(&notes, 192);
+        let path = StarPowerPath::new(&track, 0);
+        assert_eq!(path.get_no_star_power_score(), 1575);
+        assert_eq!(path.get_score(), 2950);
+        assert_eq!(path.get_activations().len(), 1);
+        let activation = path.get_activations()[0];
+        assert_eq!(activation.get_start(), 384);
+        assert_eq!(activation.get_end(), 3456);
+        assert_eq!(activation.get_phrases(), 2);
+        assert!((activation.get_start_measure() - 1.5).abs() < 1e-9);
+        assert_eq!(
+            track
+                .simulate(&ActivationStrategy::At(path.activation_ticks()))
+                .get_score(),
+            path.get_score()
+        );
+    }
+
+    #[test]
+    fn squeeze() {
+        let track = track(&[0, 96, 192, 288, 384, 3460], 0);
+        let path = StarPowerPath::new(&track, 0);
+        assert_eq!(path.get_score(), 300 + 50);
+        assert_eq!(path.get_activations()[0].get_squeezed(), 0);
+
+        let path = StarPowerPath::new(&track, DEFAULT_HIT_WINDOW_MICROS);
+        assert_eq!(path.get_score(), 300 + 100);
+        let activation = path.get_activations()[0];
+        assert_eq!(activation.get_start(), 384);
+        assert_eq!(activation.get_squeezed(), 1);
+        assert_eq!(activation.get_squeezed_end(), 3461);
+        assert_eq!(
+            activation.to_string(),
+            "activate at measure 1.5, 2 phrases (50% bar), until measure 5.5, squeezing 1 note, +100 points"
+        );
+    }
+
+    /// A few charts only, as searching every chart takes minutes without optimisations.
+    const CHARTS: [&str; 3] = [
+        "Adagio - Second Sight [Peddy]",
+        "Blur - Star Shaped [ΩHM]",
+        "Queen - Stone Cold Crazy [Peddy]",
+    ];
+
+    fn read_chart(name: &str) -> Result<Chart> {
+        let mut file = fs::File::open(format!("../charts/{}/notes.chart", name))?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        Chart::new(&file_content)
+    }
+
+    #[test]
+    fn chart_paths_beat_earliest() -> Result<()> {
+        for name in CHARTS {
+            chart_paths_helper(name).wrap_err(format!("Error occurred for chart file {}", name))?;
+        }
+        Ok(())
+    }
+
+    fn chart_paths_helper(name: &str) -> Result<()> {
+        let chart = read_chart(name)?;
+        for path in chart_paths(&chart, DEFAULT_HIT_WINDOW_MICROS) {
+            let Some(track) = ScoringTrack::from_chart(&chart, path.get_name()) else {
+                continue;
+            };
+            assert_eq!(
+                path.get_no_star_power_score(),
+                track.simulate(&ActivationStrategy::Never).get_score()
+            );
+            let exact = StarPowerPath::new(&track, 0);
+            assert!(path.get_score() >= exact.get_score());
+            assert!(exact.get_score() >= track.simulate(&ActivationStrategy::Earliest).get_score());
+            let simulated = track
+                .simulate(&ActivationStrategy::At(exact.activation_ticks()))
+                .get_score();
+            assert!(exact.get_score().abs_diff(simulated) <= 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn chart_scoring() -> Result<()> {
+        for name in CHARTS {
+            chart_scoring_helper(name).wrap_err(format!("Error occurred for chart file {}", name))?;
+        }
+        Ok(())
+    }
+
+    fn chart_scoring_helper(name: &str) -> Result<()> {
+        let chart = read_chart(name)?;
+        for score in chart_scores(&chart) {
+            assert!(score.get_base_score() <= score.get_no_star_power_score());
+            assert!(score.get_no_star_power_score() <= score.get_max_score());
+            assert!(score.get_max_score() <= score.get_base_score() * 8);
+            let track = ScoringTrack::from_chart(&chart, score.get_name()).expect("track exists");
+            let earliest = track.simulate(&ActivationStrategy::Earliest).get_score();
+            assert!(earliest <= score.get_max_score());
+        }
+        Ok(())
+    }
+}
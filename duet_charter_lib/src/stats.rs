@@ -0,0 +1,377 @@
+use eyre::Result;
+use serde::Serialize;
+
+use crate::chart::{Chart, KeyPressEvent, LyricEvent};
+use crate::difficulty::peak_nps;
+use crate::notes::{chords, star_power_phrases, Instrument};
+use crate::phrases::LyricPhraseCollection;
+use crate::timing::TempoMap;
+use crate::TimestampedEvent;
+
+/// Note statistics of a single `key_presses` track.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackStats {
+    name: String,
+    notes: usize,
+    chords: usize,
+    sustains: usize,
+    sustain_ticks: u64,
+    sustain_seconds: f64,
+    star_power_phrases: usize,
+    average_nps: f64,
+    peak_nps: f64,
+}
+
+impl TrackStats {
+    /// Constructor for `TrackStats` from the events of a track.
+    ///
+    /// Notes sharing a tick count as one note, and as a chord if more than one gem is hit.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(name: &str, events: &[KeyPressEvent], tempo_map: &TempoMap) -> Self {
+        let chords = chords(Instrument::from_track(name), events);
+        let times: Vec<f64> = chords
+            .iter()
+            .map(|chord| tempo_map.ticks_to_micros_f64(chord.get_timestamp()))
+            .collect();
+        let sustained: Vec<_> = chords
+            .iter()
+            .filter(|chord| chord.get_sustain() > 0)
+            .collect();
+        let span = match (times.first(), times.last()) {
+            (Some(first), Some(last)) if last > first => (last - first) / 1_000_000.0,
+            _ => 0.0,
+        };
+        Self {
+            name: name.to_string(),
+            notes: chords.len(),
+            chords: chords.iter().filter(|chord| chord.is_chord()).count(),
+            sustains: sustained.len(),
+            sustain_ticks: sustained
+                .iter()
+                .map(|chord| u64::from(chord.get_sustain()))
+                .sum(),
+            sustain_seconds: sustained
+                .iter()
+                .map(|chord| {
+                    tempo_map.ticks_to_seconds(chord.get_end_timestamp())
+                        - tempo_map.ticks_to_seconds(chord.get_timestamp())
+                })
+                .sum(),
+            star_power_phrases: star_power_phrases(events).len(),
+            average_nps: if span > 0.0 {
+                chords.len() as f64 / span
+            } else {
+                0.0
+            },
+            peak_nps: peak_nps(&chords, tempo_map),
+        }
+    }
+
+    /// The track name, such as `ExpertSingle`.
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of notes, counting a chord as one note.
+    #[must_use]
+    pub const fn get_notes(&self) -> usize {
+        self.notes
+    }
+
+    /// Number of notes hitting more than one gem.
+    #[must_use]
+    pub const fn get_chords(&self) -> usize {
+        self.chords
+    }
+
+    /// Number of notes with a sustain.
+    #[must_use]
+    pub const fn get_sustains(&self) -> usize {
+        self.sustains
+    }
+
+    #[must_use]
+    pub const fn get_sustain_ticks(&self) -> u64 {
+        self.sustain_ticks
+    }
+
+    #[must_use]
+    pub const fn get_sustain_seconds(&self) -> f64 {
+        self.sustain_seconds
+    }
+
+    #[must_use]
+    pub const fn get_star_power_phrases(&self) -> usize {
+        self.star_power_phrases
+    }
+
+    /// Notes per second from the first to the last note.
+    #[must_use]
+    pub const fn get_average_nps(&self) -> f64 {
+        self.average_nps
+    }
+
+    /// Most notes in any one second stretch of the track, see `difficulty::peak_nps`.
+    #[must_use]
+    pub const fn get_peak_nps(&self) -> f64 {
+        self.peak_nps
+    }
+}
+
+/// Statistics of a whole chart: its length, tempo, vocals and every note track.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChartStats {
+    song_length_seconds: f64,
+    min_bpm: f64,
+    max_bpm: f64,
+    lyrics: usize,
+    main_phrases: usize,
+    duet_phrases: usize,
+    sections: usize,
+    tracks: Vec<TrackStats>,
+}
+
+impl ChartStats {
+    /// Constructor for `ChartStats` from a chart.
+    ///
+    /// # Arguments
+    ///
+    /// * `chart`: the chart to measure.
+    ///
+    /// returns: `ChartStats`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::stats::ChartStats;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let stats = ChartStats::new(&Chart::new(&file_content).unwrap());
+    /// for track in stats.get_tracks() {
+    ///     println!("{}: {} notes, peak {} nps", track.get_name(), track.get_notes(), track.get_peak_nps());
+    /// }
+    /// ```
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(chart: &Chart) -> Self {
+        let tempo_map = TempoMap::from_chart(chart);
+        let mut names: Vec<&String> = chart.get_key_presses().keys().collect();
+        names.sort_by_key(|name| Chart::track_order(name));
+        let tracks = names
+            .into_iter()
+            .map(|name| TrackStats::new(name, &chart.get_key_presses()[name], &tempo_map))
+            .collect();
+
+        let song_end = chart
+            .get_key_presses()
+            .values()
+            .flatten()
+            .map(|event| match event {
+                KeyPressEvent::Note {
+                    timestamp,
+                    duration,
+                    ..
+                }
+                | KeyPressEvent::Special {
+                    timestamp,
+                    duration,
+                    ..
+                } => timestamp.saturating_add(*duration),
+                _ => event.get_timestamp(),
+            })
+            .chain(
+                chart
+                    .get_lyrics()
+                    .iter()
+                    .map(TimestampedEvent::get_timestamp),
+            )
+            .chain(
+                chart
+                    .get_tempo_map()
+                    .iter()
+                    .map(TimestampedEvent::get_timestamp),
+            )
+            .max()
+            .unwrap_or(0);
+
+        let (min_milli_bpm, max_milli_bpm) = tempo_map.milli_bpm_range();
+
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        Self {
+            song_length_seconds: tempo_map.ticks_to_seconds(song_end),
+            min_bpm: min_milli_bpm as f64 / 1000.0,
+            max_bpm: max_milli_bpm as f64 / 1000.0,
+            lyrics: chart
+                .get_lyrics()
+                .iter()
+                .filter(|event| {
+                    matches!(
+                        event,
+                        LyricEvent::Lyric { .. } | LyricEvent::DuetLyric { .. }
+                    )
+                })
+                .count(),
+            main_phrases: phrases.get_main_phrases().len(),
+            duet_phrases: phrases.get_duet_phrases().len(),
+            sections: chart
+                .get_lyrics()
+                .iter()
+                .filter(|event| matches!(event, LyricEvent::Section { .. }))
+                .count(),
+            tracks,
+        }
+    }
+
+    /// Time from the start of the chart to its last event, in seconds.
+    #[must_use]
+    pub const fn get_song_length_seconds(&self) -> f64 {
+        self.song_length_seconds
+    }
+
+    #[must_use]
+    pub const fn get_min_bpm(&self) -> f64 {
+        self.min_bpm
+    }
+
+    #[must_use]
+    pub const fn get_max_bpm(&self) -> f64 {
+        self.max_bpm
+    }
+
+    /// Number of lyric events of both singers.
+    #[must_use]
+    pub const fn get_lyrics(&self) -> usize {
+        self.lyrics
+    }
+
+    #[must_use]
+    pub const fn get_main_phrases(&self) -> usize {
+        self.main_phrases
+    }
+
+    #[must_use]
+    pub const fn get_duet_phrases(&self) -> usize {
+        self.duet_phrases
+    }
+
+    #[must_use]
+    pub const fn get_sections(&self) -> usize {
+        self.sections
+    }
+
+    /// Statistics of every note track, in the order the tracks are written to a chart file.
+    #[must_use]
+    pub const fn get_tracks(&self) -> &Vec<TrackStats> {
+        &self.tracks
+    }
+
+    /// The statistics as pretty-printed JSON.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the statistics cannot be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::WrapErr;
+
+    use crate::chart::{Chart, TempoEvent};
+    use crate::difficulty::chart_difficulties;
+
+    use super::*;
+
+    #[test]
+    fn track_stats() {
+        let tempo_map = TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        );
+        let mut events: Vec<KeyPressEvent> = (0..8)
+            .map(|i| KeyPressEvent::Note {
+                timestamp: i * 48,
+                duration: 0,
+                key: 0,
+            })
+            .collect();
+        events.push(KeyPressEvent::Note {
+            timestamp: 0,
+            duration: 0,
+            key: 1,
+        });
+        events.push(KeyPressEvent::Note {
+            timestamp: 768,
+            duration: 384,
+            key: 2,
+        });
+        events.push(KeyPressEvent::Special {
+            timestamp: 0,
+            special_type: 2,
+            duration: 768,
+        });
+        let stats = TrackStats::new("ExpertSingle", &events, &tempo_map);
+        assert_eq!(stats.get_notes(), 9);
+        assert_eq!(stats.get_chords(), 1);
+        assert_eq!(stats.get_sustains(), 1);
+        assert_eq!(stats.get_sustain_ticks(), 384);
+        assert!((stats.get_sustain_seconds() - 1.0).abs() < 1e-9);
+        assert_eq!(stats.get_star_power_phrases(), 1);
+        assert!((stats.get_average_nps() - 4.5).abs() < 1e-9);
+        assert!((stats.get_peak_nps() - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chart_stats() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            chart_stats_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn chart_stats_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let stats = ChartStats::new(&chart);
+        assert_eq!(stats.get_tracks().len(), chart.get_key_presses().len());
+        assert!(stats.get_min_bpm() <= stats.get_max_bpm());
+        for track in stats.get_tracks() {
+            assert!(track.get_chords() <= track.get_notes());
+            assert!(track.get_average_nps() <= track.get_peak_nps() + 1.0);
+        }
+        for (track, difficulty) in stats.get_tracks().iter().zip(chart_difficulties(&chart)) {
+            assert_eq!(track.get_peak_nps(), difficulty.get_peak_nps());
+        }
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()?)?;
+        assert_eq!(json["sections"], stats.get_sections());
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use crate::chart::LyricEvent;
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::TimestampedEvent;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongSection {
+    name: Option<String>,
+    start_timestamp: u32,
+    end_timestamp: u32,
+    main_phrases: Vec<Phrase>,
+    duet_phrases: Vec<Phrase>,
+}
+
+impl SongSection {
+    /// The section's name, or `None` for the phrases before the first section event.
+    #[must_use]
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_start_timestamp(&self) -> u32 {
+        self.start_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_end_timestamp(&self) -> u32 {
+        self.end_timestamp
+    }
+
+    #[must_use]
+    pub const fn get_phrases(&self, singer: Singer) -> &Vec<Phrase> {
+        match singer {
+            Singer::Main => &self.main_phrases,
+            Singer::Duet => &self.duet_phrases,
+        }
+    }
+
+    #[must_use]
+    pub fn phrase_count(&self, singer: Singer) -> usize {
+        self.get_phrases(singer).len()
+    }
+
+    /// Hands every phrase in this section over to `singer`, rewriting the phrase start, lyric and
+    /// phrase end events of the other singer's phrases in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `lyrics_events`: the chart's lyric events this section was built from.
+    /// * `singer`: the singer who should sing the whole section.
+    pub fn assign_to(&self, lyrics_events: &mut [LyricEvent], singer: Singer) {
+        let other = match singer {
+            Singer::Main => Singer::Duet,
+            Singer::Duet => Singer::Main,
+        };
+        for phrase in self.get_phrases(other) {
+            let start = phrase.get_start_timestamp();
+            let end = phrase.get_end_timestamp();
+            let last_lyric = phrase
+                .get_lyrics()
+                .last()
+                .map_or(start, TimestampedEvent::get_timestamp);
+            for event in lyrics_events.iter_mut() {
+                let timestamp = event.get_timestamp();
+                let replacement = match (&*event, singer) {
+                    (LyricEvent::PhraseStart { .. }, Singer::Duet) if timestamp == start => {
+                        LyricEvent::DuetPhraseStart { timestamp }
+                    }
+                    (LyricEvent::DuetPhraseStart { .. }, Singer::Main) if timestamp == start => {
+                        LyricEvent::PhraseStart { timestamp }
+                    }
+                    (LyricEvent::PhraseEnd { .. }, Singer::Duet)
+                        if timestamp == end && timestamp > start =>
+                    {
+                        LyricEvent::DuetPhraseEnd { timestamp }
+                    }
+                    (LyricEvent::DuetPhraseEnd { .. }, Singer::Main)
+                        if timestamp == end && timestamp > start =>
+                    {
+                        LyricEvent::PhraseEnd { timestamp }
+                    }
+                    (LyricEvent::Lyric { text, .. }, Singer::Duet)
+                        if (start..=last_lyric).contains(&timestamp) =>
+                    {
+                        LyricEvent::DuetLyric {
+                            timestamp,
+                            text: text.clone(),
+                        }
+                    }
+                    (LyricEvent::DuetLyric { text, .. }, Singer::Main)
+                        if (start..=last_lyric).contains(&timestamp) =>
+                    {
+                        LyricEvent::Lyric {
+                            timestamp,
+                            text: text.clone(),
+                        }
+                    }
+                    _ => continue,
+                };
+                *event = replacement;
+            }
+        }
+    }
+}
+
+/// The lyrics of a song grouped into the spans between its `section` events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongStructure {
+    sections: Vec<SongSection>,
+}
+
+impl SongStructure {
+    /// Constructor for `SongStructure` from a collection of `LyricEvent`s.
+    ///
+    /// Phrases belong to the section they start in. Phrases starting before the first section
+    /// event are grouped into a leading section without a name.
+    ///
+    /// # Arguments
+    ///
+    /// * `lyrics_events`: the collection of `LyricEvent`s to build the structure out of.
+    ///
+    /// returns: `SongStructure`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::phrases::Singer;
+    /// use duet_charter_lib::structure::SongStructure;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// let structure = SongStructure::new(chart.get_lyrics());
+    /// for section in structure.sections_named("Verse 1A") {
+    ///     section.assign_to(chart.get_lyrics_mut(), Singer::Duet);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn new(lyrics_events: &[LyricEvent]) -> Self {
+        Self::from_phrases(lyrics_events, &LyricPhraseCollection::new(lyrics_events))
+    }
+
+    /// Constructor for `SongStructure` reusing phrases that were already built from `lyrics_events`.
+    #[must_use]
+    pub fn from_phrases(lyrics_events: &[LyricEvent], phrases: &LyricPhraseCollection) -> Self {
+        let mut boundaries: Vec<(u32, Option<String>)> = lyrics_events
+            .iter()
+            .filter_map(|event| match event {
+                LyricEvent::Section { timestamp, text } => Some((*timestamp, Some(text.clone()))),
+                _ => None,
+            })
+            .collect();
+        boundaries.sort_by_key(|(timestamp, _)| *timestamp);
+
+        let first_section = boundaries.first().map(|(timestamp, _)| *timestamp);
+        let has_lead_in = [Singer::Main, Singer::Duet].iter().any(|singer| {
+            phrases.get_phrases(*singer).iter().any(|phrase| {
+                first_section.is_none_or(|first| phrase.get_start_timestamp() < first)
+            })
+        });
+        if has_lead_in {
+            boundaries.insert(0, (0, None));
+        }
+
+        let song_end = lyrics_events
+            .iter()
+            .map(TimestampedEvent::get_timestamp)
+            .chain(
+                [Singer::Main, Singer::Duet]
+                    .iter()
+                    .flat_map(|singer| phrases.get_phrases(*singer))
+                    .map(Phrase::get_end_timestamp),
+            )
+            .max()
+            .unwrap_or(0);
+
+        let sections = boundaries
+            .iter()
+            .enumerate()
+            .map(|(i, (start_timestamp, name))| {
+                let next = boundaries.get(i + 1).map(|(timestamp, _)| *timestamp);
+                let in_section = |phrase: &&Phrase| {
+                    phrase.get_start_timestamp() >= *start_timestamp
+                        && next.is_none_or(|next| phrase.get_start_timestamp() < next)
+                };
+                SongSection {
+                    name: name.clone(),
+                    start_timestamp: *start_timestamp,
+                    end_timestamp: next.unwrap_or_else(|| song_end.max(*start_timestamp)),
+                    main_phrases: phrases
+                        .get_main_phrases()
+                        .iter()
+                        .filter(in_section)
+                        .cloned()
+                        .collect(),
+                    duet_phrases: phrases
+                        .get_duet_phrases()
+                        .iter()
+                        .filter(in_section)
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect();
+        Self { sections }
+    }
+
+    #[must_use]
+    pub const fn get_sections(&self) -> &Vec<SongSection> {
+        &self.sections
+    }
+
+    /// All sections carrying the given name; songs often repeat a section name.
+    pub fn sections_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SongSection> {
+        self.sections
+            .iter()
+            .filter(move |section| section.get_name() == Some(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::chart::Chart;
+
+    use super::*;
+
+    #[test]
+    fn structure_loading() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            structure_loading_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn structure_loading_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        let structure = SongStructure::from_phrases(chart.get_lyrics(), &phrases);
+        for singer in [Singer::Main, Singer::Duet] {
+            assert_eq!(
+                structure
+                    .get_sections()
+                    .iter()
+                    .map(|section| section.phrase_count(singer))
+                    .sum::<usize>(),
+                phrases.get_phrases(singer).len()
+            );
+        }
+        Ok(())
+    }
+
+    fn example_events() -> Vec<LyricEvent> {
+        vec![
+            LyricEvent::PhraseStart { timestamp: 0 },
+            LyricEvent::Lyric {
+                timestamp: 0,
+                text: "oh".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 50 },
+            LyricEvent::Section {
+                timestamp: 100,
+                text: "Verse 1".to_string(),
+            },
+            LyricEvent::PhraseStart { timestamp: 100 },
+            LyricEvent::Lyric {
+                timestamp: 110,
+                text: "hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 120,
+                text: "lo".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 150 },
+            LyricEvent::Section {
+                timestamp: 200,
+                text: "Chorus".to_string(),
+            },
+            LyricEvent::PhraseStart { timestamp: 200 },
+            LyricEvent::Lyric {
+                timestamp: 210,
+                text: "la".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 250 },
+        ]
+    }
+
+    #[test]
+    fn section_spans() {
+        let structure = SongStructure::new(&example_events());
+        let sections = structure.get_sections();
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].get_name(), None);
+        assert_eq!(sections[1].get_name(), Some("Verse 1"));
+        assert_eq!(sections[1].get_start_timestamp(), 100);
+        assert_eq!(sections[1].get_end_timestamp(), 200);
+        assert_eq!(sections[2].get_end_timestamp(), 250);
+        assert_eq!(sections[1].phrase_count(Singer::Main), 1);
+        assert_eq!(sections[1].phrase_count(Singer::Duet), 0);
+    }
+
+    #[test]
+    fn section_assignment() {
+        let mut events = example_events();
+        let structure = SongStructure::new(&events);
+        for section in structure.sections_named("Verse 1") {
+            section.assign_to(&mut events, Singer::Duet);
+        }
+        let duet_structure = SongStructure::new(&events);
+        let verse = &duet_structure.get_sections()[1];
+        assert_eq!(verse.phrase_count(Singer::Main), 0);
+        assert_eq!(verse.phrase_count(Singer::Duet), 1);
+        assert_eq!(
+            verse.get_phrases(Singer::Duet)[0],
+            structure.get_sections()[1].get_phrases(Singer::Main)[0]
+        );
+        assert_eq!(
+            duet_structure.get_sections()[2].phrase_count(Singer::Main),
+            1
+        );
+
+        for section in duet_structure.sections_named("Verse 1") {
+            section.assign_to(&mut events, Singer::Main);
+        }
+        assert_eq!(events, example_events());
+    }
+}
@@ -0,0 +1,397 @@
+use std::fmt::Write;
+
+use crate::phrases::{LyricPhraseCollection, Phrase, Singer};
+use crate::timing::TempoMap;
+
+/// Settings for turning phrases into subtitle cues.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleOptions {
+    /// Shortest time a cue stays on screen, in microseconds. Short cues are lengthened up to the
+    /// next cue of the same singer.
+    pub min_cue_micros: u64,
+    /// Phrases shorter than this many microseconds are merged into the following phrase of the
+    /// same singer, if it starts no more than this long after the short phrase ends.
+    pub merge_short_phrases_micros: Option<u64>,
+    /// WebVTT voice name of the main singer.
+    pub main_voice: String,
+    /// WebVTT voice name of the duet singer.
+    pub duet_voice: String,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            min_cue_micros: 1_000_000,
+            merge_short_phrases_micros: None,
+            main_voice: "Main".to_string(),
+            duet_voice: "Duet".to_string(),
+        }
+    }
+}
+
+/// A single subtitle cue, made of one or more phrases of one singer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCue {
+    start_micros: u64,
+    end_micros: u64,
+    singer: Singer,
+    syllables: Vec<(u64, String, bool)>,
+}
+
+impl SubtitleCue {
+    fn from_phrase(phrase: &Phrase, singer: Singer, tempo_map: &TempoMap) -> Self {
+        Self {
+            start_micros: tempo_map.ticks_to_micros(phrase.get_start_timestamp()),
+            end_micros: tempo_map.ticks_to_micros(phrase.get_end_timestamp()),
+            singer,
+            syllables: phrase
+                .get_display_syllables()
+                .into_iter()
+                .map(|(timestamp, syllable, joined)| {
+                    (tempo_map.ticks_to_micros(timestamp), syllable, joined)
+                })
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub const fn get_start_micros(&self) -> u64 {
+        self.start_micros
+    }
+
+    #[must_use]
+    pub const fn get_end_micros(&self) -> u64 {
+        self.end_micros
+    }
+
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    /// The cue's text as shown on screen, see `Phrase::get_display_text`.
+    #[must_use]
+    pub fn get_text(&self) -> String {
+        self.render(|_, _| None, |text| text.to_string())
+    }
+
+    /// Joins the syllables, writing the tag returned by `tag` before each one.
+    fn render(
+        &self,
+        tag: impl Fn(usize, u64) -> Option<String>,
+        escape: impl Fn(&str) -> String,
+    ) -> String {
+        let mut line = String::new();
+        let mut separator = "";
+        for (i, (microseconds, syllable, joined)) in self.syllables.iter().enumerate() {
+            line.push_str(separator);
+            if let Some(tag) = tag(i, *microseconds) {
+                line.push_str(&tag);
+            }
+            line.push_str(&escape(syllable));
+            separator = if *joined { "" } else { " " };
+        }
+        line.trim().to_string()
+    }
+}
+
+/// Subtitle cues for the phrases of a chart, writable as SRT or WebVTT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtitles {
+    cues: Vec<SubtitleCue>,
+    main_voice: String,
+    duet_voice: String,
+}
+
+impl Subtitles {
+    /// Constructor for `Subtitles` from the main and duet phrases of a chart.
+    ///
+    /// # Arguments
+    ///
+    /// * `phrases`: the phrases to make cues of.
+    /// * `tempo_map`: the tempo map of the chart, used to turn ticks into times.
+    /// * `options`: the cue settings.
+    ///
+    /// returns: `Subtitles`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::phrases::LyricPhraseCollection;
+    /// use duet_charter_lib::subtitles::{SubtitleOptions, Subtitles};
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let subtitles = Subtitles::new(
+    ///     &LyricPhraseCollection::new(chart.get_lyrics()),
+    ///     &TempoMap::from_chart(&chart),
+    ///     &SubtitleOptions::default(),
+    /// );
+    /// let srt = subtitles.to_srt();
+    /// let vtt = subtitles.to_webvtt();
+    /// ```
+    #[must_use]
+    pub fn new(
+        phrases: &LyricPhraseCollection,
+        tempo_map: &TempoMap,
+        options: &SubtitleOptions,
+    ) -> Self {
+        let mut cues = vec![];
+        for singer in [Singer::Main, Singer::Duet] {
+            let mut singer_cues: Vec<SubtitleCue> = vec![];
+            for phrase in phrases.get_phrases(singer) {
+                let cue = SubtitleCue::from_phrase(phrase, singer, tempo_map);
+                if cue.get_text().is_empty() {
+                    continue;
+                }
+                match singer_cues.last_mut() {
+                    Some(previous)
+                        if options.merge_short_phrases_micros.is_some_and(|limit| {
+                            previous.end_micros - previous.start_micros < limit
+                                && cue.start_micros <= previous.end_micros + limit
+                        }) =>
+                    {
+                        previous.end_micros = previous.end_micros.max(cue.end_micros);
+                        previous.syllables.extend(cue.syllables);
+                    }
+                    _ => singer_cues.push(cue),
+                }
+            }
+            let next_starts: Vec<Option<u64>> = singer_cues
+                .iter()
+                .skip(1)
+                .map(|cue| Some(cue.start_micros))
+                .chain([None])
+                .collect();
+            for (cue, next_start) in singer_cues.iter_mut().zip(next_starts) {
+                let min_end = cue.start_micros + options.min_cue_micros;
+                if cue.end_micros < min_end {
+                    cue.end_micros =
+                        next_start.map_or(min_end, |next| next.clamp(cue.end_micros, min_end));
+                }
+            }
+            cues.extend(singer_cues);
+        }
+        cues.sort_by_key(|cue| cue.start_micros);
+        Self {
+            cues,
+            main_voice: options.main_voice.clone(),
+            duet_voice: options.duet_voice.clone(),
+        }
+    }
+
+    #[must_use]
+    pub const fn get_cues(&self) -> &Vec<SubtitleCue> {
+        &self.cues
+    }
+
+    /// Writes the cues as a SubRip (.srt) file.
+    #[must_use]
+    pub fn to_srt(&self) -> String {
+        let mut srt = String::new();
+        for (i, cue) in self.cues.iter().enumerate() {
+            // writing to a String cannot fail
+            let _ = write!(
+                srt,
+                "{}\r\n{} --> {}\r\n{}\r\n\r\n",
+                i + 1,
+                format_srt_time(cue.start_micros),
+                format_srt_time(cue.end_micros),
+                cue.get_text()
+            );
+        }
+        srt
+    }
+
+    /// Writes the cues as a WebVTT (.vtt) file, with a `<v>` voice span per singer and a
+    /// karaoke timestamp before every syllable after the first.
+    #[must_use]
+    pub fn to_webvtt(&self) -> String {
+        let mut vtt = String::from("WEBVTT\n\n");
+        for cue in &self.cues {
+            let voice = match cue.singer {
+                Singer::Main => &self.main_voice,
+                Singer::Duet => &self.duet_voice,
+            };
+            let text = cue.render(
+                |i, microseconds| {
+                    (i > 0 && microseconds > cue.start_micros && microseconds < cue.end_micros)
+                        .then(|| format!("<{}>", format_vtt_time(microseconds)))
+                },
+                escape_vtt,
+            );
+            let _ = write!(
+                vtt,
+                "{} --> {}\n<v {}>{}\n\n",
+                format_vtt_time(cue.start_micros),
+                format_vtt_time(cue.end_micros),
+                escape_vtt(voice),
+                text
+            );
+        }
+        vtt
+    }
+}
+
+fn split_millis(microseconds: u64) -> (u64, u64, u64, u64) {
+    let millis = (microseconds + 500) / 1000;
+    (
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000,
+    )
+}
+
+fn format_srt_time(microseconds: u64) -> String {
+    let (hours, minutes, seconds, millis) = split_millis(microseconds);
+    format!("{:02}:{:02}:{:02},{:03}", hours, minutes, seconds, millis)
+}
+
+/// Formats a WebVTT timestamp, leaving out the hours when they are zero.
+fn format_vtt_time(microseconds: u64) -> String {
+    let (hours, minutes, seconds, millis) = split_millis(microseconds);
+    if hours > 0 {
+        format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
+    } else {
+        format!("{:02}:{:02}.{:03}", minutes, seconds, millis)
+    }
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chart::{LyricEvent, TempoEvent};
+
+    use super::*;
+
+    fn tempo_map() -> TempoMap {
+        TempoMap::new(
+            &[TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: 120_000,
+            }],
+            192,
+        )
+    }
+
+    fn example_phrases() -> LyricPhraseCollection {
+        LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 384 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+            LyricEvent::PhraseStart { timestamp: 960 },
+            LyricEvent::Lyric {
+                timestamp: 960,
+                text: "Hey".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 1000 },
+            LyricEvent::PhraseStart { timestamp: 1056 },
+            LyricEvent::Lyric {
+                timestamp: 1056,
+                text: "you".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 1152 },
+            LyricEvent::DuetPhraseStart { timestamp: 768 },
+            LyricEvent::DuetLyric {
+                timestamp: 768,
+                text: "Bye".to_string(),
+            },
+            LyricEvent::DuetPhraseEnd { timestamp: 1536 },
+        ])
+    }
+
+    #[test]
+    fn srt_export() {
+        let subtitles = Subtitles::new(
+            &example_phrases(),
+            &tempo_map(),
+            &SubtitleOptions::default(),
+        );
+        assert_eq!(
+            subtitles.to_srt(),
+            "1\r\n00:00:01,000 --> 00:00:02,000\r\nHello world\r\n\r\n\
+             2\r\n00:00:02,000 --> 00:00:04,000\r\nBye\r\n\r\n\
+             3\r\n00:00:02,500 --> 00:00:02,750\r\nHey\r\n\r\n\
+             4\r\n00:00:02,750 --> 00:00:03,750\r\nyou\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn chart_markup_export() {
+        let phrases = LyricPhraseCollection::new(&[
+            LyricEvent::PhraseStart { timestamp: 384 },
+            LyricEvent::Lyric {
+                timestamp: 384,
+                text: "Hel-".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 480,
+                text: "lo#".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 528,
+                text: "+".to_string(),
+            },
+            LyricEvent::Lyric {
+                timestamp: 576,
+                text: "world".to_string(),
+            },
+            LyricEvent::PhraseEnd { timestamp: 768 },
+        ]);
+        let subtitles = Subtitles::new(&phrases, &tempo_map(), &SubtitleOptions::default());
+        assert_eq!(
+            subtitles.to_srt(),
+            "1\r\n00:00:01,000 --> 00:00:02,000\r\nHello world\r\n\r\n"
+        );
+        assert_eq!(
+            subtitles.to_webvtt(),
+            "WEBVTT\n\n\
+             00:01.000 --> 00:02.000\n<v Main>Hel<00:01.250>lo <00:01.500>world\n\n"
+        );
+    }
+
+    #[test]
+    fn webvtt_export() {
+        let subtitles = Subtitles::new(
+            &example_phrases(),
+            &tempo_map(),
+            &SubtitleOptions {
+                merge_short_phrases_micros: Some(500_000),
+                ..SubtitleOptions::default()
+            },
+        );
+        assert_eq!(
+            subtitles.to_webvtt(),
+            "WEBVTT\n\n\
+             00:01.000 --> 00:02.000\n<v Main>Hel<00:01.250>lo <00:01.500>world\n\n\
+             00:02.000 --> 00:04.000\n<v Duet>Bye\n\n\
+             00:02.500 --> 00:03.500\n<v Main>Hey <00:02.750>you\n\n"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use eyre::{eyre, Result};
+
+use crate::chart::{Chart, KeyPressEvent, TempoEvent};
+use crate::timing::TempoMap;
+use crate::TimestampedEvent;
+
+/// What a tempo map edit keeps in place for the lyrics and notes of a chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempoEditMode {
+    /// Lyrics and notes keep their ticks, so they move in song time with the new tempo.
+    KeepTicks,
+    /// Lyrics and notes get new ticks at the same song time, such as when re-syncing a chart to
+    /// its audio.
+    KeepAudioTime,
+}
+
+fn beat_at(tempo_map: &[TempoEvent], timestamp: u32) -> Option<u64> {
+    tempo_map.iter().find_map(|event| match event {
+        TempoEvent::Beat {
+            timestamp: tick,
+            milli_bpm,
+        } if *tick == timestamp => Some(*milli_bpm),
+        _ => None,
+    })
+}
+
+fn set_beat(tempo_map: &mut Vec<TempoEvent>, timestamp: u32, milli_bpm: u64) {
+    tempo_map.retain(
+        |event| !matches!(event, TempoEvent::Beat { timestamp: tick, .. } if *tick == timestamp),
+    );
+    tempo_map.push(TempoEvent::Beat {
+        timestamp,
+        milli_bpm,
+    });
+}
+
+impl Chart {
+    /// Applies an edit to the tempo map, then moves the lyrics and notes as `mode` asks.
+    fn edit_tempo_map(&mut self, mode: TempoEditMode, edit: impl FnOnce(&mut Vec<TempoEvent>)) {
+        let before = TempoMap::from_chart(self);
+        let tempo_map = self.get_tempo_map_mut();
+        edit(tempo_map);
+        tempo_map.sort_by_key(|event| (event.get_timestamp(), event.write_rank()));
+        if mode == TempoEditMode::KeepTicks {
+            return;
+        }
+
+        let after = TempoMap::from_chart(self);
+        let remap = |timestamp: u32| after.micros_to_ticks(before.ticks_to_micros(timestamp));
+        for event in self.get_lyrics_mut() {
+            event.set_timestamp(remap(event.get_timestamp()));
+        }
+        for event in self.get_key_presses_mut().values_mut().flatten() {
+            let timestamp = event.get_timestamp();
+            match event {
+                KeyPressEvent::Note { duration, .. } | KeyPressEvent::Special { duration, .. }
+                    if *duration > 0 =>
+                {
+                    let start = remap(timestamp);
+                    *duration = remap(timestamp.saturating_add(*duration)).saturating_sub(start);
+                    event.set_timestamp(start);
+                }
+                _ => event.set_timestamp(remap(timestamp)),
+            }
+        }
+    }
+
+    /// Sets the tempo from a tick up to the next tempo change, adding a `B` event if there is
+    /// none at that tick.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick the tempo starts at.
+    /// * `milli_bpm`: the tempo in thousandths of a beat per minute.
+    /// * `mode`: whether lyrics and notes keep their ticks or their song time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::tempo_edit::TempoEditMode;
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// chart.set_bpm(0, 150_000, TempoEditMode::KeepAudioTime);
+    /// assert_eq!(TempoMap::from_chart(&chart).milli_bpm_at(0), 150_000);
+    /// ```
+    pub fn set_bpm(&mut self, at: u32, milli_bpm: u64, mode: TempoEditMode) {
+        self.edit_tempo_map(mode, |tempo_map| {
+            set_beat(tempo_map, at, milli_bpm.max(1));
+        });
+    }
+
+    /// Removes the `B` event at a tick, so the tempo before it carries on.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick of the tempo change.
+    /// * `mode`: whether lyrics and notes keep their ticks or their song time.
+    ///
+    /// returns: whether there was a tempo change at that tick.
+    pub fn remove_tempo_change(&mut self, at: u32, mode: TempoEditMode) -> bool {
+        if beat_at(self.get_tempo_map(), at).is_none() {
+            return false;
+        }
+        self.edit_tempo_map(mode, |tempo_map| {
+            tempo_map.retain(
+                |event| !matches!(event, TempoEvent::Beat { timestamp, .. } if *timestamp == at),
+            );
+        });
+        true
+    }
+
+    /// Sets the time signature from a tick up to the next time signature change, adding a `TS`
+    /// event if there is none at that tick.
+    ///
+    /// Time signatures only change how measures are counted, so no event moves in song time.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick the time signature starts at, normally the start of a measure.
+    /// * `time_signature`: the beats per measure and the note value of a beat, such as `(6, 8)`.
+    pub fn insert_time_signature(&mut self, at: u32, time_signature: (u32, u32)) {
+        self.edit_tempo_map(TempoEditMode::KeepTicks, |tempo_map| {
+            tempo_map.retain(|event| {
+                !matches!(event, TempoEvent::TimeSignature { timestamp, .. } if *timestamp == at)
+            });
+            tempo_map.push(TempoEvent::TimeSignature {
+                timestamp: at,
+                time_signature,
+            });
+        });
+    }
+
+    /// Anchors a tick to a song time, as Moonscraper does.
+    ///
+    /// The tempo from the previous tempo change up to the tick is changed so the tick lands on
+    /// that time, and the tempo that was in effect at the tick is set again there, so the tempo
+    /// after the anchor is unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick to anchor.
+    /// * `song_microseconds`: the song time the tick should land on.
+    /// * `mode`: whether lyrics and notes keep their ticks or their song time.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the time is not after the time of the previous tempo change, or
+    /// the tick is at a tempo change without one before it.
+    pub fn add_anchor(
+        &mut self,
+        at: u32,
+        song_microseconds: u64,
+        mode: TempoEditMode,
+    ) -> Result<()> {
+        let timing = TempoMap::from_chart(self);
+        let previous = self
+            .get_tempo_map()
+            .iter()
+            .filter_map(|event| match event {
+                TempoEvent::Beat { timestamp, .. } if *timestamp < at => Some(*timestamp),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let previous_micros = timing.ticks_to_micros(previous);
+        if at <= previous || song_microseconds <= previous_micros {
+            return Err(eyre!(
+                "Cannot anchor tick {} to {}µs, it must come after the tempo change at tick {} ({}µs)",
+                at,
+                song_microseconds,
+                previous,
+                previous_micros
+            ));
+        }
+
+        let resolution = u64::from(timing.get_resolution());
+        let span_micros = song_microseconds - previous_micros;
+        let milli_bpm = (60_000_000_000 * u64::from(at - previous) + span_micros * resolution / 2)
+            / (span_micros * resolution);
+        let after = timing.milli_bpm_at(at);
+        self.edit_tempo_map(mode, |tempo_map| {
+            if beat_at(tempo_map, at).is_none() {
+                set_beat(tempo_map, at, after);
+            }
+            set_beat(tempo_map, previous, milli_bpm.max(1));
+            tempo_map.retain(
+                |event| !matches!(event, TempoEvent::Anchor { timestamp, .. } if *timestamp == at),
+            );
+            tempo_map.push(TempoEvent::Anchor {
+                timestamp: at,
+                song_microseconds,
+            });
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::WrapErr;
+
+    use super::*;
+    use crate::chart::LyricEvent;
+    use crate::test_util::tempo_chart;
+
+    fn chart() -> Chart {
+        tempo_chart(
+            vec![
+                LyricEvent::PhraseStart { timestamp: 0 },
+                LyricEvent::Lyric {
+                    timestamp: 384,
+                    text: "la".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 768 },
+            ],
+            vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+            ],
+            vec![KeyPressEvent::Note {
+                timestamp: 192,
+                duration: 192,
+                key: 0,
+            }],
+        )
+    }
+
+    #[test]
+    fn set_bpm() {
+        let mut kept_ticks = chart();
+        kept_ticks.set_bpm(0, 240_000, TempoEditMode::KeepTicks);
+        assert_eq!(kept_ticks.get_lyrics()[1].get_timestamp(), 384);
+
+        let mut kept_time = chart();
+        kept_time.set_bpm(0, 240_000, TempoEditMode::KeepAudioTime);
+        assert_eq!(kept_time.get_lyrics()[1].get_timestamp(), 768);
+        assert_eq!(
+            kept_time.get_key_presses()["ExpertSingle"],
+            vec![KeyPressEvent::Note {
+                timestamp: 384,
+                duration: 384,
+                key: 0,
+            }]
+        );
+        assert!(kept_time.remove_tempo_change(0, TempoEditMode::KeepAudioTime));
+        assert_eq!(kept_time.get_lyrics()[1].get_timestamp(), 384);
+        assert!(!kept_time.remove_tempo_change(0, TempoEditMode::KeepAudioTime));
+    }
+
+    #[test]
+    fn add_anchor() -> Result<()> {
+        let mut chart = chart();
+        chart.add_anchor(384, 1_500_000, TempoEditMode::KeepTicks)?;
+        assert_eq!(
+            chart.get_tempo_map(),
+            &vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 80_000,
+                },
+                TempoEvent::Anchor {
+                    timestamp: 384,
+                    song_microseconds: 1_500_000,
+                },
+                TempoEvent::Beat {
+                    timestamp: 384,
+                    milli_bpm: 120_000,
+                },
+            ]
+        );
+        assert!(chart.add_anchor(384, 0, TempoEditMode::KeepTicks).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn keep_audio_time() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            keep_audio_time_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn keep_audio_time_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let mut chart = Chart::new(&file_content)?;
+        let before = TempoMap::from_chart(&chart);
+        let original: Vec<u64> = chart
+            .get_lyrics()
+            .iter()
+            .map(|event| before.ticks_to_micros(event.get_timestamp()))
+            .collect();
+
+        chart.set_bpm(
+            0,
+            before.milli_bpm_at(0) * 3 / 2,
+            TempoEditMode::KeepAudioTime,
+        );
+        let after = TempoMap::from_chart(&chart);
+        for (micros, event) in original.iter().zip(chart.get_lyrics()) {
+            let timestamp = event.get_timestamp();
+            let tick_micros =
+                after.ticks_to_micros(timestamp + 1) - after.ticks_to_micros(timestamp);
+            assert!(after.ticks_to_micros(timestamp).abs_diff(*micros) <= tick_micros);
+        }
+        Ok(())
+    }
+}
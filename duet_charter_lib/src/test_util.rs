@@ -0,0 +1,75 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::collections::HashMap;
+
+use crate::chart::{Chart, KeyPressEvent, LyricEvent, TempoEvent};
+use crate::scoring::ScoringTrack;
+
+/// A chart at resolution 192 and 120 BPM with the given lyrics and `ExpertSingle` notes.
+pub(crate) fn chart(lyrics: Vec<LyricEvent>, notes: Vec<KeyPressEvent>) -> Chart {
+    tempo_chart(
+        lyrics,
+        vec![TempoEvent::Beat {
+            timestamp: 0,
+            milli_bpm: 120_000,
+        }],
+        notes,
+    )
+}
+
+/// A chart at resolution 192 with the given lyrics, tempo map and `ExpertSingle` notes.
+pub(crate) fn tempo_chart(
+    lyrics: Vec<LyricEvent>,
+    tempo_map: Vec<TempoEvent>,
+    notes: Vec<KeyPressEvent>,
+) -> Chart {
+    Chart::from_parts(
+        HashMap::from([("Resolution".to_string(), "192".to_string())]),
+        lyrics,
+        tempo_map,
+        HashMap::from([("ExpertSingle".to_string(), notes)]),
+    )
+}
+
+/// A main phrase starting at `timestamp` with a word every 48 ticks, ending a beat later.
+pub(crate) fn phrase(timestamp: u32, words: &[&str]) -> Vec<LyricEvent> {
+    let mut events = vec![LyricEvent::PhraseStart { timestamp }];
+    for (i, word) in (0_u32..).zip(words) {
+        events.push(LyricEvent::Lyric {
+            timestamp: timestamp + i * 48,
+            text: (*word).to_string(),
+        });
+    }
+    events.push(LyricEvent::PhraseEnd {
+        timestamp: timestamp + 192,
+    });
+    events
+}
+
+/// The `ExpertSingle` track of `chart` with single green notes at `notes`, the last one
+/// sustained for `sustain_last` ticks, and star power phrases as `(timestamp, duration)` pairs.
+pub(crate) fn scoring_track(
+    notes: &[u32],
+    sustain_last: u32,
+    phrases: &[(u32, u32)],
+) -> ScoringTrack {
+    let mut events: Vec<KeyPressEvent> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, timestamp)| KeyPressEvent::Note {
+            timestamp: *timestamp,
+            duration: if i + 1 == notes.len() {
+                sustain_last
+            } else {
+                0
+            },
+            key: 0,
+        })
+        .collect();
+    events.extend(phrases.iter().map(|(timestamp, duration)| KeyPressEvent::Special {
+        timestamp: *timestamp,
+        special_type: 2,
+        duration: *duration,
+    }));
+    ScoringTrack::from_chart(&chart(vec![], events), "ExpertSingle").expect("guitar track")
+}
@@ -0,0 +1,972 @@
+use crate::chart::{Chart, KeyPressEvent, LyricEvent, TempoEvent};
+use crate::timing::{TempoMap, DEFAULT_MILLI_BPM, DEFAULT_TIME_SIGNATURE};
+use crate::TimestampedEvent;
+
+/// Inserted audio is split into beats of at least this length, so the tempo of the gap stays
+/// in a range where rounding it to a thousandth of a BPM does not add up.
+const MIN_GAP_BEAT_MICROS: u64 = 500_000;
+
+/// Shortest mismatch in microseconds worth inserting a gap for when shifting by milliseconds.
+const MIN_GAP_MICROS: u64 = 500;
+
+/// The tempo and time signature in effect at a tick, with the ticks of the events setting them.
+#[derive(Debug, Clone, Copy)]
+struct InEffect {
+    beat: Option<u32>,
+    milli_bpm: u64,
+    signature: Option<u32>,
+    time_signature: (u32, u32),
+}
+
+impl InEffect {
+    fn at(tempo_map: &[TempoEvent], timestamp: u32) -> Self {
+        let beat = tempo_map
+            .iter()
+            .filter_map(|event| match event {
+                TempoEvent::Beat {
+                    timestamp: tick,
+                    milli_bpm,
+                } if *tick <= timestamp => Some((*tick, *milli_bpm)),
+                _ => None,
+            })
+            .max_by_key(|(tick, _)| *tick);
+        let signature = tempo_map
+            .iter()
+            .filter_map(|event| match event {
+                TempoEvent::TimeSignature {
+                    timestamp: tick,
+                    time_signature,
+                } if *tick <= timestamp => Some((*tick, *time_signature)),
+                _ => None,
+            })
+            .max_by_key(|(tick, _)| *tick);
+        Self {
+            beat: beat.map(|(tick, _)| tick),
+            milli_bpm: beat.map_or(DEFAULT_MILLI_BPM, |(_, milli_bpm)| milli_bpm),
+            signature: signature.map(|(tick, _)| tick),
+            time_signature: signature.map_or(DEFAULT_TIME_SIGNATURE, |(_, signature)| signature),
+        }
+    }
+
+    fn ticks_per_measure(&self, resolution: u32) -> u32 {
+        let (numerator, denominator) = self.time_signature;
+        (numerator * resolution * 4 / denominator.max(1)).max(1)
+    }
+}
+
+fn has_event_at(tempo_map: &[TempoEvent], timestamp: u32, beat: bool) -> bool {
+    tempo_map.iter().any(|event| match event {
+        TempoEvent::Beat {
+            timestamp: tick, ..
+        } => beat && *tick == timestamp,
+        TempoEvent::TimeSignature {
+            timestamp: tick, ..
+        } => !beat && *tick == timestamp,
+        _ => false,
+    })
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn shift_anchor(event: &mut TempoEvent, micros: f64) {
+    if let TempoEvent::Anchor {
+        song_microseconds, ..
+    } = event
+    {
+        let shifted = *song_microseconds as f64 + micros;
+        *song_microseconds = shifted.max(0.0).round() as u64;
+    }
+}
+
+/// Which singer a phrase marker opens or closes a phrase for, and whether it opens it.
+const fn phrase_marker(event: &LyricEvent) -> Option<(bool, bool)> {
+    match event {
+        LyricEvent::PhraseStart { .. } => Some((false, true)),
+        LyricEvent::PhraseEnd { .. } => Some((false, false)),
+        LyricEvent::DuetPhraseStart { .. } => Some((true, true)),
+        LyricEvent::DuetPhraseEnd { .. } => Some((true, false)),
+        _ => None,
+    }
+}
+
+/// Whether a singer's phrase is open just before a tick.
+fn phrase_open_before(lyrics: &[LyricEvent], timestamp: u32, duet: bool) -> bool {
+    lyrics
+        .iter()
+        .filter(|event| event.get_timestamp() < timestamp)
+        .filter_map(phrase_marker)
+        .rfind(|(marker_duet, _)| *marker_duet == duet)
+        .is_some_and(|(_, start)| start)
+}
+
+impl Chart {
+    /// Moves every event of the chart by a number of ticks.
+    ///
+    /// Moving events later inserts silence at the start of the chart in the starting tempo;
+    /// moving them earlier deletes the start of the chart, see `delete_range`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticks`: the number of ticks to move events by, negative to move them earlier.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// let original = chart.clone();
+    /// chart.shift_ticks(768);
+    /// chart.shift_ticks(-768);
+    /// assert_eq!(chart, original);
+    /// ```
+    pub fn shift_ticks(&mut self, ticks: i64) {
+        let amount = u32::try_from(ticks.unsigned_abs()).unwrap_or(u32::MAX);
+        if ticks >= 0 {
+            self.insert_ticks(0, amount);
+        } else {
+            self.delete_range(0, amount);
+        }
+    }
+
+    /// Moves every event of the chart by a length of song time, such as when audio got re-cut.
+    ///
+    /// Moving events later inserts silence at the start with its own tempo, so the rest of the
+    /// chart moves by exactly that time. Moving them earlier deletes the start of the chart up
+    /// to the nearest tick not before that time, then inserts the leftover fraction of a tick as
+    /// silence when it is long enough to matter.
+    ///
+    /// # Arguments
+    ///
+    /// * `millis`: the number of milliseconds to move events by, negative to move them earlier.
+    pub fn shift_millis(&mut self, millis: i64) {
+        let micros = millis.unsigned_abs().saturating_mul(1000);
+        if millis >= 0 {
+            self.insert_micros(0, micros);
+            return;
+        }
+        let tempo_map = TempoMap::from_chart(self);
+        let mut end = tempo_map.micros_to_ticks(micros);
+        if tempo_map.ticks_to_micros(end) + MIN_GAP_MICROS <= micros {
+            end = end.saturating_add(1);
+        }
+        let leftover = tempo_map.ticks_to_micros(end).saturating_sub(micros);
+        self.delete_range(0, end);
+        if leftover >= MIN_GAP_MICROS {
+            self.insert_micros(0, leftover);
+        }
+    }
+
+    /// Inserts silence in the tempo in effect at a tick, moving every later event.
+    ///
+    /// Sustains and special spans held over the tick are lengthened so they still end with the
+    /// music they ended with. When the silence is not a whole number of measures, the time
+    /// signature is repeated after it, so bar lines start again at the moved events.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick to insert silence at; events at this tick move.
+    /// * `ticks`: the length of the silence.
+    pub fn insert_ticks(&mut self, at: u32, ticks: u32) {
+        let milli_bpm = InEffect::at(self.get_tempo_map(), at).milli_bpm;
+        self.insert_gap(at, ticks, milli_bpm);
+    }
+
+    /// Inserts a length of song time as silence at a tick, moving every later event.
+    ///
+    /// The silence gets tempo events of its own, so it lasts exactly that long and the tempo
+    /// after it is unchanged. Otherwise behaves like `insert_ticks`.
+    ///
+    /// # Arguments
+    ///
+    /// * `at`: the tick to insert silence at; events at this tick move.
+    /// * `millis`: the length of the silence in milliseconds.
+    pub fn insert_millis(&mut self, at: u32, millis: u32) {
+        self.insert_micros(at, u64::from(millis) * 1000);
+    }
+
+    fn insert_micros(&mut self, at: u32, micros: u64) {
+        if micros == 0 {
+            return;
+        }
+        let beats = (micros / MIN_GAP_BEAT_MICROS).max(1);
+        let resolution = self.get_resolution().max(1);
+        let ticks = u32::try_from(beats * u64::from(resolution)).unwrap_or(u32::MAX);
+        let milli_bpm = (60_000_000_000 * beats + micros / 2) / micros;
+        self.insert_gap(at, ticks, milli_bpm.max(1));
+    }
+
+    fn insert_gap(&mut self, at: u32, ticks: u32, milli_bpm: u64) {
+        if ticks == 0 {
+            return;
+        }
+        let resolution = self.get_resolution().max(1);
+        let in_effect = InEffect::at(self.get_tempo_map(), at);
+        #[allow(clippy::cast_precision_loss)]
+        let gap_micros =
+            f64::from(ticks) * 60_000_000_000.0 / (f64::from(resolution) * milli_bpm as f64);
+        let after = at.saturating_add(ticks);
+
+        let tempo_map = self.get_tempo_map_mut();
+        for event in tempo_map.iter_mut() {
+            if event.get_timestamp() >= at {
+                event.set_timestamp(event.get_timestamp().saturating_add(ticks));
+                shift_anchor(event, gap_micros);
+            }
+        }
+        if milli_bpm != in_effect.milli_bpm || in_effect.beat == Some(at) {
+            tempo_map.push(TempoEvent::Beat {
+                timestamp: at,
+                milli_bpm,
+            });
+        }
+        if milli_bpm != in_effect.milli_bpm && !has_event_at(tempo_map, after, true) {
+            tempo_map.push(TempoEvent::Beat {
+                timestamp: after,
+                milli_bpm: in_effect.milli_bpm,
+            });
+        }
+        if in_effect.signature == Some(at) {
+            tempo_map.push(TempoEvent::TimeSignature {
+                timestamp: at,
+                time_signature: in_effect.time_signature,
+            });
+        }
+        if !ticks.is_multiple_of(in_effect.ticks_per_measure(resolution))
+            && !has_event_at(tempo_map, after, false)
+        {
+            tempo_map.push(TempoEvent::TimeSignature {
+                timestamp: after,
+                time_signature: in_effect.time_signature,
+            });
+        }
+        tempo_map.sort_by_key(|event| (event.get_timestamp(), event.write_rank()));
+
+        for event in self.get_lyrics_mut() {
+            if event.get_timestamp() >= at {
+                event.set_timestamp(event.get_timestamp().saturating_add(ticks));
+            }
+        }
+        for event in self.get_key_presses_mut().values_mut().flatten() {
+            let timestamp = event.get_timestamp();
+            match event {
+                KeyPressEvent::Note { duration, .. } | KeyPressEvent::Special { duration, .. }
+                    if timestamp < at && timestamp.saturating_add(*duration) > at =>
+                {
+                    *duration = duration.saturating_add(ticks);
+                }
+                _ if timestamp >= at => event.set_timestamp(timestamp.saturating_add(ticks)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Deletes the events in a range of ticks and moves every later event back by its length.
+    ///
+    /// The tempo and time signature in effect at the end of the range take effect at its
+    /// start, so later events keep their tempo; when the range is not a whole number of
+    /// measures, the time signature is repeated at its start so bar lines start again there.
+    /// Sustains and special spans reaching into the range are cut at its start, those held
+    /// over all of it are shortened by its length, and special spans starting in it that go on
+    /// after it start again at its start. Phrases ending in the range are closed at
+    /// its start and phrases starting in it that go on after it are opened there.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: the first tick to delete.
+    /// * `end`: the tick after the last one to delete, which moves to `start`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let mut chart = Chart::new(&file_content).unwrap();
+    /// let original = chart.clone();
+    /// chart.insert_ticks(7680, 1536);
+    /// chart.delete_range(7680, 9216);
+    /// assert_eq!(chart, original);
+    /// ```
+    pub fn delete_range(&mut self, start: u32, end: u32) {
+        if end <= start {
+            return;
+        }
+        let length = end - start;
+        let resolution = self.get_resolution().max(1);
+        let timing = TempoMap::from_chart(self);
+        let removed_micros = timing.ticks_to_micros_f64(end) - timing.ticks_to_micros_f64(start);
+        let at_start = InEffect::at(self.get_tempo_map(), start);
+        let at_end = InEffect::at(self.get_tempo_map(), end);
+        let in_range = |timestamp: u32| (start..end).contains(&timestamp);
+
+        let tempo_map = self.get_tempo_map_mut();
+        tempo_map.retain(|event| !in_range(event.get_timestamp()));
+        for event in tempo_map.iter_mut() {
+            if event.get_timestamp() >= end {
+                event.set_timestamp(event.get_timestamp() - length);
+                shift_anchor(event, -removed_micros);
+            }
+        }
+        if at_end.beat.is_some_and(in_range) {
+            tempo_map.push(TempoEvent::Beat {
+                timestamp: start,
+                milli_bpm: at_end.milli_bpm,
+            });
+        }
+        if (at_end.signature.is_some_and(in_range)
+            || !length.is_multiple_of(at_start.ticks_per_measure(resolution)))
+            && !has_event_at(tempo_map, start, false)
+        {
+            tempo_map.push(TempoEvent::TimeSignature {
+                timestamp: start,
+                time_signature: at_end.time_signature,
+            });
+        }
+        tempo_map.sort_by_key(|event| (event.get_timestamp(), event.write_rank()));
+
+        let lyrics = self.get_lyrics_mut();
+        let mut markers = vec![];
+        for duet in [false, true] {
+            let crossed = lyrics.iter().any(|event| {
+                in_range(event.get_timestamp())
+                    && phrase_marker(event).is_some_and(|(marker_duet, _)| marker_duet == duet)
+            });
+            if !crossed {
+                continue;
+            }
+            if phrase_open_before(lyrics, start, duet) {
+                markers.push(if duet {
+                    LyricEvent::DuetPhraseEnd { timestamp: start }
+                } else {
+                    LyricEvent::PhraseEnd { timestamp: start }
+                });
+            }
+            if phrase_open_before(lyrics, end, duet) {
+                markers.push(if duet {
+                    LyricEvent::DuetPhraseStart { timestamp: start }
+                } else {
+                    LyricEvent::PhraseStart { timestamp: start }
+                });
+            }
+        }
+        // ends before starts, so a phrase closed at the cut does not swallow the next one
+        markers.sort_by_key(|event| phrase_marker(event).map(|(_, start)| start));
+        let later = lyrics
+            .iter()
+            .position(|event| event.get_timestamp() >= start)
+            .unwrap_or(lyrics.len());
+        lyrics.retain(|event| !in_range(event.get_timestamp()));
+        for event in lyrics.iter_mut() {
+            if event.get_timestamp() >= end {
+                event.set_timestamp(event.get_timestamp() - length);
+            }
+        }
+        lyrics.splice(later..later, markers);
+
+        for events in self.get_key_presses_mut().values_mut() {
+            for event in events.iter_mut() {
+                if let KeyPressEvent::Special {
+                    timestamp,
+                    duration,
+                    ..
+                } = event
+                {
+                    if in_range(*timestamp) && timestamp.saturating_add(*duration) > end {
+                        *duration = timestamp.saturating_add(*duration) - end;
+                        *timestamp = end;
+                    }
+                }
+            }
+            events.retain(|event| !in_range(event.get_timestamp()));
+            for event in events.iter_mut() {
+                let timestamp = event.get_timestamp();
+                match event {
+                    KeyPressEvent::Note { duration, .. }
+                    | KeyPressEvent::Special { duration, .. }
+                        if timestamp < start && timestamp.saturating_add(*duration) > start =>
+                    {
+                        *duration = if timestamp.saturating_add(*duration) > end {
+                            *duration - length
+                        } else {
+                            start - timestamp
+                        };
+                    }
+                    _ if timestamp >= end => event.set_timestamp(timestamp - length),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Cuts a range of ticks out into a standalone chart, such as a practice excerpt.
+    ///
+    /// The tempo and time signature in effect at `start` are moved to tick 0, and anchors are
+    /// moved to keep their distance to it. Phrases are cut at the range boundaries and closed
+    /// at its end, the section in effect at `start` is repeated at tick 0, notes outside the
+    /// range are dropped, and sustains and special spans crossing its boundaries are truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: the first tick of the excerpt, which becomes tick 0.
+    /// * `end`: the tick after the last one of the excerpt.
+    ///
+    /// returns: the excerpt, with the same properties as this chart.
+    #[must_use]
+    pub fn slice(&self, start: u32, end: u32) -> Self {
+        let end = end.max(start);
+        let mut chart = self.clone();
+        let section = self
+            .get_lyrics()
+            .iter()
+            .filter_map(|event| match event {
+                LyricEvent::Section { timestamp, text } if *timestamp < start => {
+                    Some((*timestamp, text.clone()))
+                }
+                _ => None,
+            })
+            .max_by_key(|(timestamp, _)| *timestamp);
+
+        chart.delete_range(end, u32::MAX);
+        chart
+            .get_tempo_map_mut()
+            .retain(|event| event.get_timestamp() < end);
+        // deleting the start would drop sustains ringing into the excerpt, so clip them first
+        for events in chart.get_key_presses_mut().values_mut() {
+            for event in events.iter_mut() {
+                if let KeyPressEvent::Note {
+                    timestamp,
+                    duration,
+                    ..
+                } = event
+                {
+                    if *timestamp < start && timestamp.saturating_add(*duration) > start {
+                        *duration = timestamp.saturating_add(*duration) - start;
+                        *timestamp = start;
+                    }
+                }
+            }
+        }
+        chart.delete_range(0, start);
+
+        let lyrics = chart.get_lyrics_mut();
+        if let Some((_, text)) = section {
+            if !lyrics
+                .iter()
+                .any(|event| matches!(event, LyricEvent::Section { timestamp: 0, .. }))
+            {
+                lyrics.insert(0, LyricEvent::Section { timestamp: 0, text });
+            }
+        }
+        for duet in [false, true] {
+            if phrase_open_before(lyrics, u32::MAX, duet) {
+                let timestamp = end - start;
+                lyrics.push(if duet {
+                    LyricEvent::DuetPhraseEnd { timestamp }
+                } else {
+                    LyricEvent::PhraseEnd { timestamp }
+                });
+            }
+        }
+        chart
+    }
+
+    /// Cuts the first section with the given name out into a standalone chart, see `slice`.
+    ///
+    /// The section runs up to the next section event, or for the last section up to the end of
+    /// the last event of the chart.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the section, such as `Chorus 2`.
+    ///
+    /// returns: the excerpt, or `None` if the chart has no section with that name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::{Chart, LyricEvent};
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let chorus = chart.slice_section("Chorus 2").unwrap();
+    /// assert!(chorus
+    ///     .get_lyrics()
+    ///     .contains(&LyricEvent::Section { timestamp: 0, text: "Chorus 2".to_string() }));
+    /// assert!(chart.slice_section("Bridge 9").is_none());
+    /// ```
+    #[must_use]
+    pub fn slice_section(&self, name: &str) -> Option<Self> {
+        let mut sections: Vec<(u32, &str)> = self
+            .get_lyrics()
+            .iter()
+            .filter_map(|event| match event {
+                LyricEvent::Section { timestamp, text } => Some((*timestamp, text.as_str())),
+                _ => None,
+            })
+            .collect();
+        sections.sort_by_key(|(timestamp, _)| *timestamp);
+        let start = sections
+            .iter()
+            .find(|(_, text)| *text == name)
+            .map(|(timestamp, _)| *timestamp)?;
+        let end = sections
+            .iter()
+            .map(|(timestamp, _)| *timestamp)
+            .find(|timestamp| *timestamp > start)
+            .unwrap_or_else(|| self.last_tick().saturating_add(1));
+        Some(self.slice(start, end))
+    }
+
+    /// The last tick any event of the chart reaches, including the ends of sustains.
+    fn last_tick(&self) -> u32 {
+        let key_presses = self
+            .get_key_presses()
+            .values()
+            .flatten()
+            .map(|event| match event {
+                KeyPressEvent::Note {
+                    timestamp,
+                    duration,
+                    ..
+                }
+                | KeyPressEvent::Special {
+                    timestamp,
+                    duration,
+                    ..
+                } => timestamp.saturating_add(*duration),
+                _ => event.get_timestamp(),
+            });
+        self.get_lyrics()
+            .iter()
+            .map(TimestampedEvent::get_timestamp)
+            .chain(
+                self.get_tempo_map()
+                    .iter()
+                    .map(TimestampedEvent::get_timestamp),
+            )
+            .chain(key_presses)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use crate::test_util::tempo_chart;
+
+    use super::*;
+
+    fn chart() -> Chart {
+        tempo_chart(
+            vec![
+                LyricEvent::PhraseStart { timestamp: 0 },
+                LyricEvent::Lyric {
+                    timestamp: 0,
+                    text: "one".to_string(),
+                },
+                LyricEvent::Lyric {
+                    timestamp: 384,
+                    text: "two".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 768 },
+                LyricEvent::PhraseStart { timestamp: 960 },
+                LyricEvent::Lyric {
+                    timestamp: 1152,
+                    text: "three".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 1536 },
+            ],
+            vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+                TempoEvent::Beat {
+                    timestamp: 768,
+                    milli_bpm: 60_000,
+                },
+            ],
+            vec![
+                KeyPressEvent::Note {
+                    timestamp: 192,
+                    duration: 384,
+                    key: 0,
+                },
+                KeyPressEvent::Special {
+                    timestamp: 0,
+                    special_type: 2,
+                    duration: 1536,
+                },
+                KeyPressEvent::Note {
+                    timestamp: 1152,
+                    duration: 0,
+                    key: 1,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn insert_ticks() {
+        let mut chart = chart();
+        chart.insert_ticks(384, 96);
+        assert_eq!(
+            chart.get_tempo_map(),
+            &vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+                TempoEvent::TimeSignature {
+                    timestamp: 480,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 864,
+                    milli_bpm: 60_000,
+                },
+            ]
+        );
+        assert_eq!(chart.get_lyrics()[2].get_timestamp(), 480);
+        assert_eq!(
+            chart.get_key_presses()["ExpertSingle"][0],
+            KeyPressEvent::Note {
+                timestamp: 192,
+                duration: 480,
+                key: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn insert_millis() {
+        let mut chart = chart();
+        let before = TempoMap::from_chart(&chart).ticks_to_micros(1152);
+        chart.insert_millis(768, 750);
+        let timing = TempoMap::from_chart(&chart);
+        assert_eq!(timing.ticks_to_micros(1152 + 192), before + 750_000);
+        assert_eq!(timing.milli_bpm_at(768), 80_000);
+        assert_eq!(timing.milli_bpm_at(1152), 60_000);
+    }
+
+    #[test]
+    fn delete_range() {
+        let mut chart = chart();
+        chart.delete_range(384, 1152);
+        assert_eq!(
+            chart.get_tempo_map(),
+            &vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+                TempoEvent::Beat {
+                    timestamp: 384,
+                    milli_bpm: 60_000,
+                },
+            ]
+        );
+        assert_eq!(
+            chart.get_lyrics(),
+            &vec![
+                LyricEvent::PhraseStart { timestamp: 0 },
+                LyricEvent::Lyric {
+                    timestamp: 0,
+                    text: "one".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 384 },
+                LyricEvent::PhraseStart { timestamp: 384 },
+                LyricEvent::Lyric {
+                    timestamp: 384,
+                    text: "three".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 768 },
+            ]
+        );
+        assert_eq!(
+            chart.get_key_presses()["ExpertSingle"],
+            vec![
+                KeyPressEvent::Note {
+                    timestamp: 192,
+                    duration: 192,
+                    key: 0,
+                },
+                KeyPressEvent::Special {
+                    timestamp: 0,
+                    special_type: 2,
+                    duration: 768,
+                },
+                KeyPressEvent::Note {
+                    timestamp: 384,
+                    duration: 0,
+                    key: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_range_keeps_special_spans() {
+        let mut chart = chart();
+        chart.delete_range(0, 384);
+        assert!(chart.get_key_presses()["ExpertSingle"].contains(&KeyPressEvent::Special {
+            timestamp: 0,
+            special_type: 2,
+            duration: 1152,
+        }));
+    }
+
+    #[test]
+    fn shift_millis() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            shift_millis_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn lyric_micros(chart: &Chart) -> Vec<i64> {
+        let timing = TempoMap::from_chart(chart);
+        chart
+            .get_lyrics()
+            .iter()
+            .filter(|event| matches!(event, LyricEvent::Lyric { .. }))
+            .map(|event| i64::try_from(timing.ticks_to_micros(event.get_timestamp())).unwrap())
+            .collect()
+    }
+
+    fn shift_millis_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let original = lyric_micros(&chart);
+
+        let mut later = chart.clone();
+        later.shift_millis(1234);
+        for (before, after) in original.iter().zip(lyric_micros(&later)) {
+            assert!(
+                (after - before - 1_234_000).abs() <= 100,
+                "{before} {after}"
+            );
+        }
+
+        let mut earlier = later.clone();
+        earlier.shift_millis(-1234);
+        for (before, after) in original.iter().zip(lyric_micros(&earlier)) {
+            assert!((after - before).abs() <= 100, "{before} {after}");
+        }
+        assert_eq!(
+            earlier.get_key_presses().len(),
+            chart.get_key_presses().len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn slice() {
+        let excerpt = chart().slice(384, 1344);
+        assert_eq!(
+            excerpt.get_tempo_map(),
+            &vec![
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+                TempoEvent::Beat {
+                    timestamp: 384,
+                    milli_bpm: 60_000,
+                },
+            ]
+        );
+        assert_eq!(
+            excerpt.get_lyrics(),
+            &vec![
+                LyricEvent::PhraseStart { timestamp: 0 },
+                LyricEvent::Lyric {
+                    timestamp: 0,
+                    text: "two".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 384 },
+                LyricEvent::PhraseStart { timestamp: 576 },
+                LyricEvent::Lyric {
+                    timestamp: 768,
+                    text: "three".to_string(),
+                },
+                LyricEvent::PhraseEnd { timestamp: 960 },
+            ]
+        );
+        assert_eq!(
+            excerpt.get_key_presses()["ExpertSingle"],
+            vec![
+                KeyPressEvent::Note {
+                    timestamp: 0,
+                    duration: 192,
+                    key: 0,
+                },
+                KeyPressEvent::Special {
+                    timestamp: 0,
+                    special_type: 2,
+                    duration: 960,
+                },
+                KeyPressEvent::Note {
+                    timestamp: 768,
+                    duration: 0,
+                    key: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn slice_clips_sustain_crossing_start() {
+        let excerpt = chart().slice(288, 1344);
+        assert_eq!(
+            excerpt.get_key_presses()["ExpertSingle"][0],
+            KeyPressEvent::Note {
+                timestamp: 0,
+                duration: 288,
+                key: 0,
+            }
+        );
+        let excerpt = chart().slice(576, 1344);
+        assert!(!excerpt.get_key_presses()["ExpertSingle"]
+            .iter()
+            .any(|event| matches!(event, KeyPressEvent::Note { key: 0, .. })));
+    }
+
+    #[test]
+    fn slice_sections() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            slice_sections_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn slice_sections_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let timing = TempoMap::from_chart(&chart);
+        let original = lyric_micros(&chart);
+        let mut section_starts: Vec<u32> = chart
+            .get_lyrics()
+            .iter()
+            .filter(|event| matches!(event, LyricEvent::Section { .. }))
+            .map(TimestampedEvent::get_timestamp)
+            .collect();
+        section_starts.sort_unstable();
+        let mut seen: Vec<&str> = vec![];
+        for event in chart.get_lyrics() {
+            let LyricEvent::Section { timestamp, text } = event else {
+                continue;
+            };
+            if seen.contains(&text.as_str()) {
+                continue;
+            }
+            seen.push(text);
+            let end = section_starts
+                .iter()
+                .copied()
+                .find(|start| start > timestamp)
+                .unwrap_or_else(|| chart.last_tick().saturating_add(1));
+            let length = end - timestamp;
+            let excerpt = chart.slice_section(text).unwrap();
+            let offset = i64::try_from(timing.ticks_to_micros(*timestamp))?;
+            let sliced = lyric_micros(&excerpt);
+            let first = original
+                .iter()
+                .position(|micros| *micros >= offset)
+                .unwrap_or(original.len());
+            for (before, after) in original[first..].iter().zip(&sliced) {
+                assert!((before - offset - after).abs() <= 2, "{before} {after}");
+            }
+            assert!(excerpt
+                .get_key_presses()
+                .values()
+                .flatten()
+                .all(|event| match event {
+                    KeyPressEvent::Note {
+                        timestamp,
+                        duration,
+                        ..
+                    }
+                    | KeyPressEvent::Special {
+                        timestamp,
+                        duration,
+                        ..
+                    } => timestamp + duration <= length,
+                    _ => true,
+                }));
+            assert!(excerpt
+                .get_key_presses()
+                .values()
+                .flatten()
+                .all(|event| event.get_timestamp() < length));
+        }
+        Ok(())
+    }
+}
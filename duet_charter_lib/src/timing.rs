@@ -0,0 +1,347 @@
+use crate::chart::{Chart, TempoEvent};
+
+/// Tempo used before the first `B` event, as Clone Hero does.
+pub(crate) const DEFAULT_MILLI_BPM: u64 = 120_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TempoSegment {
+    timestamp: u32,
+    microseconds: f64,
+    milli_bpm: u64,
+}
+
+/// Time signature used before the first `TS` event.
+pub(crate) const DEFAULT_TIME_SIGNATURE: (u32, u32) = (4, 4);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MeasureSegment {
+    timestamp: u32,
+    measure: f64,
+    ticks_per_measure: u32,
+}
+
+/// Converts between chart ticks and song time using a chart's `B` events and resolution, and
+/// counts measures using its `TS` events.
+///
+/// Anchors are not needed for the conversion, as the tempo events around them already place
+/// the anchored beats at their audio time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    resolution: u32,
+    segments: Vec<TempoSegment>,
+    measures: Vec<MeasureSegment>,
+}
+
+impl TempoMap {
+    /// Constructor for `TempoMap` from the tempo events of a chart.
+    ///
+    /// # Arguments
+    ///
+    /// * `tempo_events`: the chart's `SyncTrack` events, in any order.
+    /// * `resolution`: the number of ticks per beat.
+    ///
+    /// returns: `TempoMap`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::chart::TempoEvent;
+    /// use duet_charter_lib::timing::TempoMap;
+    ///
+    /// let tempo_map = TempoMap::new(&[TempoEvent::Beat { timestamp: 0, milli_bpm: 120_000 }], 192);
+    /// assert_eq!(tempo_map.ticks_to_micros(384), 1_000_000);
+    /// assert_eq!(tempo_map.micros_to_ticks(1_000_000), 384);
+    /// ```
+    #[must_use]
+    pub fn new(tempo_events: &[TempoEvent], resolution: u32) -> Self {
+        let mut beats: Vec<(u32, u64)> = tempo_events
+            .iter()
+            .filter_map(|event| match event {
+                TempoEvent::Beat {
+                    timestamp,
+                    milli_bpm,
+                } if *milli_bpm > 0 => Some((*timestamp, *milli_bpm)),
+                _ => None,
+            })
+            .collect();
+        beats.sort_by_key(|(timestamp, _)| *timestamp);
+        if beats.first().is_none_or(|(timestamp, _)| *timestamp > 0) {
+            beats.insert(0, (0, DEFAULT_MILLI_BPM));
+        }
+
+        let resolution = resolution.max(1);
+        let mut segments: Vec<TempoSegment> = Vec::with_capacity(beats.len());
+        for (timestamp, milli_bpm) in beats {
+            let microseconds = segments.last().map_or(0.0, |previous| {
+                previous.microseconds
+                    + Self::micros_per_tick(previous.milli_bpm, resolution)
+                        * f64::from(timestamp - previous.timestamp)
+            });
+            if segments
+                .last()
+                .is_some_and(|previous| previous.timestamp == timestamp)
+            {
+                segments.pop();
+            }
+            segments.push(TempoSegment {
+                timestamp,
+                microseconds,
+                milli_bpm,
+            });
+        }
+
+        let mut signatures: Vec<(u32, (u32, u32))> = tempo_events
+            .iter()
+            .filter_map(|event| match event {
+                TempoEvent::TimeSignature {
+                    timestamp,
+                    time_signature,
+                } if time_signature.0 > 0 && time_signature.1 > 0 => {
+                    Some((*timestamp, *time_signature))
+                }
+                _ => None,
+            })
+            .collect();
+        signatures.sort_by_key(|(timestamp, _)| *timestamp);
+        if signatures.first().is_none_or(|(timestamp, _)| *timestamp > 0) {
+            signatures.insert(0, (0, DEFAULT_TIME_SIGNATURE));
+        }
+        let mut measures: Vec<MeasureSegment> = Vec::with_capacity(signatures.len());
+        for (timestamp, (numerator, denominator)) in signatures {
+            let measure = measures.last().map_or(0.0, |previous| {
+                previous.measure
+                    + f64::from(timestamp - previous.timestamp)
+                        / f64::from(previous.ticks_per_measure)
+            });
+            if measures
+                .last()
+                .is_some_and(|previous| previous.timestamp == timestamp)
+            {
+                measures.pop();
+            }
+            measures.push(MeasureSegment {
+                timestamp,
+                measure,
+                ticks_per_measure: (numerator * resolution * 4 / denominator).max(1),
+            });
+        }
+        Self {
+            resolution,
+            segments,
+            measures,
+        }
+    }
+
+    /// Constructor for `TempoMap` from a chart's tempo map and `Resolution` property.
+    #[must_use]
+    pub fn from_chart(chart: &Chart) -> Self {
+        Self::new(chart.get_tempo_map(), chart.get_resolution())
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn micros_per_tick(milli_bpm: u64, resolution: u32) -> f64 {
+        60_000_000_000.0 / (milli_bpm as f64 * f64::from(resolution))
+    }
+
+    fn segment_at_tick(&self, timestamp: u32) -> &TempoSegment {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.timestamp <= timestamp);
+        &self.segments[index.saturating_sub(1)]
+    }
+
+    #[must_use]
+    pub const fn get_resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Tempo in thousandths of a beat per minute in effect at the given tick.
+    #[must_use]
+    pub fn milli_bpm_at(&self, timestamp: u32) -> u64 {
+        self.segment_at_tick(timestamp).milli_bpm
+    }
+
+    /// Slowest and fastest tempo of the chart, in thousandths of a beat per minute.
+    #[must_use]
+    pub fn milli_bpm_range(&self) -> (u64, u64) {
+        let tempos = self.segments.iter().map(|segment| segment.milli_bpm);
+        (
+            tempos.clone().min().unwrap_or(DEFAULT_MILLI_BPM),
+            tempos.max().unwrap_or(DEFAULT_MILLI_BPM),
+        )
+    }
+
+    fn measure_segment_at_tick(&self, timestamp: u32) -> &MeasureSegment {
+        let index = self
+            .measures
+            .partition_point(|segment| segment.timestamp <= timestamp);
+        &self.measures[index.saturating_sub(1)]
+    }
+
+    /// Length of a measure under the time signature in effect at the given tick.
+    #[must_use]
+    pub fn ticks_per_measure_at(&self, timestamp: u32) -> u32 {
+        self.measure_segment_at_tick(timestamp).ticks_per_measure
+    }
+
+    /// The ticks the time signature changes at, ascending and starting at 0.
+    #[must_use]
+    pub fn time_signature_timestamps(&self) -> Vec<u32> {
+        self.measures
+            .iter()
+            .map(|segment| segment.timestamp)
+            .collect()
+    }
+
+    /// Number of measures from the start of the chart to a tick, counting the fraction of the
+    /// current measure; the first measure starts at 0.
+    #[must_use]
+    pub fn measure_at(&self, timestamp: u32) -> f64 {
+        let segment = self.measure_segment_at_tick(timestamp);
+        segment.measure
+            + f64::from(timestamp - segment.timestamp) / f64::from(segment.ticks_per_measure)
+    }
+
+    /// Song time of a tick in fractional microseconds.
+    #[must_use]
+    pub fn ticks_to_micros_f64(&self, timestamp: u32) -> f64 {
+        let segment = self.segment_at_tick(timestamp);
+        segment.microseconds
+            + Self::micros_per_tick(segment.milli_bpm, self.resolution)
+                * f64::from(timestamp - segment.timestamp)
+    }
+
+    /// Song time of a tick, rounded to the nearest microsecond.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn ticks_to_micros(&self, timestamp: u32) -> u64 {
+        self.ticks_to_micros_f64(timestamp).round() as u64
+    }
+
+    /// Song time of a tick in seconds.
+    #[must_use]
+    pub fn ticks_to_seconds(&self, timestamp: u32) -> f64 {
+        self.ticks_to_micros_f64(timestamp) / 1_000_000.0
+    }
+
+    /// Tick closest to the given song time in microseconds.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub fn micros_to_ticks(&self, microseconds: u64) -> u32 {
+        let microseconds = microseconds as f64;
+        let index = self
+            .segments
+            .partition_point(|segment| segment.microseconds <= microseconds);
+        let segment = &self.segments[index.saturating_sub(1)];
+        let ticks = (microseconds - segment.microseconds)
+            / Self::micros_per_tick(segment.milli_bpm, self.resolution);
+        (f64::from(segment.timestamp) + ticks.round()).min(f64::from(u32::MAX)) as u32
+    }
+
+    /// Tick closest to the given song time in seconds, negative times map to tick 0.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn seconds_to_ticks(&self, seconds: f64) -> u32 {
+        self.micros_to_ticks((seconds.max(0.0) * 1_000_000.0).round() as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use eyre::{Result, WrapErr};
+
+    use super::*;
+
+    #[test]
+    fn tick_round_trip() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            tick_round_trip_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn tick_round_trip_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let tempo_map = TempoMap::from_chart(&chart);
+        for event in chart.get_tempo_map() {
+            let timestamp = crate::TimestampedEvent::get_timestamp(event);
+            assert_eq!(
+                tempo_map.micros_to_ticks(tempo_map.ticks_to_micros(timestamp)),
+                timestamp
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tempo_changes() {
+        let tempo_map = TempoMap::new(
+            &[
+                TempoEvent::Beat {
+                    timestamp: 0,
+                    milli_bpm: 120_000,
+                },
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::Beat {
+                    timestamp: 384,
+                    milli_bpm: 60_000,
+                },
+            ],
+            192,
+        );
+        assert_eq!(tempo_map.ticks_to_micros(192), 500_000);
+        assert_eq!(tempo_map.ticks_to_micros(384), 1_000_000);
+        assert_eq!(tempo_map.ticks_to_micros(576), 2_000_000);
+        assert_eq!(tempo_map.micros_to_ticks(1_500_000), 480);
+        assert_eq!(tempo_map.milli_bpm_at(400), 60_000);
+        assert_eq!(tempo_map.milli_bpm_range(), (60_000, 120_000));
+    }
+
+    #[test]
+    fn measures() {
+        let tempo_map = TempoMap::new(
+            &[
+                TempoEvent::TimeSignature {
+                    timestamp: 0,
+                    time_signature: (4, 4),
+                },
+                TempoEvent::TimeSignature {
+                    timestamp: 1536,
+                    time_signature: (6, 8),
+                },
+            ],
+            192,
+        );
+        assert_eq!(tempo_map.ticks_per_measure_at(0), 768);
+        assert_eq!(tempo_map.ticks_per_measure_at(1536), 576);
+        assert!((tempo_map.measure_at(1152) - 1.5).abs() < 1e-9);
+        assert!((tempo_map.measure_at(1824) - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missing_tempo_defaults() {
+        let tempo_map = TempoMap::new(&[], 480);
+        assert_eq!(tempo_map.ticks_to_micros(480), 500_000);
+    }
+}
@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::chart::{Chart, LyricEvent, TempoEvent};
+use crate::phrases::{LyricPhraseCollection, Singer};
+use crate::timing::TempoMap;
+use crate::TimestampedEvent;
+
+/// The kind of an UltraStar note, given by the marker at the start of its line.
+///
+/// Charts have no golden notes, so `Golden` imports as a normal syllable. Rap notes are marked
+/// with the unpitched `#` lyric suffix and freestyle notes with the `^` suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UltraStarNoteType {
+    Normal,
+    Golden,
+    Freestyle,
+    Rap,
+    GoldenRap,
+}
+
+impl UltraStarNoteType {
+    fn from_marker(marker: &str) -> Option<Self> {
+        match marker {
+            ":" => Some(Self::Normal),
+            "*" => Some(Self::Golden),
+            "F" => Some(Self::Freestyle),
+            "R" => Some(Self::Rap),
+            "G" => Some(Self::GoldenRap),
+            _ => None,
+        }
+    }
+
+    const fn marker(self) -> char {
+        match self {
+            Self::Normal => ':',
+            Self::Golden => '*',
+            Self::Freestyle => 'F',
+            Self::Rap => 'R',
+            Self::GoldenRap => 'G',
+        }
+    }
+
+    const fn lyric_suffix(self) -> &'static str {
+        match self {
+            Self::Normal | Self::Golden => "",
+            Self::Freestyle => "^",
+            Self::Rap | Self::GoldenRap => "#",
+        }
+    }
+}
+
+/// A single syllable of an UltraStar song.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UltraStarNote {
+    note_type: UltraStarNoteType,
+    start_beat: u32,
+    length: u32,
+    pitch: i32,
+    /// The syllable as written in the file, where a leading or trailing space separates words.
+    text: String,
+}
+
+impl UltraStarNote {
+    #[must_use]
+    pub const fn get_note_type(&self) -> UltraStarNoteType {
+        self.note_type
+    }
+
+    #[must_use]
+    pub const fn get_start_beat(&self) -> u32 {
+        self.start_beat
+    }
+
+    #[must_use]
+    pub const fn get_length(&self) -> u32 {
+        self.length
+    }
+
+    #[must_use]
+    pub const fn get_pitch(&self) -> i32 {
+        self.pitch
+    }
+
+    #[must_use]
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    const fn get_end_beat(&self) -> u32 {
+        self.start_beat + self.length
+    }
+}
+
+/// The notes between two `-` line breaks, sung by one singer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UltraStarLine {
+    singer: Singer,
+    notes: Vec<UltraStarNote>,
+}
+
+impl UltraStarLine {
+    #[must_use]
+    pub const fn get_singer(&self) -> Singer {
+        self.singer
+    }
+
+    #[must_use]
+    pub const fn get_notes(&self) -> &Vec<UltraStarNote> {
+        &self.notes
+    }
+}
+
+/// A song in the UltraStar .txt format.
+///
+/// UltraStar beats are a quarter of a beat of the `#BPM` tempo, so a chart using the same tempo
+/// fits four UltraStar beats in `Resolution` ticks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UltraStarSong {
+    tags: Vec<(String, String)>,
+    milli_bpm: u64,
+    gap_micros: u64,
+    lines: Vec<UltraStarLine>,
+}
+
+/// Converts UltraStar beats to chart ticks at the given resolution.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn beats_to_ticks(beats: u32, resolution: u32) -> u32 {
+    ((beats as u64 * resolution as u64 + 2) / 4) as u32
+}
+
+/// Converts chart ticks to the nearest UltraStar beat at the given resolution.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn ticks_to_beats(ticks: u32, resolution: u32) -> u32 {
+    ((ticks as u64 * 4 * 2 + resolution as u64) / (resolution as u64 * 2)) as u32
+}
+
+impl UltraStarSong {
+    /// Creates an `UltraStarSong` struct by parsing the contents of an UltraStar .txt file.
+    ///
+    /// Both absolute and `#RELATIVE:yes` beats are read. `P1` marks the following lines as main
+    /// and `P2` as duet, `P3`, sung by both, is read as main.
+    ///
+    /// # Arguments
+    ///
+    /// * `txt_file`: the contents of the .txt file to parse.
+    ///
+    /// returns: `Result<UltraStarSong, eyre::Report>`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the `#BPM` tag is missing, or if a tag value, note or line break
+    /// cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use duet_charter_lib::ultrastar::UltraStarSong;
+    ///
+    /// let song = UltraStarSong::new("#TITLE:Test\n#BPM:300\n#GAP:1000\n: 0 2 5 Hel\n: 2 2 5 lo\nE\n").unwrap();
+    /// let chart = song.to_chart(192);
+    /// assert_eq!(chart.get_lyrics().len(), 4);
+    /// ```
+    pub fn new(txt_file: &str) -> Result<Self> {
+        let mut tags = vec![];
+        let mut milli_bpm = None;
+        let mut gap_micros = 0;
+        let mut relative = false;
+        let mut offset = 0;
+        let mut singer = Singer::Main;
+        let mut lines: Vec<UltraStarLine> = vec![];
+        let mut current: Vec<UltraStarNote> = vec![];
+
+        for (number, raw_line) in txt_file.lines().enumerate() {
+            let raw_line = raw_line
+                .trim_start_matches('\u{feff}')
+                .trim_end_matches('\r');
+            let context = || format!("Could not read line {}: {}", number + 1, raw_line);
+            if let Some(tag) = raw_line.strip_prefix('#') {
+                let (name, value) = tag.split_once(':').ok_or_else(|| eyre!(context()))?;
+                let value = value.trim();
+                match name.trim().to_uppercase().as_str() {
+                    "BPM" => {
+                        milli_bpm = Some(Self::parse_thousandths(value).wrap_err_with(context)?)
+                    }
+                    "GAP" => gap_micros = Self::parse_thousandths(value).wrap_err_with(context)?,
+                    "RELATIVE" => relative = value.eq_ignore_ascii_case("yes"),
+                    name => tags.push((name.to_string(), value.to_string())),
+                }
+                continue;
+            }
+            let trimmed = raw_line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed == "E" {
+                break;
+            }
+            if let Some(player) = trimmed.strip_prefix('P') {
+                Self::close_line(&mut lines, &mut current, singer);
+                offset = 0;
+                singer = match player.trim() {
+                    "2" => Singer::Duet,
+                    "1" | "3" => Singer::Main,
+                    _ => return Err(eyre!(context())),
+                };
+                continue;
+            }
+            if let Some(line_break) = trimmed.strip_prefix('-') {
+                Self::close_line(&mut lines, &mut current, singer);
+                if relative {
+                    let beats: Vec<u32> = line_break
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .wrap_err_with(context)?;
+                    offset += beats.get(1).or_else(|| beats.first()).copied().unwrap_or(0);
+                }
+                continue;
+            }
+            let mut fields = raw_line.trim_start().splitn(5, ' ');
+            let note_type = fields
+                .next()
+                .and_then(UltraStarNoteType::from_marker)
+                .ok_or_else(|| eyre!(context()))?;
+            let mut number_field = || fields.next().ok_or_else(|| eyre!(context()));
+            let start_beat: u32 = number_field()?.parse().wrap_err_with(context)?;
+            let length = number_field()?.parse().wrap_err_with(context)?;
+            let pitch = number_field()?.parse().wrap_err_with(context)?;
+            current.push(UltraStarNote {
+                note_type,
+                start_beat: start_beat + offset,
+                length,
+                pitch,
+                text: fields.next().unwrap_or_default().to_string(),
+            });
+        }
+        Self::close_line(&mut lines, &mut current, singer);
+
+        Ok(Self {
+            tags,
+            milli_bpm: milli_bpm.ok_or_else(|| eyre!("No #BPM tag found"))?,
+            gap_micros,
+            lines,
+        })
+    }
+
+    fn close_line(lines: &mut Vec<UltraStarLine>, notes: &mut Vec<UltraStarNote>, singer: Singer) {
+        if !notes.is_empty() {
+            lines.push(UltraStarLine {
+                singer,
+                notes: std::mem::take(notes),
+            });
+        }
+    }
+
+    /// Reads a decimal number with either a `.` or a `,` separator, in thousandths.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn parse_thousandths(value: &str) -> Result<u64> {
+        let number: f64 = value.replace(',', ".").parse()?;
+        if number < 0.0 {
+            return Err(eyre!("Negative value {}", value));
+        }
+        Ok((number * 1000.0).round() as u64)
+    }
+
+    #[must_use]
+    pub const fn get_tags(&self) -> &Vec<(String, String)> {
+        &self.tags
+    }
+
+    /// The `#BPM` tag in thousandths of a beat per minute.
+    #[must_use]
+    pub const fn get_milli_bpm(&self) -> u64 {
+        self.milli_bpm
+    }
+
+    /// The `#GAP` tag, the song time of beat 0, in microseconds.
+    #[must_use]
+    pub const fn get_gap_micros(&self) -> u64 {
+        self.gap_micros
+    }
+
+    #[must_use]
+    pub const fn get_lines(&self) -> &Vec<UltraStarLine> {
+        &self.lines
+    }
+
+    fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Converts the song to a chart with a single tempo and the given resolution, with a phrase
+    /// per line running from its first note to the end of its last note.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn to_chart(&self, resolution: u32) -> Chart {
+        let resolution = resolution.max(1);
+        let gap_ticks =
+            ((u128::from(self.gap_micros) * u128::from(self.milli_bpm) * u128::from(resolution)
+                + 30_000_000_000)
+                / 60_000_000_000) as u32;
+        let ticks = |beats: u32| gap_ticks + beats_to_ticks(beats, resolution);
+
+        let mut lyrics = vec![];
+        for line in &self.lines {
+            let (Some(first), Some(last)) = (line.notes.first(), line.notes.last()) else {
+                continue;
+            };
+            let (start, end) = (ticks(first.start_beat), ticks(last.get_end_beat()));
+            lyrics.push(match line.singer {
+                Singer::Main => LyricEvent::PhraseStart { timestamp: start },
+                Singer::Duet => LyricEvent::DuetPhraseStart { timestamp: start },
+            });
+            for (i, note) in line.notes.iter().enumerate() {
+                let joined = line
+                    .notes
+                    .get(i + 1)
+                    .is_some_and(|next| !note.text.ends_with(' ') && !next.text.starts_with(' '));
+                let syllable = match note.text.trim() {
+                    "~" | "" => "+",
+                    syllable => syllable,
+                };
+                let text = format!(
+                    "{}{}{}",
+                    syllable,
+                    note.note_type.lyric_suffix(),
+                    if joined { "-" } else { "" }
+                );
+                let timestamp = ticks(note.start_beat);
+                lyrics.push(match line.singer {
+                    Singer::Main => LyricEvent::Lyric { timestamp, text },
+                    Singer::Duet => LyricEvent::DuetLyric { timestamp, text },
+                });
+            }
+            lyrics.push(match line.singer {
+                Singer::Main => LyricEvent::PhraseEnd { timestamp: end },
+                Singer::Duet => LyricEvent::DuetPhraseEnd { timestamp: end },
+            });
+        }
+        lyrics.sort_by_key(TimestampedEvent::get_timestamp);
+
+        let mut properties = HashMap::new();
+        for (tag, property) in [
+            ("TITLE", "Name"),
+            ("ARTIST", "Artist"),
+            ("CREATOR", "Charter"),
+            ("GENRE", "Genre"),
+            ("YEAR", "Year"),
+            ("MP3", "MusicStream"),
+        ] {
+            if let Some(value) = self.get_tag(tag) {
+                properties.insert(property.to_string(), format!("\"{}\"", value));
+            }
+        }
+        properties.insert("Offset".to_string(), "0".to_string());
+        properties.insert("Resolution".to_string(), resolution.to_string());
+
+        let tempo_map = vec![
+            TempoEvent::TimeSignature {
+                timestamp: 0,
+                time_signature: (4, 4),
+            },
+            TempoEvent::Beat {
+                timestamp: 0,
+                milli_bpm: self.milli_bpm,
+            },
+        ];
+        Chart::from_parts(properties, lyrics, tempo_map, HashMap::new())
+    }
+
+    /// Constructor for `UltraStarSong` from the phrases of a chart.
+    ///
+    /// The song uses the chart's starting tempo, with `#GAP` at the first phrase, so beats follow
+    /// the song time of every syllable even when the chart changes tempo. Syllables last until
+    /// the next syllable or the end of their phrase, and all pitches are 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Read;
+    /// use duet_charter_lib::chart::Chart;
+    /// use duet_charter_lib::ultrastar::UltraStarSong;
+    ///
+    /// let mut file_content = String::new();
+    /// fs::File::open("../charts/Adagio - Second Sight [Peddy]/notes.chart")
+    ///     .unwrap()
+    ///     .read_to_string(&mut file_content)
+    ///     .expect("file reading failed");
+    ///
+    /// let chart = Chart::new(&file_content).unwrap();
+    /// let txt_file = UltraStarSong::from_chart(&chart).to_string();
+    /// ```
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub fn from_chart(chart: &Chart) -> Self {
+        let tempo_map = TempoMap::from_chart(chart);
+        let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+        let milli_bpm = tempo_map.milli_bpm_at(0);
+        let gap_micros = [Singer::Main, Singer::Duet]
+            .iter()
+            .flat_map(|singer| phrases.get_phrases(*singer))
+            .filter(|phrase| !phrase.get_lyrics().is_empty())
+            .map(|phrase| tempo_map.ticks_to_micros(phrase.get_start_timestamp()))
+            .min()
+            .map_or(0, |micros| (micros + 500) / 1000 * 1000);
+        let beat = |timestamp: u32| {
+            let micros = tempo_map.ticks_to_micros_f64(timestamp) - gap_micros as f64;
+            (micros * milli_bpm as f64 * 4.0 / 60_000_000_000.0)
+                .round()
+                .max(0.0) as u32
+        };
+
+        let mut lines = vec![];
+        for singer in [Singer::Main, Singer::Duet] {
+            for phrase in phrases.get_phrases(singer) {
+                let lyrics = phrase.get_lyrics();
+                let end_beat = beat(phrase.get_end_timestamp());
+                let mut notes = vec![];
+                let mut word_start = false;
+                for (i, lyric) in lyrics.iter().enumerate() {
+                    let start_beat = beat(lyric.get_timestamp());
+                    let next_beat = lyrics
+                        .get(i + 1)
+                        .map_or(end_beat, |next| beat(next.get_timestamp()));
+                    let (syllable, note_type, joined) = Self::split_lyric(lyric.get_text());
+                    let syllable = if syllable == "+" { "~" } else { syllable };
+                    notes.push(UltraStarNote {
+                        note_type,
+                        start_beat,
+                        length: next_beat.saturating_sub(start_beat).max(1),
+                        pitch: 0,
+                        text: if word_start {
+                            format!(" {}", syllable)
+                        } else {
+                            syllable.to_string()
+                        },
+                    });
+                    word_start = !joined;
+                }
+                if !notes.is_empty() {
+                    lines.push(UltraStarLine { singer, notes });
+                }
+            }
+        }
+
+        let mut tags = vec![];
+        for (property, tag) in [
+            ("Name", "TITLE"),
+            ("Artist", "ARTIST"),
+            ("Charter", "CREATOR"),
+            ("Genre", "GENRE"),
+            ("Year", "YEAR"),
+            ("MusicStream", "MP3"),
+        ] {
+            if let Some(value) = chart.get_properties().get(property) {
+                let value = value.trim().trim_matches('"').trim_start_matches(", ").trim();
+                if !value.is_empty() {
+                    tags.push((tag.to_string(), value.to_string()));
+                }
+            }
+        }
+        Self {
+            tags,
+            milli_bpm,
+            gap_micros,
+            lines,
+        }
+    }
+
+    /// Splits a chart lyric into its text, the note type its suffixes stand for, and whether it
+    /// is joined to the next syllable.
+    fn split_lyric(text: &str) -> (&str, UltraStarNoteType, bool) {
+        let mut text = text;
+        let mut note_type = UltraStarNoteType::Normal;
+        let mut joined = false;
+        loop {
+            if let Some(rest) = text.strip_suffix('-') {
+                joined = true;
+                text = rest;
+            } else if let Some(rest) = text.strip_suffix('#') {
+                note_type = UltraStarNoteType::Rap;
+                text = rest;
+            } else if let Some(rest) = text.strip_suffix('^') {
+                note_type = UltraStarNoteType::Freestyle;
+                text = rest;
+            } else {
+                return (text, note_type, joined);
+            }
+        }
+    }
+
+    fn format_thousandths(value: u64) -> String {
+        if value.is_multiple_of(1000) {
+            (value / 1000).to_string()
+        } else {
+            format!("{}.{:03}", value / 1000, value % 1000)
+                .trim_end_matches('0')
+                .to_string()
+        }
+    }
+}
+
+impl Display for UltraStarSong {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "#{}:{}", name, value)?;
+        }
+        writeln!(f, "#BPM:{}", Self::format_thousandths(self.milli_bpm))?;
+        writeln!(f, "#GAP:{}", Self::format_thousandths(self.gap_micros))?;
+        let duet = self.lines.iter().any(|line| line.singer == Singer::Duet);
+        for (player, singer) in [(1, Singer::Main), (2, Singer::Duet)] {
+            let lines: Vec<&UltraStarLine> = self
+                .lines
+                .iter()
+                .filter(|line| line.singer == singer)
+                .collect();
+            if duet {
+                writeln!(f, "P{}", player)?;
+            }
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    let previous_end = lines[i - 1]
+                        .notes
+                        .last()
+                        .map_or(0, UltraStarNote::get_end_beat);
+                    let next_start = line.notes.first().map_or(0, UltraStarNote::get_start_beat);
+                    writeln!(f, "- {}", previous_end.min(next_start))?;
+                }
+                for note in &line.notes {
+                    writeln!(
+                        f,
+                        "{} {} {} {} {}",
+                        note.note_type.marker(),
+                        note.start_beat,
+                        note.length,
+                        note.pitch,
+                        note.text
+                    )?;
+                }
+            }
+        }
+        writeln!(f, "E")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Read;
+
+    use super::*;
+
+    const EXAMPLE: &str = "#TITLE:Test Song\n#ARTIST:Someone\n#MP3:song.ogg\n#BPM:300\n\
+                           #GAP:1000\nP1\n: 0 2 5 Hel\n: 2 2 5 lo\n* 4 4 7  world\n- 8\n\
+                           R 8 2 0 yeah\nP2\nF 10 2 0 hey\n: 12 2 0 ~\nE\n";
+
+    #[test]
+    fn import() -> Result<()> {
+        let song = UltraStarSong::new(EXAMPLE)?;
+        assert_eq!(song.get_milli_bpm(), 300_000);
+        assert_eq!(song.get_gap_micros(), 1_000_000);
+        assert_eq!(song.get_lines().len(), 3);
+        assert_eq!(song.get_lines()[2].get_singer(), Singer::Duet);
+
+        let chart = song.to_chart(192);
+        assert_eq!(chart.get_properties()["Name"], "\"Test Song\"");
+        let tempo_map = TempoMap::from_chart(&chart);
+        assert_eq!(tempo_map.ticks_to_micros(960), 1_000_000);
+        let lyrics: Vec<(u32, &str)> = chart
+            .get_lyrics()
+            .iter()
+            .filter_map(|event| match event {
+                LyricEvent::Lyric { timestamp, text }
+                | LyricEvent::DuetLyric { timestamp, text } => Some((*timestamp, text.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            lyrics,
+            vec![
+                (960, "Hel-"),
+                (1056, "lo"),
+                (1152, "world"),
+                (1344, "yeah#"),
+                (1440, "hey^-"),
+                (1536, "+"),
+            ]
+        );
+        assert!(chart
+            .get_lyrics()
+            .contains(&LyricEvent::PhraseEnd { timestamp: 1344 }));
+        Ok(())
+    }
+
+    #[test]
+    fn relative_import() -> Result<()> {
+        let song =
+            UltraStarSong::new("#BPM:120,5\n#RELATIVE:yes\n: 0 2 0 a\n- 4 6\n: 0 2 0 b\nE\n")?;
+        assert_eq!(song.get_milli_bpm(), 120_500);
+        assert_eq!(song.get_lines()[1].get_notes()[0].get_start_beat(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn export() -> Result<()> {
+        let song = UltraStarSong::new(EXAMPLE)?;
+        let exported = UltraStarSong::from_chart(&song.to_chart(192));
+        assert_eq!(
+            exported.to_string(),
+            "#TITLE:Test Song\n#ARTIST:Someone\n#MP3:song.ogg\n#BPM:300\n#GAP:1000\nP1\n\
+             : 0 2 0 Hel\n: 2 2 0 lo\n: 4 4 0  world\n- 8\nR 8 2 0 yeah\nP2\nF 10 2 0 hey\n\
+             : 12 2 0 ~\nE\n"
+        );
+        assert_eq!(
+            UltraStarSong::new(&exported.to_string())?.to_chart(192),
+            exported.to_chart(192)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chart_round_trip() -> Result<()> {
+        let dir: Vec<_> = fs::read_dir("../charts/")?.collect();
+        for folder in dir {
+            let entry = folder?;
+            chart_round_trip_helper(&entry).wrap_err(format!(
+                "Error occurred for chart file {}",
+                &entry.file_name().to_str().unwrap_or("filename failure")
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn chart_round_trip_helper(folder: &fs::DirEntry) -> Result<()> {
+        let mut path = folder.path();
+        path.push("notes");
+        path.set_extension("chart");
+        let mut file = fs::File::open(&path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        let chart = Chart::new(&file_content)?;
+        let song = UltraStarSong::from_chart(&chart);
+        let reimported = UltraStarSong::new(&song.to_string())?;
+        assert_eq!(reimported, song);
+        Ok(())
+    }
+}
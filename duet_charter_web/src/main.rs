@@ -2,18 +2,27 @@ use std::collections::HashMap;
 
 use eyre::{ErrReport, eyre, Result};
 use gloo::file::callbacks::{FileReader, read_as_text};
-use gloo::file::File;
+use gloo::file::{Blob, File, ObjectUrl};
 use web_sys::{console, HtmlInputElement};
 use yew::prelude::*;
 
 use duet_charter_lib::chart::Chart;
 
-use duet_charter_lib::phrases::LyricPhraseCollection;
+use duet_charter_lib::difficulty::{chart_difficulties, DensityCurve, TrackDifficulty};
+use duet_charter_lib::duet_analysis::DuetAnalysis;
+use duet_charter_lib::pacing::{PacingReport, PacingThresholds};
+use duet_charter_lib::phrases::{LyricPhraseCollection, Phrase, Singer};
+use duet_charter_lib::scoring::ScoringTrack;
+use duet_charter_lib::star_power_path::{chart_paths, StarPowerPath, DEFAULT_HIT_WINDOW_MICROS};
+use duet_charter_lib::structure::SongStructure;
+use duet_charter_lib::timing::TempoMap;
 
 enum Msg {
     Files(Result<Vec<File>>),
     Loaded(String, String),
     Parsed(),
+    LyricsChanged(),
+    AssignSection(usize, Singer),
 }
 
 struct Main {
@@ -21,6 +30,86 @@ struct Main {
     chart: Option<Chart>,
     error: Option<ErrReport>,
     phrases: Option<LyricPhraseCollection>,
+    structure: Option<SongStructure>,
+    pacing: Option<PacingReport>,
+    duet_analysis: Option<DuetAnalysis>,
+    difficulties: Option<Vec<TrackDifficulty>>,
+    paths: Option<Vec<(ScoringTrack, StarPowerPath)>>,
+    download: Option<ObjectUrl>,
+}
+
+impl Main {
+    /// Lists a singer's phrases, highlighting the ones with pacing issues and naming the issues
+    /// on hover.
+    fn phrase_list(&self, phrases: &[Phrase], singer: Singer) -> Html {
+        html! {
+            <ul>
+                { for phrases.iter().enumerate().map(|(index, phrase)| {
+                    let issues: Vec<String> = self
+                        .pacing
+                        .as_ref()
+                        .and_then(|pacing| pacing.get_phrases(singer).get(index))
+                        .map(|pacing| pacing.get_issues().iter().map(ToString::to_string).collect())
+                        .unwrap_or_default();
+                    if issues.is_empty() {
+                        html!{ <li> { format!("{}", phrase) } </li> }
+                    } else {
+                        html!{ <li style="background-color: #fdd" title={ issues.join(", ") }> { format!("{} ({})", phrase, issues.join(", ")) } </li> }
+                    }
+                }) }
+            </ul>
+        }
+    }
+
+    /// Plots a notes-per-second curve as an SVG line, time on the x axis.
+    fn density_plot(curve: &DensityCurve) -> Html {
+        const WIDTH: f64 = 800.0;
+        const HEIGHT: f64 = 150.0;
+        let seconds = curve.get_points().last().map_or(0.0, |point| point.get_seconds()).max(1.0);
+        let peak = curve.peak().max(1.0);
+        let points: Vec<String> = curve
+            .get_points()
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", point.get_seconds() / seconds * WIDTH, HEIGHT - point.get_nps() / peak * HEIGHT))
+            .collect();
+        html! {
+            <svg width={ WIDTH.to_string() } height={ HEIGHT.to_string() } style="border: 1px solid #ccc">
+                <polyline points={ points.join(" ") } fill="none" stroke="#36c" stroke-width="1.5"/>
+            </svg>
+        }
+    }
+
+    /// Draws a track as a strip with time on the x axis: chords as ticks, star power phrases
+    /// shaded blue and the activations of its path shaded green, each marked where it starts.
+    fn highway(track: &ScoringTrack, path: &StarPowerPath) -> Html {
+        const WIDTH: f64 = 800.0;
+        const HEIGHT: f64 = 40.0;
+        let tempo_map = track.get_tempo_map();
+        let seconds = track
+            .get_chords()
+            .last()
+            .map_or(0.0, |chord| tempo_map.ticks_to_seconds(chord.get_end_timestamp()))
+            .max(1.0);
+        let x = |tick: u32| tempo_map.ticks_to_seconds(tick) / seconds * WIDTH;
+        html! {
+            <svg width={ WIDTH.to_string() } height={ HEIGHT.to_string() } style="border: 1px solid #ccc">
+                { for track.get_star_power_phrases().iter().map(|(start, duration)| html!{
+                    <rect x={ format!("{:.1}", x(*start)) } y="0" width={ format!("{:.1}", (x(start + duration) - x(*start)).max(1.0)) } height={ HEIGHT.to_string() } fill="#cde"/>
+                }) }
+                { for path.get_activations().iter().map(|activation| html!{
+                    <>
+                        <rect x={ format!("{:.1}", x(activation.get_start())) } y="0" width={ format!("{:.1}", x(activation.get_squeezed_end()) - x(activation.get_start())) } height={ HEIGHT.to_string() } fill="#6c6" fill-opacity="0.4"/>
+                        <line x1={ format!("{:.1}", x(activation.get_start())) } y1="0" x2={ format!("{:.1}", x(activation.get_start())) } y2={ HEIGHT.to_string() } stroke="#060" stroke-width="2">
+                            <title>{ activation.to_string() }</title>
+                        </line>
+                    </>
+                }) }
+                { for track.get_chords().iter().map(|chord| html!{
+                    <line x1={ format!("{:.1}", x(chord.get_timestamp())) } y1="15" x2={ format!("{:.1}", x(chord.get_timestamp())) } y2="25" stroke="#333" stroke-width="0.5"/>
+                }) }
+            </svg>
+        }
+    }
 }
 
 impl Component for Main {
@@ -34,6 +123,12 @@ impl Component for Main {
             chart: None,
             error: None,
             phrases: None,
+            structure: None,
+            pacing: None,
+            duet_analysis: None,
+            difficulties: None,
+            paths: None,
+            download: None,
         }
     }
 
@@ -74,13 +169,44 @@ impl Component for Main {
                 };
                 true
             }
+            // note-derived results, computed once per file as the lyric editing leaves notes alone
             Msg::Parsed() => match &self.chart {
                 None => false,
                 Some(chart) => {
-                    self.phrases = Some(LyricPhraseCollection::new(chart.get_lyrics()));
+                    self.difficulties = Some(chart_difficulties(chart));
+                    self.paths = Some(
+                        chart_paths(chart, DEFAULT_HIT_WINDOW_MICROS)
+                            .into_iter()
+                            .filter_map(|path| Some((ScoringTrack::from_chart(chart, path.get_name())?, path)))
+                            .collect(),
+                    );
+                    link.send_message(Msg::LyricsChanged());
+                    false
+                }
+            },
+            Msg::LyricsChanged() => match &self.chart {
+                None => false,
+                Some(chart) => {
+                    let phrases = LyricPhraseCollection::new(chart.get_lyrics());
+                    let structure = SongStructure::from_phrases(chart.get_lyrics(), &phrases);
+                    let tempo_map = TempoMap::from_chart(chart);
+                    self.pacing = Some(PacingReport::new(&phrases, &tempo_map, &PacingThresholds::default()));
+                    self.duet_analysis = Some(DuetAnalysis::new(&phrases, &structure, &tempo_map));
+                    self.structure = Some(structure);
+                    self.phrases = Some(phrases);
+                    self.download = Some(ObjectUrl::from(Blob::new(chart.to_string().as_str())));
                     true
                 }
             },
+            Msg::AssignSection(index, singer) => {
+                if let (Some(chart), Some(structure)) = (&mut self.chart, &self.structure) {
+                    if let Some(section) = structure.get_sections().get(index) {
+                        section.assign_to(chart.get_lyrics_mut(), singer);
+                        link.send_message(Msg::LyricsChanged());
+                    }
+                }
+                false
+            }
         }
     }
 
@@ -119,6 +245,18 @@ impl Component for Main {
                                 if self.phrases.is_some() {
                                     <li><a href="#phrases">{ "Phrases" }</a></li>
                                 }
+                                if self.structure.is_some() {
+                                    <li><a href="#sections">{ "Sections" }</a></li>
+                                }
+                                if self.duet_analysis.is_some() {
+                                    <li><a href="#duet_analysis">{ "Duet analysis" }</a></li>
+                                }
+                                if self.difficulties.is_some() {
+                                    <li><a href="#difficulty">{ "Difficulty" }</a></li>
+                                }
+                                if self.paths.is_some() {
+                                    <li><a href="#star_power">{ "Star power" }</a></li>
+                                }
                             </ol>
                         </section>
                         <section id = "properties">
@@ -155,18 +293,85 @@ impl Component for Main {
                     <section id = "phrases">
                         <h1>{ "Main phrases:" }</h1>
                         <a href="#toc">{ "^" }</a>
-                        <ul>
-                            { for phrases.get_main_phrases().iter().map(|event| html!{ <li> { format!("{}", event) } </li> }) }
-                        </ul>
+                        { self.phrase_list(phrases.get_main_phrases(), Singer::Main) }
                     </section>
                     <section id = "duet_phrases">
                         <h1>{ "Duet phrases:" }</h1>
                         <a href="#toc">{ "^" }</a>
+                        { self.phrase_list(phrases.get_duet_phrases(), Singer::Duet) }
+                    </section>
+                }
+                if let Some(structure) = &self.structure {
+                    <section id = "sections">
+                        <h1>{ "Sections:" }</h1>
+                        <a href="#toc">{ "^" }</a>
+                        if let Some(url) = &self.download {
+                            <p><a href={ url.to_string() } download="notes.chart">{ "Download chart" }</a></p>
+                        }
+                        { for structure.get_sections().iter().enumerate().map(|(index, section)| html!{
+                            <section>
+                                <h2>{ section.get_name().unwrap_or("No section") }</h2>
+                                <p>
+                                    { format!("main: {} phrases, duet: {} phrases ", section.phrase_count(Singer::Main), section.phrase_count(Singer::Duet)) }
+                                    <button onclick={ ctx.link().callback(move |_| Msg::AssignSection(index, Singer::Main)) }>{ "Assign to main" }</button>
+                                    <button onclick={ ctx.link().callback(move |_| Msg::AssignSection(index, Singer::Duet)) }>{ "Assign to duet" }</button>
+                                </p>
+                                <ul>
+                                    { for section.get_phrases(Singer::Main).iter().map(|phrase| html!{ <li> { format!("main: {}", phrase) } </li> }) }
+                                    { for section.get_phrases(Singer::Duet).iter().map(|phrase| html!{ <li> { format!("duet: {}", phrase) } </li> }) }
+                                </ul>
+                            </section>
+                        }) }
+                    </section>
+                }
+                if let Some(analysis) = &self.duet_analysis {
+                    <section id = "duet_analysis">
+                        <h1>{ "Duet analysis:" }</h1>
+                        <a href="#toc">{ "^" }</a>
                         <ul>
-                            { for phrases.get_duet_phrases().iter().map(|event| html!{ <li> { format!("{}", event) } </li> }) }
+                            { for [Singer::Main, Singer::Duet].into_iter().map(|singer| html!{
+                                <li> { format!("{}: {:.1}% of sung time, {} syllables ({:.1}%)", singer, analysis.time_percentage(singer), analysis.syllables(singer), analysis.syllable_percentage(singer)) } </li>
+                            }) }
+                            if let Some(stretch) = analysis.get_longest_stretch() {
+                                <li> { format!("longest stretch without a switch: {} sings {} phrases for {:.1}s, from tick {} to {}", stretch.get_singer(), stretch.get_phrase_count(), stretch.get_micros() as f64 / 1_000_000.0, stretch.get_start_timestamp(), stretch.get_end_timestamp()) } </li>
+                            }
+                            { for analysis.get_overlaps().iter().map(|overlap| html!{
+                                <li style="background-color: #fdd"> { format!("main phrase at {} overlaps duet phrase at {} from tick {} to {}", overlap.get_main_phrase_start(), overlap.get_duet_phrase_start(), overlap.get_start_timestamp(), overlap.get_end_timestamp()) } </li>
+                            }) }
+                            { for analysis.get_absences().iter().map(|absence| html!{
+                                <li> { format!("{} has no {} phrases", absence.get_name().unwrap_or("No section"), absence.get_absent()) } </li>
+                            }) }
                         </ul>
                     </section>
                 }
+                if let Some(difficulties) = &self.difficulties {
+                    <section id = "difficulty">
+                        <h1>{ "Difficulty:" }</h1>
+                        <a href="#toc">{ "^" }</a>
+                        { for difficulties.iter().map(|track| html!{
+                            <section>
+                                <h2>{ format!("{}: tier {} ({:.2})", track.get_name(), track.get_tier(), track.get_score()) }</h2>
+                                <p>{ format!("peak {} nps, {:.2} extra gems per note, {:.0}% HOPOs and taps, {:.0}% sustained", track.get_peak_nps(), track.get_chord_complexity(), track.get_hopo_tap_ratio() * 100.0, track.get_sustain_load() * 100.0) }</p>
+                                { Self::density_plot(track.get_curve()) }
+                            </section>
+                        }) }
+                    </section>
+                }
+                if let Some(paths) = &self.paths {
+                    <section id = "star_power">
+                        <h1>{ "Star power:" }</h1>
+                        <a href="#toc">{ "^" }</a>
+                        { for paths.iter().map(|(track, path)| html!{
+                            <section>
+                                <h2>{ format!("{}: {} points, +{} over no star power", path.get_name(), path.get_score(), path.get_score() - path.get_no_star_power_score()) }</h2>
+                                { Self::highway(track, path) }
+                                <ol>
+                                    { for path.get_activations().iter().map(|activation| html!{ <li> { activation.to_string() } </li> }) }
+                                </ol>
+                            </section>
+                        }) }
+                    </section>
+                }
                 if let Some(err) = &self.error {
                     <>
                         <h1>{ "Error:"}</h1>